@@ -0,0 +1,108 @@
+// Rustic - accessibility lint for rendered pages
+
+use anyhow::Result;
+use lol_html::{element, end_tag, rewrite_str, text, RewriteStrSettings};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// State of the `<a>` element currently being scanned.
+#[derive(Debug, Default)]
+struct Link {
+    href: String,
+    has_label: bool,
+}
+
+/// Lists accessibility problems in a page: images without alt text, skipped heading
+/// levels, links without text and a missing `lang` attribute.
+pub fn audit(html: &str) -> Result<Vec<String>> {
+    let problems = Rc::new(RefCell::new(Vec::new()));
+    let has_lang = RefCell::new(false);
+    let last_heading = RefCell::new(0u8);
+    let link: Rc<RefCell<Option<Link>>> = Rc::default();
+
+    rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("html", |el| {
+                *has_lang.borrow_mut() = el.get_attribute("lang").is_some_and(|l| !l.trim().is_empty());
+                Ok(())
+            }))
+            .append_element_content_handler(element!("img", |el| {
+                if el.get_attribute("alt").is_none() {
+                    let src = el.get_attribute("src").unwrap_or_default();
+                    problems.borrow_mut().push(format!("Image without alt text: {}", src));
+                }
+                if let Some(link) = link.borrow_mut().as_mut() {
+                    link.has_label |= el.get_attribute("alt").is_some_and(|a| !a.trim().is_empty());
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("h1, h2, h3, h4, h5, h6", |el| {
+                let level = el.tag_name()[1..].parse::<u8>().unwrap_or(1);
+                let previous = last_heading.replace(level);
+                if previous > 0 && level > previous + 1 {
+                    problems
+                        .borrow_mut()
+                        .push(format!("Heading level skipped: <h{}> follows <h{}>", level, previous));
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("a[href]", |el| {
+                let labelled = ["aria-label", "aria-labelledby", "title"]
+                    .iter()
+                    .any(|a| el.get_attribute(a).is_some_and(|v| !v.trim().is_empty()));
+                *link.borrow_mut() = Some(Link {
+                    href: el.get_attribute("href").unwrap_or_default(),
+                    has_label: labelled,
+                });
+
+                let link = Rc::clone(&link);
+                let problems = Rc::clone(&problems);
+                el.on_end_tag(end_tag!(move |_| {
+                    if let Some(link) = link.borrow_mut().take().filter(|l| !l.has_label) {
+                        problems.borrow_mut().push(format!("Link without text: {}", link.href));
+                    }
+                    Ok(())
+                }))?;
+                Ok(())
+            }))
+            .append_element_content_handler(text!("a[href]", |chunk| {
+                if let Some(link) = link.borrow_mut().as_mut() {
+                    link.has_label |= !chunk.as_str().trim().is_empty();
+                }
+                Ok(())
+            })),
+    )?;
+
+    let mut problems = problems.take();
+    if !has_lang.into_inner() {
+        problems.insert(0, "Missing lang attribute on <html>".to_string());
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_finds_problems() {
+        // Arrange
+        let html = r#"<html><body><h1>T</h1><h3>S</h3><img src="a.png"><img src="b.png" alt="">
+            <a href="/x"></a><a href="/y">Y</a><a href="/z"><img src="z.png" alt="Zed"></a></body></html>"#;
+
+        // Act
+        let problems = audit(html).unwrap();
+
+        // Assert
+        assert_eq!(
+            problems,
+            [
+                "Missing lang attribute on <html>",
+                "Heading level skipped: <h3> follows <h1>",
+                "Image without alt text: a.png",
+                "Link without text: /x",
+            ]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+// Rustic - static ActivityPub actor, outbox and WebFinger files so posts can be followed
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dates::parse_date;
+use crate::feed::absolute_url;
+use crate::listing::{Listing, PageListing};
+
+/// Where the actor and its outbox are published, relative to the output directory.
+const ACTOR_PATH: &str = "activitypub/actor.json";
+const OUTBOX_PATH: &str = "activitypub/outbox.json";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// Settings for the read-only fediverse actor, declared under `[activitypub]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+pub struct ActivityPubConfig {
+    /// Account name, so the site is followed as `@<username>@<host>`
+    pub username: String,
+    /// Display name; defaults to the site title
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    /// Avatar image URL, site-relative or absolute
+    pub icon: Option<String>,
+    /// PEM file with the actor's public key, which servers need to verify it
+    pub public_key: Option<PathBuf>,
+    /// Only post pages from these sections; every dated page when empty
+    #[serde(default)]
+    pub sections: Vec<String>,
+    /// Most posts listed in the outbox, newest first
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// Writes the WebFinger file, the actor and its outbox of the newest dated pages.
+pub fn write(config: &ActivityPubConfig, site_title: Option<&str>, base_url: &str, listing: &Listing, out_dir: &Path) -> Result<()> {
+    let Some(host) = base_url.split("://").nth(1).and_then(|rest| rest.split('/').next()).filter(|h| !h.is_empty()) else {
+        bail!("ActivityPub needs `base_url` to be set in rustic.toml");
+    };
+    let actor_id = absolute_url(base_url, ACTOR_PATH);
+
+    let webfinger = json!({
+        "subject": format!("acct:{}@{}", config.username, host),
+        "aliases": [actor_id],
+        "links": [{ "rel": "self", "type": "application/activity+json", "href": actor_id }],
+    });
+    write_json(&out_dir.join(".well-known/webfinger"), &webfinger)?;
+
+    let mut actor = json!({
+        "@context": ["https://www.w3.org/ns/activitystreams", "https://w3id.org/security/v1"],
+        "id": actor_id,
+        "type": "Person",
+        "preferredUsername": config.username,
+        "name": config.name.as_deref().or(site_title).unwrap_or(&config.username),
+        "summary": config.summary,
+        "url": absolute_url(base_url, "/"),
+        "outbox": absolute_url(base_url, OUTBOX_PATH),
+        // A static site can't receive activities, but servers expect an inbox
+        "inbox": absolute_url(base_url, "activitypub/inbox"),
+        "manuallyApprovesFollowers": false,
+        "discoverable": true,
+    });
+    if let Some(icon) = &config.icon {
+        actor["icon"] = json!({ "type": "Image", "url": absolute_url(base_url, icon) });
+    }
+    if let Some(path) = &config.public_key {
+        let pem = fs::read_to_string(path).with_context(|| format!("Failed to read ActivityPub public key: {:?}", path))?;
+        actor["publicKey"] = json!({ "id": format!("{}#main-key", actor_id), "owner": actor_id, "publicKeyPem": pem });
+    }
+    write_json(&out_dir.join(ACTOR_PATH), &actor)?;
+
+    let mut posts: Vec<&PageListing> = listing
+        .pages
+        .iter()
+        .filter(|page| config.sections.is_empty() || page.section.as_ref().is_some_and(|s| config.sections.contains(s)))
+        .filter(|page| page.date.as_deref().and_then(parse_date).is_some())
+        .collect();
+    posts.sort_by_key(|page| std::cmp::Reverse(page.date.as_deref().and_then(parse_date)));
+    posts.truncate(config.limit);
+    let items: Vec<Value> = posts.iter().map(|page| activity(page, &actor_id, base_url)).collect();
+    let outbox = json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": absolute_url(base_url, OUTBOX_PATH),
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items,
+    });
+    write_json(&out_dir.join(OUTBOX_PATH), &outbox)?;
+    println!("Generated ActivityPub actor @{}@{} with {} post(s)", config.username, host, posts.len());
+    Ok(())
+}
+
+/// The `Create` activity announcing a page as an article.
+fn activity(page: &PageListing, actor_id: &str, base_url: &str) -> Value {
+    let url = absolute_url(base_url, &page.url);
+    let published = page.date.as_deref().and_then(parse_date).map(|date| date.to_rfc3339());
+    json!({
+        "id": format!("{}#create", url),
+        "type": "Create",
+        "actor": actor_id,
+        "published": published,
+        "to": [PUBLIC],
+        "object": {
+            "id": url,
+            "type": "Article",
+            "attributedTo": actor_id,
+            "name": page.title,
+            "summary": page.description,
+            "content": page.description.as_deref().unwrap_or(&page.title),
+            "url": url,
+            "published": published,
+            "updated": page.updated,
+            "to": [PUBLIC],
+        },
+    })
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", path))?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value)?).with_context(|| format!("Failed to write {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity_wraps_page_in_create() {
+        // Arrange
+        let page = PageListing {
+            title: "Hello".to_string(),
+            url: "/blog/hello.html".to_string(),
+            date: Some("2024-03-01".to_string()),
+            description: Some("First post".to_string()),
+            lang: "en".to_string(),
+            section: Some("blog".to_string()),
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: Default::default(),
+            menu: None,
+            sitemap: None,
+        };
+
+        // Act
+        let activity = activity(&page, "https://example.com/activitypub/actor.json", "https://example.com");
+
+        // Assert
+        assert_eq!(activity["type"], "Create");
+        assert_eq!(activity["object"]["id"], "https://example.com/blog/hello.html");
+        assert_eq!(activity["object"]["published"], "2024-03-01T00:00:00+00:00");
+    }
+}
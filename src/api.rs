@@ -0,0 +1,133 @@
+// Rustic - static JSON endpoints mirroring the section listings and taxonomies
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+use crate::listing::{Listing, PageListing};
+use crate::taxonomies::{self, TaxonomyConfig};
+
+/// Settings for the JSON endpoints, declared under `[api]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Pages per section endpoint
+    pub per_page: usize,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self { per_page: 10 }
+    }
+}
+
+/// One page of a section's listing, as written to `/api/<section>/page/<n>.json`.
+#[derive(Debug, Serialize)]
+struct ListingPage<'a> {
+    section: &'a str,
+    page: usize,
+    total_pages: usize,
+    total_items: usize,
+    /// URL of the previous and next endpoint, for "load more" buttons
+    prev: Option<String>,
+    next: Option<String>,
+    items: &'a [&'a PageListing],
+}
+
+fn page_url(section: &str, page: usize) -> String {
+    format!("/api/{}/page/{}.json", section, page)
+}
+
+/// Splits each section's listing into numbered endpoints, in the section's sort order.
+fn section_pages<'a>(section: &'a str, pages: &'a [&'a PageListing], per_page: usize) -> Vec<ListingPage<'a>> {
+    let chunks: Vec<_> = pages.chunks(per_page.max(1)).collect();
+    let total_pages = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, items)| ListingPage {
+            section,
+            page: i + 1,
+            total_pages,
+            total_items: pages.len(),
+            prev: (i > 0).then(|| page_url(section, i)),
+            next: (i + 1 < total_pages).then(|| page_url(section, i + 2)),
+            items,
+        })
+        .collect()
+}
+
+/// Writes the section and taxonomy term endpoints under `/api`, and `/api/index.json`
+/// listing them all.
+pub fn write(config: &ApiConfig, taxonomies: &[TaxonomyConfig], listing: &Listing, out_dir: &Path) -> Result<()> {
+    let mut index_sections = Vec::new();
+    for (section, pages) in listing.sections() {
+        let endpoints = section_pages(section, &pages, config.per_page);
+        for endpoint in &endpoints {
+            write_json(out_dir, &page_url(section, endpoint.page), endpoint)?;
+        }
+        index_sections.push(json!({ "name": section, "total_items": pages.len(), "url": page_url(section, 1) }));
+    }
+
+    let mut index_taxonomies = serde_json::Map::new();
+    for (taxonomy, terms) in taxonomies::collect(taxonomies, listing) {
+        let mut index_terms = Vec::new();
+        for term in &terms {
+            let url = format!("/api/{}/{}.json", taxonomy, term.slug);
+            write_json(out_dir, &url, &json!({ "taxonomy": taxonomy, "term": term.name, "items": term.pages }))?;
+            index_terms.push(json!({ "name": term.name, "total_items": term.pages.len(), "url": url }));
+        }
+        index_taxonomies.insert(taxonomy, index_terms.into());
+    }
+
+    write_json(out_dir, "/api/index.json", &json!({ "sections": index_sections, "taxonomies": index_taxonomies }))
+}
+
+fn write_json(out_dir: &Path, url: &str, value: &impl Serialize) -> Result<()> {
+    let path = out_dir.join(url.trim_start_matches('/'));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", path))?;
+    }
+    fs::write(&path, serde_json::to_string(value)?).with_context(|| format!("Failed to write endpoint: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_pages() {
+        // Arrange
+        let listing = |n: usize| PageListing {
+            title: format!("Post {}", n),
+            url: format!("/posts/{}.html", n),
+            date: None,
+            description: None,
+            lang: "en".to_string(),
+            section: Some("posts".to_string()),
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: Default::default(),
+            menu: None,
+            sitemap: None,
+        };
+        let posts: Vec<PageListing> = (1..=5).map(listing).collect();
+        let refs: Vec<&PageListing> = posts.iter().collect();
+
+        // Act
+        let pages = section_pages("posts", &refs, 2);
+
+        // Assert
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].prev, None);
+        assert_eq!(pages[0].next.as_deref(), Some("/api/posts/page/2.json"));
+        assert_eq!(pages[1].prev.as_deref(), Some("/api/posts/page/1.json"));
+        assert_eq!(pages[2].items.len(), 1);
+        assert_eq!(pages[2].next, None);
+    }
+}
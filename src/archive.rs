@@ -0,0 +1,122 @@
+// Rustic - reproducible .tar.gz or .zip archive of the built site
+
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::dates::source_date_epoch;
+use crate::zip::ZipWriter;
+
+/// Packs the output directory into `archive`, a `.tar.gz`, `.tgz` or `.zip` file.
+///
+/// Entries are sorted by path and carry fixed owners, permissions and timestamps
+/// (`SOURCE_DATE_EPOCH` if set), so the same output always gives the same bytes.
+pub fn write(out_dir: &Path, archive: &Path) -> Result<()> {
+    let name = archive.to_string_lossy();
+    let is_zip = name.ends_with(".zip");
+    if !is_zip && !name.ends_with(".tar.gz") && !name.ends_with(".tgz") {
+        bail!("Unsupported archive {:?}; use a .tar.gz, .tgz or .zip file name", archive);
+    }
+    let archive_path = archive.canonicalize().ok();
+
+    let mut entries = Vec::new();
+    for entry in WalkDir::new(out_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("Failed to read output directory: {:?}", out_dir))?;
+        // An archive written into the output directory isn't part of the site
+        if archive_path.is_some() && entry.path().canonicalize().ok() == archive_path {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(out_dir)?.to_string_lossy().replace('\\', "/");
+        entries.push((relative, entry.file_type().is_dir(), entry.path().to_path_buf()));
+    }
+
+    let file = File::create(archive).with_context(|| format!("Failed to create archive: {:?}", archive))?;
+    let out = BufWriter::new(file);
+    if is_zip {
+        let mut zip = ZipWriter::new(out);
+        for (name, is_dir, path) in &entries {
+            if !is_dir {
+                zip.add(name, &fs::read(path)?, true)?;
+            }
+        }
+        zip.finish()?.flush()?;
+    } else {
+        let mtime = source_date_epoch()?.map_or(0, |date| date.timestamp().max(0) as u64);
+        // A zero gzip timestamp, so the compressed stream is reproducible too
+        let mut gz = GzEncoder::new(out, Compression::default());
+        for (name, is_dir, path) in &entries {
+            if *is_dir {
+                gz.write_all(&tar_header(&format!("{}/", name), 0, mtime, true)?)?;
+            } else {
+                let data = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+                gz.write_all(&tar_header(name, data.len() as u64, mtime, false)?)?;
+                gz.write_all(&data)?;
+                gz.write_all(&vec![0; (512 - data.len() % 512) % 512])?;
+            }
+        }
+        // Two empty blocks end the archive
+        gz.write_all(&[0; 1024])?;
+        gz.finish()?.flush()?;
+    }
+    println!("✅ Archived {} files to {:?}", entries.iter().filter(|(_, is_dir, _)| !is_dir).count(), archive);
+    Ok(())
+}
+
+/// A ustar header block, with names over 100 bytes split between the name and prefix fields.
+fn tar_header(name: &str, size: u64, mtime: u64, is_dir: bool) -> Result<[u8; 512]> {
+    let (prefix, name) = if name.len() <= 100 {
+        ("", name)
+    } else {
+        let split = name[..name.len().min(156)]
+            .rfind('/')
+            .filter(|&i| i <= 155 && name.len() - i - 1 <= 100 && i > 0);
+        match split {
+            Some(i) => (&name[..i], &name[i + 1..]),
+            None => bail!("Path is too long for a tar archive: {}", name),
+        }
+    };
+
+    let mut header = [0u8; 512];
+    let mut field = |offset: usize, value: &[u8]| header[offset..offset + value.len()].copy_from_slice(value);
+    field(0, name.as_bytes());
+    field(100, if is_dir { b"0000755\0" } else { b"0000644\0" });
+    field(108, b"0000000\0"); // uid
+    field(116, b"0000000\0"); // gid
+    field(124, format!("{:011o}\0", size).as_bytes());
+    field(136, format!("{:011o}\0", mtime).as_bytes());
+    field(148, b"        "); // checksum, counted as spaces
+    field(156, if is_dir { b"5" } else { b"0" });
+    field(257, b"ustar\x0000");
+    field(345, prefix.as_bytes());
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_header() {
+        // Arrange
+        let long = format!("{}/{}", "d".repeat(60), "f".repeat(60));
+
+        // Act
+        let header = tar_header("index.html", 5, 0, false).unwrap();
+        let split = tar_header(&long, 0, 0, false).unwrap();
+
+        // Assert
+        assert_eq!(&header[..10], b"index.html");
+        assert_eq!(&header[124..136], b"00000000005\0");
+        let checksum: u32 = header.iter().enumerate().map(|(i, &b)| if (148..156).contains(&i) { 32 } else { b as u32 }).sum();
+        assert_eq!(&header[148..156], format!("{:06o}\0 ", checksum).as_bytes());
+        assert_eq!(&split[..60], "f".repeat(60).as_bytes());
+        assert_eq!(&split[345..405], "d".repeat(60).as_bytes());
+        assert!(tar_header(&"x".repeat(101), 0, 0, false).is_err());
+    }
+}
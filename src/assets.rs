@@ -0,0 +1,317 @@
+// Rustic - incremental, parallel copy of static assets into the output directory
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use walkdir::WalkDir;
+
+use crate::exif;
+
+/// Files at least this large are linked rather than copied when linking is enabled.
+const LINK_MIN_SIZE: u64 = 256 * 1024;
+
+/// What a sync did, for the build summary.
+#[derive(Debug, Default)]
+pub struct SyncStats {
+    pub copied: usize,
+    /// Copies made by reflinking or hard-linking instead, counted in `copied` too
+    pub linked: usize,
+    pub skipped: usize,
+    pub removed: usize,
+    pub stripped: usize,
+}
+
+/// Mirrors `src` into `dest`, copying files in parallel and skipping unchanged ones.
+///
+/// Copies get the source's modification time, so a copy with the source's time and size
+/// is unchanged without reading either file. When only the time differs, the contents
+/// are hashed, so touching a file without editing it doesn't copy it again. Images are
+/// stripped of metadata after copying when `strip_metadata` is set; turning stripping on
+/// then replaces copies published with their metadata. Files in `dest` with no source
+/// are removed.
+///
+/// With `link`, large files are reflinked where the file system supports it, and
+/// otherwise hard-linked unless their metadata is about to be stripped in place.
+pub fn sync(src: &Path, dest: &Path, strip_metadata: bool, link: bool) -> Result<SyncStats> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(src).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            files.push(entry.path().strip_prefix(src)?.to_path_buf());
+        }
+    }
+
+    let removed = remove_stale(dest, &files.iter().collect())?;
+
+    let next = AtomicUsize::new(0);
+    let copied = AtomicUsize::new(0);
+    let linked = AtomicUsize::new(0);
+    let stripped = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(4, |n| n.get()).min(files.len().max(1));
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let source = src.join(relative);
+                        let target = dest.join(relative);
+                        if unchanged(&source, &target, strip_metadata)? {
+                            continue;
+                        }
+                        let strip = strip_metadata && exif::is_image(&source);
+                        copied.fetch_add(1, Ordering::Relaxed);
+                        match place(&source, &target, link, !strip)? {
+                            Placed::Copied => {}
+                            Placed::Reflinked => {
+                                linked.fetch_add(1, Ordering::Relaxed);
+                            }
+                            // Shares the source's data and modification time already
+                            Placed::HardLinked => {
+                                linked.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                        }
+                        if strip && exif::strip_file(&target)? {
+                            stripped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        // Stripping rewrote the file, so stamp it afterwards
+                        set_modified(&source, &target)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+    })?;
+
+    let copied = copied.into_inner();
+    Ok(SyncStats {
+        copied,
+        linked: linked.into_inner(),
+        skipped: files.len() - copied,
+        removed,
+        stripped: stripped.into_inner(),
+    })
+}
+
+/// Whether `target` already holds the current version of `source`.
+fn unchanged(source: &Path, target: &Path, strip_metadata: bool) -> Result<bool> {
+    let Ok(existing) = fs::metadata(target) else {
+        return Ok(false);
+    };
+    let metadata = fs::metadata(source).with_context(|| format!("Failed to read asset: {:?}", source))?;
+    let strip = strip_metadata && exif::is_image(source);
+    // A hard link is the source itself, and is only made for files that aren't stripped
+    if same_file(&metadata, &existing) {
+        return Ok(!strip);
+    }
+
+    if existing.modified()? == metadata.modified()? {
+        if !strip {
+            return Ok(existing.len() == metadata.len());
+        }
+        // A copy the source's size was either copied before stripping was turned on or
+        // came from an image without metadata; only the first still has some to strip
+        if existing.len() != metadata.len() {
+            return Ok(true);
+        }
+        let bytes = fs::read(target).with_context(|| format!("Failed to read asset: {:?}", target))?;
+        return Ok(exif::stripped(target, &bytes).is_none_or(|clean| clean == bytes));
+    }
+
+    // Touched, but perhaps not edited
+    let same = if strip {
+        let bytes = fs::read(source).with_context(|| format!("Failed to read asset: {:?}", source))?;
+        let expected = exif::stripped(source, &bytes).unwrap_or(bytes);
+        existing.len() == expected.len() as u64 && hash(target)? == Sha256::digest(&expected)[..]
+    } else {
+        existing.len() == metadata.len() && hash(target)? == hash(source)?
+    };
+    // Stamped, so the next build trusts the time again instead of reading both files
+    if same {
+        set_modified(source, target)?;
+    }
+    Ok(same)
+}
+
+/// Whether two files are the same file on disk, as a hard link and its source are.
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    false
+}
+
+/// The SHA-256 of a file's contents, read in chunks so large assets aren't loaded whole.
+fn hash(path: &Path) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    File::open(path)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher))
+        .with_context(|| format!("Failed to read asset: {:?}", path))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// How an asset ended up in the output directory.
+enum Placed {
+    Copied,
+    Reflinked,
+    HardLinked,
+}
+
+/// Puts `source` at `target`, linking large files when `link` is set.
+///
+/// Hard links share the source file, so they are only used when `hard_link` allows
+/// it; a reflink is a copy-on-write clone and is safe to modify.
+fn place(source: &Path, target: &Path, link: bool, hard_link: bool) -> Result<Placed> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", target))?;
+    }
+    // Writing through a previous hard link would truncate the source, so start afresh
+    match fs::remove_file(target) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to replace asset: {:?}", target));
+        }
+        _ => {}
+    }
+    let large = fs::metadata(source).is_ok_and(|m| m.len() >= LINK_MIN_SIZE);
+    if link && large {
+        if reflink(source, target).is_ok() {
+            return Ok(Placed::Reflinked);
+        }
+        if hard_link && fs::hard_link(source, target).is_ok() {
+            return Ok(Placed::HardLinked);
+        }
+    }
+    fs::copy(source, target).with_context(|| format!("Failed to copy asset: {:?}", source))?;
+    Ok(Placed::Copied)
+}
+
+/// Clones `source` into a new file at `target` without copying its data.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let from = File::open(source)?;
+    let to = File::create(target)?;
+    // SAFETY: both descriptors are open for the duration of the call
+    if unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) } == 0 {
+        return Ok(());
+    }
+    let error = io::Error::last_os_error();
+    drop(to);
+    let _ = fs::remove_file(target);
+    Err(error)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+fn set_modified(source: &Path, target: &Path) -> Result<()> {
+    let modified = fs::metadata(source)?.modified()?;
+    File::options()
+        .write(true)
+        .open(target)
+        .and_then(|file| file.set_modified(modified))
+        .with_context(|| format!("Failed to set modification time of {:?}", target))
+}
+
+/// Deletes files under `dest` that aren't in `keep`, returning how many went.
+fn remove_stale(dest: &Path, keep: &HashSet<&PathBuf>) -> Result<usize> {
+    let mut removed = 0;
+    for entry in WalkDir::new(dest).into_iter().filter_map(|e| e.ok()) {
+        let relative = entry.path().strip_prefix(dest)?.to_path_buf();
+        if entry.file_type().is_file() && !keep.contains(&relative) {
+            fs::remove_file(entry.path())
+                .with_context(|| format!("Failed to remove stale asset: {:?}", entry.path()))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_skips_unchanged_and_removes_stale() {
+        // Arrange
+        let root = std::env::temp_dir().join(format!("rustic-assets-{}", std::process::id()));
+        let (src, dest) = (root.join("static"), root.join("public/static"));
+        fs::create_dir_all(src.join("css")).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(src.join("css/site.css"), "body {}").unwrap();
+        fs::write(dest.join("old.js"), "gone").unwrap();
+
+        // Act
+        let first = sync(&src, &dest, false, false).unwrap();
+        let second = sync(&src, &dest, false, false).unwrap();
+        fs::write(src.join("css/site.css"), "body { margin: 0 }").unwrap();
+        let third = sync(&src, &dest, false, false).unwrap();
+        fs::write(src.join("css/site.css"), "body { margin: 0 }").unwrap();
+        let touched = sync(&src, &dest, false, false).unwrap();
+        fs::write(src.join("css/site.css"), "body { margin: 1 }").unwrap();
+        // An edit within the file system's timestamp granularity would keep the time
+        let edited = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        File::options().write(true).open(src.join("css/site.css")).unwrap().set_modified(edited).unwrap();
+        let same_size = sync(&src, &dest, false, false).unwrap();
+
+        // Assert
+        assert_eq!((first.copied, first.removed), (1, 1));
+        assert_eq!((second.copied, second.skipped), (0, 1));
+        assert_eq!((third.copied, touched.copied, same_size.copied), (1, 0, 1));
+        assert_eq!(fs::read_to_string(dest.join("css/site.css")).unwrap(), "body { margin: 1 }");
+        assert!(!dest.join("old.js").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_sync_strips_images_copied_with_metadata() {
+        // Arrange
+        let root = std::env::temp_dir().join(format!("rustic-assets-strip-{}", std::process::id()));
+        let (src, dest) = (root.join("static"), root.join("public/static"));
+        fs::create_dir_all(&src).unwrap();
+        let exif = [0xFF, 0xE1, 0x00, 0x06, b'E', b'x', b'i', b'f'];
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+        fs::write(src.join("photo.jpg"), [&[0xFF, 0xD8][..], &exif, &scan].concat()).unwrap();
+
+        // Act
+        let kept = sync(&src, &dest, false, false).unwrap();
+        let stripped = sync(&src, &dest, true, false).unwrap();
+        let again = sync(&src, &dest, true, false).unwrap();
+
+        // Assert
+        assert_eq!((kept.copied, stripped.copied, stripped.stripped, again.copied), (1, 1, 1, 0));
+        assert_eq!(fs::read(dest.join("photo.jpg")).unwrap(), [&[0xFF, 0xD8][..], &scan].concat());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_sync_links_large_files() {
+        // Arrange
+        let root = std::env::temp_dir().join(format!("rustic-assets-link-{}", std::process::id()));
+        let (src, dest) = (root.join("static"), root.join("public/static"));
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("video.mp4"), vec![7u8; LINK_MIN_SIZE as usize]).unwrap();
+        fs::write(src.join("small.txt"), "tiny").unwrap();
+
+        // Act
+        let synced = sync(&src, &dest, false, true).unwrap();
+
+        // Assert
+        assert_eq!((synced.copied, synced.linked), (2, 1));
+        assert_eq!(fs::read(dest.join("video.mp4")).unwrap().len(), LINK_MIN_SIZE as usize);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
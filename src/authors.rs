@@ -0,0 +1,67 @@
+// Rustic - `authors:` front matter resolved against the authors data file
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A page author, as listed in `data/authors.toml` (or `.json`) under their id.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Author {
+    /// Key of the author's entry, as written in front matter
+    pub id: String,
+    pub name: String,
+    /// Image URL, e.g. "/static/authors/ada.jpg"
+    pub avatar: Option<String>,
+    /// Home page, used in JSON-LD
+    pub url: Option<String>,
+    /// Profiles by site name, e.g. `mastodon = "https://..."`
+    pub links: BTreeMap<String, String>,
+}
+
+/// Looks up each author id in `data.authors`, falling back to the bare id with a warning.
+pub fn resolve(ids: &[String], data: &Value, page: &Path) -> Vec<Author> {
+    ids.iter()
+        .map(|id| {
+            let entry = data.get("authors").and_then(|authors| authors.get(id));
+            match entry.map(|entry| serde_json::from_value::<Author>(entry.clone())) {
+                Some(Ok(author)) => Author {
+                    id: id.clone(),
+                    name: if author.name.is_empty() { id.clone() } else { author.name },
+                    ..author
+                },
+                Some(Err(e)) => {
+                    eprintln!("⚠️  Invalid author {:?} in the authors data file: {}", id, e);
+                    Author { id: id.clone(), name: id.clone(), ..Author::default() }
+                }
+                None => {
+                    eprintln!("⚠️  Unknown author {:?} in {:?}; add them to data/authors.toml", id, page);
+                    Author { id: id.clone(), name: id.clone(), ..Author::default() }
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_authors() {
+        // Arrange
+        let data = serde_json::json!({
+            "authors": { "ada": { "name": "Ada Lovelace", "links": { "mastodon": "https://example.social/@ada" } } }
+        });
+        let ids = ["ada".to_string(), "bob".to_string()];
+
+        // Act
+        let authors = resolve(&ids, &data, Path::new("post.md"));
+
+        // Assert
+        assert_eq!(authors[0].name, "Ada Lovelace");
+        assert_eq!(authors[0].links["mastodon"], "https://example.social/@ada");
+        assert_eq!((authors[1].id.as_str(), authors[1].name.as_str()), ("bob", "bob"));
+    }
+}
@@ -0,0 +1,134 @@
+// Rustic - `rustic bench` repeatable build benchmarks
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::SKIP_COMMANDS;
+
+/// Builds the site `runs` times in fresh processes and prints duration statistics.
+///
+/// With `pages`, a synthetic corpus of that many pages replaces the content directory.
+/// Output always goes to a temporary directory so the real site is left alone, and hooks
+/// and pipeline commands are skipped so a post-build deploy doesn't run once per build.
+pub fn run(input_dir: &Path, config: &Path, config_args: &[String], runs: usize, pages: Option<usize>) -> Result<()> {
+    if runs == 0 {
+        bail!("--runs must be at least 1");
+    }
+    let scratch = std::env::temp_dir().join(format!("rustic-bench-{}", std::process::id()));
+    let result = bench(input_dir, config, config_args, runs, pages, &scratch);
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+fn bench(
+    input_dir: &Path,
+    config: &Path,
+    config_args: &[String],
+    runs: usize,
+    pages: Option<usize>,
+    scratch: &Path,
+) -> Result<()> {
+    let input_dir = match pages {
+        Some(count) => {
+            let corpus = scratch.join("content");
+            generate_corpus(&corpus, count)?;
+            println!("Generated {} synthetic page(s)", count);
+            corpus
+        }
+        None => input_dir.to_path_buf(),
+    };
+    let out_dir = scratch.join("public");
+
+    // One untimed build first, so every timed run sees warm file system caches
+    build_once(&input_dir, &out_dir, config, config_args)?;
+    let mut durations = Vec::with_capacity(runs);
+    for run in 1..=runs {
+        let elapsed = build_once(&input_dir, &out_dir, config, config_args)?;
+        println!("Run {:>3}: {:>9.1} ms", run, elapsed.as_secs_f64() * 1000.0);
+        durations.push(elapsed);
+    }
+
+    durations.sort();
+    let mean = durations.iter().sum::<Duration>() / runs as u32;
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    println!(
+        "\n{} run(s): mean {:.1} ms, median {:.1} ms, p90 {:.1} ms, min {:.1} ms, max {:.1} ms",
+        runs,
+        ms(mean),
+        ms(percentile(&durations, 50)),
+        ms(percentile(&durations, 90)),
+        ms(durations[0]),
+        ms(durations[runs - 1])
+    );
+    Ok(())
+}
+
+/// Runs one full build in a child process, so every run starts cold like a real invocation.
+fn build_once(input_dir: &Path, out_dir: &Path, config: &Path, config_args: &[String]) -> Result<Duration> {
+    let exe = std::env::current_exe().context("Failed to locate the rustic executable")?;
+    let started = Instant::now();
+    let output = Command::new(exe)
+        .arg("--input-dir")
+        .arg(input_dir)
+        .arg("--out-dir")
+        .arg(out_dir)
+        .arg("--config")
+        .arg(config)
+        .args(config_args)
+        .args(SKIP_COMMANDS.iter().flat_map(|skipped| ["--set", skipped]))
+        .stdout(Stdio::null())
+        .output()
+        .context("Failed to run the build")?;
+    let elapsed = started.elapsed();
+
+    if !output.status.success() {
+        bail!("Build failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(elapsed)
+}
+
+/// Nearest-rank percentile of sorted durations.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Writes `count` pages of representative markdown, spread across a few sections.
+fn generate_corpus(dir: &Path, count: usize) -> Result<()> {
+    for i in 0..count {
+        let section = dir.join(format!("section-{}", i % 10));
+        fs::create_dir_all(&section)
+            .with_context(|| format!("Failed to create directory: {:?}", section))?;
+        let page = format!(
+            "---\ntitle: \"Synthetic page {i}\"\nstinger: \"Benchmark\"\ndate: \"2024-01-01\"\ndescription: \"Page {i} of the benchmark corpus\"\n---\n\
+             ## Introduction\n\nLorem ipsum dolor sit amet, *consectetur* adipiscing elit. Sed do eiusmod tempor \
+             incididunt ut labore et dolore magna aliqua, with [a link](../section-0/page-0.html).\n\n\
+             - First point\n- Second point with `inline code`\n- Third point\n\n\
+             ```rust\nfn main() {{\n    println!(\"page {i}\");\n}}\n```\n\n\
+             ## Details\n\n> A quotation to break up the text.\n\n\
+             | Column | Value |\n|--------|-------|\n| page | {i} |\n"
+        );
+        let path = section.join(format!("page-{}.md", i));
+        fs::write(&path, page).with_context(|| format!("Failed to write page: {:?}", path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        // Arrange
+        let durations: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+
+        // Act & Assert
+        assert_eq!(percentile(&durations, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&durations, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&durations[..1], 90), Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,79 @@
+// Rustic - OPML export of the blogroll data file
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::feed::escape_xml;
+
+/// A recommended site, listed under `[[blogs]]` in `data/blogroll.toml`.
+#[derive(Debug, Deserialize)]
+pub struct Blog {
+    pub name: String,
+    /// Home page
+    pub url: String,
+    /// RSS or Atom feed readers subscribe to
+    pub feed: String,
+    pub description: Option<String>,
+}
+
+/// Reads the blogs from `data.blogroll`, if the site has a blogroll data file.
+pub fn load(data: &Value) -> Result<Option<Vec<Blog>>> {
+    let Some(blogs) = data.get("blogroll").map(|blogroll| blogroll.get("blogs").cloned().unwrap_or_default()) else {
+        return Ok(None);
+    };
+    serde_json::from_value(blogs)
+        .map(Some)
+        .context("Failed to read the blogroll data file; expected a list of `blogs` with name, url and feed")
+}
+
+/// Renders the blogroll as an OPML 2.0 subscription list.
+pub fn opml(blogs: &[Blog], title: &str) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n");
+    xml.push_str(&format!("<head>\n<title>{}</title>\n</head>\n<body>\n", escape_xml(title)));
+    for blog in blogs {
+        xml.push_str(&format!(
+            "<outline type=\"rss\" text=\"{0}\" title=\"{0}\" xmlUrl=\"{1}\" htmlUrl=\"{2}\"",
+            escape_xml(&blog.name),
+            escape_xml(&blog.feed),
+            escape_xml(&blog.url)
+        ));
+        if let Some(description) = &blog.description {
+            xml.push_str(&format!(" description=\"{}\"", escape_xml(description)));
+        }
+        xml.push_str("/>\n");
+    }
+    xml.push_str("</body>\n</opml>\n");
+    xml
+}
+
+/// Writes `blogroll.opml` to the root of the output directory.
+pub fn write(blogs: &[Blog], title: &str, out_dir: &Path) -> Result<()> {
+    let path = out_dir.join("blogroll.opml");
+    fs::write(&path, opml(blogs, title)).with_context(|| format!("Failed to write blogroll: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blogroll_to_opml() {
+        // Arrange
+        let data = serde_json::json!({
+            "blogroll": { "blogs": [{ "name": "Tom & Jerry", "url": "https://tj.example", "feed": "https://tj.example/rss.xml" }] }
+        });
+
+        // Act
+        let blogs = load(&data).unwrap().unwrap();
+        let xml = opml(&blogs, "Blogroll");
+
+        // Assert
+        assert!(xml.contains(
+            "<outline type=\"rss\" text=\"Tom &amp; Jerry\" title=\"Tom &amp; Jerry\" xmlUrl=\"https://tj.example/rss.xml\" htmlUrl=\"https://tj.example\"/>"
+        ));
+        assert!(load(&serde_json::json!({})).unwrap().is_none());
+    }
+}
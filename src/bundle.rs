@@ -0,0 +1,90 @@
+// Rustic - JavaScript bundling through an external esbuild-compatible bundler
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::pipeline::run_shell;
+
+/// Bundler invocation used when a `[[bundle]]` entry doesn't set its own command.
+const DEFAULT_BUNDLE_COMMAND: &str = "esbuild {entry} --bundle --minify --outfile={output}";
+
+/// A JavaScript entry point declared under `[[bundle]]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+pub struct Bundle {
+    /// Key the bundle URL is exposed under in templates (`bundles.<name>`)
+    pub name: String,
+    /// Entry point passed to the bundler
+    pub entry: PathBuf,
+    /// Bundler command; `{entry}` and `{output}` are substituted before running
+    pub command: Option<String>,
+}
+
+/// Runs the bundler for every entry point and writes content-hashed files to `<out_dir>/js`.
+///
+/// Returns a map of bundle name to site-relative URL for use in templates.
+pub fn build_bundles(bundles: &[Bundle], out_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut urls = BTreeMap::new();
+    if bundles.is_empty() {
+        return Ok(urls);
+    }
+
+    let js_dir = out_dir.join("js");
+    fs::create_dir_all(&js_dir)
+        .with_context(|| format!("Failed to create bundle directory: {:?}", js_dir))?;
+
+    for bundle in bundles {
+        println!("Bundling: {:?}", bundle.entry);
+        if !bundle.entry.exists() {
+            bail!("Bundle entry point not found: {:?}", bundle.entry);
+        }
+
+        // Let the bundler write to a scratch file, then rename it after its contents
+        let scratch = js_dir.join(format!("{}.bundle-tmp.js", bundle.name));
+        let command = bundle
+            .command
+            .as_deref()
+            .unwrap_or(DEFAULT_BUNDLE_COMMAND)
+            .replace("{entry}", &bundle.entry.to_string_lossy())
+            .replace("{output}", &scratch.to_string_lossy());
+        run_shell(&command).with_context(|| format!("Failed to bundle '{}'", bundle.name))?;
+
+        let contents = fs::read(&scratch)
+            .with_context(|| format!("Bundler did not produce output for '{}'", bundle.name))?;
+        let file_name = format!("{}.{}.js", bundle.name, short_hash(&contents));
+        fs::rename(&scratch, js_dir.join(&file_name))
+            .with_context(|| format!("Failed to write bundle '{}'", bundle.name))?;
+
+        urls.insert(bundle.name.clone(), format!("/js/{}", file_name));
+    }
+
+    Ok(urls)
+}
+
+/// Returns the first 8 bytes of the SHA-256 digest as lowercase hex.
+pub fn short_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_hash_is_stable() {
+        // Act
+        let hash = short_hash(b"console.log('hi');");
+
+        // Assert
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, short_hash(b"console.log('hi');"));
+        assert_ne!(hash, short_hash(b"console.log('bye');"));
+    }
+}
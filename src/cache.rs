@@ -0,0 +1,77 @@
+// Rustic - persistent cache of parsed front matter and rendered markdown
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+/// Content-addressed entries stored under `cache_dir`, one file per entry.
+///
+/// Keys are hashed together with the rustic version, so upgrading never serves
+/// output produced by an older parser.
+#[derive(Debug, Default)]
+pub struct Cache {
+    dir: Option<PathBuf>,
+}
+
+impl Cache {
+    /// A cache in `dir`, or a disabled one that never hits when `dir` is `None`.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    /// Returns the cached value for `input`, computing and storing it on a miss.
+    pub fn get_or_insert(
+        &self,
+        kind: &str,
+        input: &str,
+        compute: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        let Some(dir) = &self.dir else {
+            return compute();
+        };
+
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update([0]);
+        hasher.update(input);
+        let key: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        let path = dir.join(kind).join(&key[..2]).join(&key[2..]);
+
+        if let Ok(value) = fs::read_to_string(&path) {
+            return Ok(value);
+        }
+
+        let value = compute()?;
+        let parent = path.parent().unwrap_or(dir);
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create cache directory: {:?}", parent))?;
+        // Write then rename, so an interrupted build never leaves a truncated entry
+        let scratch = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&scratch, &value)
+            .with_context(|| format!("Failed to write cache entry: {:?}", scratch))?;
+        fs::rename(&scratch, &path)
+            .with_context(|| format!("Failed to write cache entry: {:?}", path))?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit_skips_compute() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-cache-{}", std::process::id()));
+        let cache = Cache::new(Some(dir.clone()));
+
+        // Act
+        let first = cache.get_or_insert("html", "# Hi", || Ok("<h1>Hi</h1>".to_string())).unwrap();
+        let second = cache.get_or_insert("html", "# Hi", || panic!("should be cached")).unwrap();
+
+        // Assert
+        assert_eq!(first, second);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
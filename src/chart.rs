@@ -0,0 +1,240 @@
+// Rustic - chart shortcode drawing CSV data as an SVG line or bar chart at build time
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use tera::Context as TeraContext;
+
+use crate::shortcodes::{self, escape_attr, string_arg, Args};
+use crate::{data, Site};
+
+/// Series colours, in order; themes can restyle them through the `series-<n>` classes.
+const PALETTE: [&str; 8] = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#9c755f"];
+
+/// Space around the plot for the axis labels, and below it for the legend.
+const MARGIN_LEFT: f64 = 56.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 16.0;
+const MARGIN_BOTTOM: f64 = 32.0;
+const LEGEND_HEIGHT: f64 = 24.0;
+
+/// One column of values; blank or non-numeric cells are gaps.
+#[derive(Debug, Serialize)]
+struct Series {
+    name: String,
+    values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Line,
+    Bar,
+}
+
+/// Renders `{{ chart(path="data/bench.csv", kind="line") }}`: the CSV file, relative to the
+/// site root, as an inline SVG. The first column labels the x axis and every other
+/// column is a series named by the header row.
+pub fn render(args: &Args, site: &Site) -> Result<String> {
+    let path = string_arg(args, "path")?;
+    let kind = match args.get("kind").and_then(Value::as_str).unwrap_or("line") {
+        "line" => Kind::Line,
+        "bar" => Kind::Bar,
+        other => bail!("Unknown chart kind {:?}; expected line or bar", other),
+    };
+    let title = args.get("title").and_then(Value::as_str);
+    let width = args.get("width").and_then(Value::as_f64).unwrap_or(640.0);
+    let height = args.get("height").and_then(Value::as_f64).unwrap_or(360.0);
+
+    let raw = fs::read_to_string(path).with_context(|| format!("Failed to read chart data: {:?}", path))?;
+    let rows = data::parse_csv(&raw).with_context(|| format!("Failed to parse chart data: {:?}", path))?;
+    let (labels, series) = columns(&rows).with_context(|| format!("Failed to chart {:?}", path))?;
+
+    let mut context = TeraContext::new();
+    context.insert("path", path);
+    context.insert("kind", if kind == Kind::Line { "line" } else { "bar" });
+    context.insert("title", &title);
+    context.insert("labels", &labels);
+    context.insert("series", &series);
+    if let Some(html) = shortcodes::render_template("chart", &context, site)? {
+        return Ok(html);
+    }
+
+    let description = title.map_or_else(|| format!("Chart of {}", path), str::to_string);
+    let mut html = String::from("<figure class=\"chart\">\n");
+    html.push_str(&svg(&labels, &series, kind, width, height, &description));
+    if let Some(title) = title {
+        html.push_str(&format!("\n<figcaption>{}</figcaption>", escape_attr(title)));
+    }
+    html.push_str("\n</figure>");
+    Ok(html)
+}
+
+/// Splits CSV rows into x labels and series, using the header row for series names.
+fn columns(rows: &[Vec<String>]) -> Result<(Vec<String>, Vec<Series>)> {
+    let Some((header, rows)) = rows.split_first() else {
+        bail!("the file is empty");
+    };
+    if header.len() < 2 || rows.is_empty() {
+        bail!("a chart needs a header row, a column of labels and at least one column and row of values");
+    }
+    let labels = rows.iter().map(|row| row.first().cloned().unwrap_or_default()).collect();
+    let series: Vec<Series> = header[1..]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Series {
+            name: name.clone(),
+            values: rows.iter().map(|row| row.get(i + 1).and_then(|cell| cell.trim().parse().ok())).collect(),
+        })
+        .collect();
+    if series.iter().all(|s| s.values.iter().all(Option::is_none)) {
+        bail!("there are no numbers to plot");
+    }
+    Ok((labels, series))
+}
+
+/// Round axis bounds and a step of 1, 2 or 5 times a power of ten, for about five ticks.
+fn ticks(min: f64, max: f64) -> (f64, f64, f64) {
+    let (min, max) = if min == max { (min - 1.0, max + 1.0) } else { (min, max) };
+    let rough = (max - min) / 5.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * magnitude).find(|&s| s >= rough).unwrap_or(rough);
+    ((min / step).floor() * step, (max / step).ceil() * step, step)
+}
+
+fn svg(labels: &[String], series: &[Series], kind: Kind, width: f64, height: f64, description: &str) -> String {
+    let values = series.iter().flat_map(|s| s.values.iter().flatten().copied());
+    let (mut min, mut max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    // Bars are measured from zero
+    if kind == Kind::Bar {
+        (min, max) = (min.min(0.0), max.max(0.0));
+    }
+    let (lo, hi, step) = ticks(min, max);
+    let legend = if series.len() > 1 { LEGEND_HEIGHT } else { 0.0 };
+    let (plot_width, plot_height) = (width - MARGIN_LEFT - MARGIN_RIGHT, height - MARGIN_TOP - MARGIN_BOTTOM - legend);
+    let band = plot_width / labels.len() as f64;
+    let x = |i: usize| MARGIN_LEFT + band * (i as f64 + 0.5);
+    let y = |v: f64| MARGIN_TOP + plot_height - (v - lo) / (hi - lo) * plot_height;
+    let decimals = if step.fract() == 0.0 { 0 } else { (-step.log10().floor()).max(0.0) as usize };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {w} {h}\" width=\"{w}\" height=\"{h}\" role=\"img\" aria-label=\"{d}\" font-family=\"sans-serif\" font-size=\"12\" style=\"max-width: 100%; height: auto\">\n<title>{d}</title>\n",
+        w = width,
+        h = height,
+        d = escape_attr(description)
+    );
+
+    // Grid lines and y axis labels
+    svg.push_str("<g class=\"chart-grid\">\n");
+    let mut tick = lo;
+    while tick <= hi + step / 2.0 {
+        let label = format!("{:.*}", decimals, tick);
+        let label = if label.trim_start_matches(['-', '0', '.']).is_empty() { "0".to_string() } else { label };
+        svg.push_str(&format!(
+            "<line x1=\"{:.1}\" x2=\"{:.1}\" y1=\"{y:.1}\" y2=\"{y:.1}\" stroke=\"#ddd\" />\n<text x=\"{:.1}\" y=\"{y:.1}\" dy=\"4\" text-anchor=\"end\" fill=\"#555\">{}</text>\n",
+            MARGIN_LEFT,
+            width - MARGIN_RIGHT,
+            MARGIN_LEFT - 8.0,
+            label,
+            y = y(tick)
+        ));
+        tick += step;
+    }
+    svg.push_str("</g>\n");
+
+    // x axis labels, thinned out so they don't overlap
+    let every = labels.len().div_ceil(12).max(1);
+    svg.push_str("<g class=\"chart-labels\" fill=\"#555\" text-anchor=\"middle\">\n");
+    for (i, label) in labels.iter().enumerate().step_by(every) {
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\">{}</text>\n", x(i), MARGIN_TOP + plot_height + 20.0, escape_attr(label)));
+    }
+    svg.push_str("</g>\n");
+
+    for (n, s) in series.iter().enumerate() {
+        let colour = PALETTE[n % PALETTE.len()];
+        svg.push_str(&format!("<g class=\"series-{}\" fill=\"{}\">\n", n, colour));
+        let point_title = |i: usize, v: f64| format!("<title>{}, {}: {}</title>", escape_attr(&s.name), escape_attr(&labels[i]), v);
+        match kind {
+            Kind::Line => {
+                // Gaps in the data break the line
+                let mut d = String::new();
+                let mut pen_down = false;
+                for (i, value) in s.values.iter().enumerate() {
+                    match value {
+                        Some(v) => {
+                            d.push_str(&format!("{}{:.1},{:.1} ", if pen_down { "L" } else { "M" }, x(i), y(*v)));
+                            pen_down = true;
+                        }
+                        None => pen_down = false,
+                    }
+                }
+                svg.push_str(&format!("<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\" />\n", d.trim_end(), colour));
+                for (i, v) in s.values.iter().enumerate().filter_map(|(i, v)| v.map(|v| (i, v))) {
+                    svg.push_str(&format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\">{}</circle>\n", x(i), y(v), point_title(i, v)));
+                }
+            }
+            Kind::Bar => {
+                let bar = band * 0.8 / series.len() as f64;
+                for (i, v) in s.values.iter().enumerate().filter_map(|(i, v)| v.map(|v| (i, v))) {
+                    let left = x(i) - band * 0.4 + bar * n as f64;
+                    let (top, bottom) = (y(v.max(0.0)), y(v.min(0.0)));
+                    svg.push_str(&format!(
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\">{}</rect>\n",
+                        left,
+                        top,
+                        bar,
+                        bottom - top,
+                        point_title(i, v)
+                    ));
+                }
+            }
+        }
+        svg.push_str("</g>\n");
+    }
+
+    if series.len() > 1 {
+        svg.push_str("<g class=\"chart-legend\" fill=\"#555\">\n");
+        let mut left = MARGIN_LEFT;
+        let top = height - LEGEND_HEIGHT + 4.0;
+        for (n, s) in series.iter().enumerate() {
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"12\" height=\"12\" fill=\"{}\" />\n<text x=\"{:.1}\" y=\"{:.1}\">{}</text>\n",
+                left,
+                top,
+                PALETTE[n % PALETTE.len()],
+                left + 16.0,
+                top + 10.0,
+                escape_attr(&s.name)
+            ));
+            left += 16.0 + s.name.chars().count() as f64 * 7.0 + 16.0;
+        }
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart_svg() {
+        // Arrange
+        let rows = data::parse_csv("Version,Build (s),Render (s)\n0.1,12,3.5\n0.2,,2\n0.3,8,1.5\n").unwrap();
+        let (labels, series) = columns(&rows).unwrap();
+
+        // Act
+        let line = svg(&labels, &series, Kind::Line, 400.0, 240.0, "Build times");
+        let bar = svg(&labels, &series, Kind::Bar, 400.0, 240.0, "Build times");
+
+        // Assert
+        assert_eq!(ticks(1.5, 12.0), (0.0, 15.0, 5.0));
+        assert!(line.contains("<title>Build times</title>"));
+        assert_eq!(line.matches("<path ").count(), 2);
+        assert!(line.contains("<path d=\"M110.7,49.6 M329.3,94.4\""), "a gap breaks the line:\n{}", line);
+        assert!(line.contains("<title>Render (s), 0.2: 2</title>"));
+        assert_eq!(bar.matches("<rect ").count(), 5 + 2);
+        assert!(columns(&data::parse_csv("Name,Note\nAda,n/a\n").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+// Rustic - `rustic check` reports about the built site
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::graph::LinkGraph;
+use crate::{a11y, seo, spelling, unused_assets};
+
+/// Settings for `rustic check`, declared under `[check]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CheckConfig {
+    /// Pages readers arrive at directly, which don't need incoming links
+    pub entry_points: Vec<String>,
+    /// Stock word list used by the spellchecker, one word per line
+    pub dictionary: PathBuf,
+    /// Project-specific words accepted by the spellchecker, if the file exists
+    pub words: PathBuf,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            entry_points: vec!["index.html".to_string()],
+            dictionary: PathBuf::from("/usr/share/dict/words"),
+            words: PathBuf::from("words.txt"),
+        }
+    }
+}
+
+/// Which reports `rustic check` runs; with none selected, all of them run.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Checks {
+    pub orphans: bool,
+    pub a11y: bool,
+    pub spelling: bool,
+    pub seo: bool,
+    pub assets: bool,
+    /// Exit with an error when anything is reported, rather than only warning
+    pub strict: bool,
+}
+
+impl Checks {
+    fn all(self) -> bool {
+        !(self.orphans || self.a11y || self.spelling || self.seo || self.assets)
+    }
+}
+
+/// Runs the selected checks against the site in `out_dir` (and, for spelling, the
+/// sources in `input_dir`) and reports findings per file.
+///
+/// Findings are warnings unless `checks.strict` is set, which turns them into a failure.
+pub fn run(input_dir: &Path, out_dir: &Path, config: &CheckConfig, checks: Checks) -> Result<()> {
+    let graph = LinkGraph::scan(out_dir)?;
+    let mut findings: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    if checks.orphans || checks.all() {
+        // Redirect stubs, unlisted pages and others kept out of search engines aren't meant to be found
+        let mut hidden = BTreeSet::new();
+        for page in graph.pages.keys() {
+            let path = out_dir.join(page);
+            let html = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read page: {:?}", path))?;
+            if seo::scan(&html)?.noindex {
+                hidden.insert(page.as_str());
+            }
+        }
+        for page in orphans(&graph, &config.entry_points, &hidden) {
+            findings.entry(page.to_string()).or_default().push("Orphan page, nothing links to it".to_string());
+        }
+    }
+
+    if checks.a11y || checks.all() {
+        for page in graph.pages.keys() {
+            let path = out_dir.join(page);
+            let html = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read page: {:?}", path))?;
+            let problems = a11y::audit(&html)
+                .with_context(|| format!("Failed to check accessibility of {:?}", path))?;
+            findings.entry(page.clone()).or_default().extend(problems);
+        }
+    }
+
+    if checks.seo || checks.all() {
+        let mut pages = BTreeMap::new();
+        for page in graph.pages.keys() {
+            let path = out_dir.join(page);
+            let html = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read page: {:?}", path))?;
+            pages.insert(page.clone(), seo::scan(&html)?);
+        }
+        let sitemap_path = out_dir.join("sitemap.xml");
+        let sitemap = match fs::read_to_string(&sitemap_path) {
+            // A multilingual site's sitemap.xml indexes one sitemap per language
+            Ok(xml) if xml.contains("<sitemapindex") => Some(
+                seo::sitemap_pages(&xml)
+                    .iter()
+                    .filter_map(|sitemap| fs::read_to_string(out_dir.join(sitemap)).ok())
+                    .flat_map(|xml| seo::sitemap_pages(&xml))
+                    .collect(),
+            ),
+            Ok(xml) => Some(seo::sitemap_pages(&xml)),
+            Err(_) => None,
+        };
+        for (page, problems) in seo::audit(&pages, sitemap.as_deref()) {
+            findings.entry(page).or_default().extend(problems);
+        }
+    }
+
+    if checks.assets || checks.all() {
+        for asset in unused_assets::unused(out_dir)? {
+            findings.entry(asset).or_default().push("Unused asset, nothing refers to it".to_string());
+        }
+    }
+
+    if checks.spelling || checks.all() {
+        match spelling::Dictionary::load(&config.dictionary, &config.words) {
+            Ok(dictionary) => {
+                for entry in WalkDir::new(input_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+                    let path = entry.path();
+                    if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("md") {
+                        continue;
+                    }
+                    let misspelled = spelling::check_file(path, &dictionary)?;
+                    findings.entry(path.display().to_string()).or_default().extend(
+                        misspelled
+                            .into_iter()
+                            .map(|(line, word)| format!("line {}: unknown word `{}`", line, word)),
+                    );
+                }
+            }
+            // Only a hard error when the spellchecker was asked for by name
+            Err(err) if checks.spelling => return Err(err),
+            Err(err) => eprintln!("⚠️  Skipping spelling: {:#}", err),
+        }
+    }
+
+    let count: usize = findings.values().map(Vec::len).sum();
+    for (page, messages) in findings.iter().filter(|(_, m)| !m.is_empty()) {
+        eprintln!("{}", page);
+        for message in messages {
+            eprintln!("  ⚠️  {}", message);
+        }
+    }
+
+    if count == 0 {
+        println!("✅ No problems found in {} page(s)", graph.pages.len());
+    } else if checks.strict {
+        bail!("Found {} problem(s) in {:?}", count, out_dir);
+    } else {
+        println!("Found {} problem(s) in {} page(s)", count, graph.pages.len());
+    }
+    Ok(())
+}
+
+/// Pages with no incoming links from any other page, excluding the entry points and
+/// `hidden` pages, which are unlinked on purpose.
+fn orphans<'a>(graph: &'a LinkGraph, entry_points: &[String], hidden: &BTreeSet<&str>) -> Vec<&'a str> {
+    let linked: BTreeSet<&str> = graph.links.values().flatten().map(String::as_str).collect();
+    graph
+        .pages
+        .keys()
+        .map(String::as_str)
+        .filter(|page| !linked.contains(page) && !hidden.contains(page) && !entry_points.iter().any(|e| e == page))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orphans() {
+        // Arrange
+        let mut graph = LinkGraph::default();
+        for page in ["index.html", "about.html", "lost.html", "old-about.html"] {
+            graph.pages.insert(page.to_string(), String::new());
+        }
+        graph.links.insert("index.html".to_string(), BTreeSet::from(["about.html".to_string()]));
+
+        // Act
+        let orphans = orphans(&graph, &["index.html".to_string()], &BTreeSet::from(["old-about.html"]));
+
+        // Assert
+        assert_eq!(orphans, ["lost.html"]);
+    }
+}
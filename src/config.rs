@@ -0,0 +1,254 @@
+// Rustic - site configuration loaded from rustic.toml
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::value::{Table, Value};
+
+use crate::activitypub::ActivityPubConfig;
+use crate::api::ApiConfig;
+use crate::bundle::Bundle;
+use crate::check::CheckConfig;
+use crate::containers::AdmonitionConfig;
+use crate::embeds::EmbedMode;
+use crate::feed::{FeedConfig, PodcastConfig};
+use crate::gemini::GeminiConfig;
+use crate::listing::SectionConfig;
+use crate::menus::MenuEntry;
+use crate::pipeline::{Hooks, PipelineStep};
+use crate::plugins::Plugin;
+use crate::postprocess::RewriteConfig;
+use crate::i18n::LanguageConfig;
+use crate::load_data::LoadDataConfig;
+use crate::social::SocialCardConfig;
+use crate::taxonomies::TaxonomyConfig;
+use crate::webmention::WebmentionConfig;
+
+/// Site-wide settings read from `rustic.toml` in the project root.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Site name, exposed to templates as `site.title`
+    pub title: Option<String>,
+    /// Default description of the site
+    pub description: Option<String>,
+    pub author: Option<String>,
+    /// Language of the content, as used for `<html lang>`; defaults to `en`
+    pub language: Option<String>,
+    /// Further languages, each with its pages under `content/<code>/`, e.g. `[languages.fr]`
+    pub languages: BTreeMap<String, LanguageConfig>,
+    /// Public URL the site is served from, used for absolute links in feeds
+    pub base_url: String,
+    /// Stylesheet whose contents are inlined into every page's `<head>`
+    pub critical_css: Option<PathBuf>,
+    /// Directory where parsed front matter and rendered markdown are cached between builds
+    pub cache_dir: Option<PathBuf>,
+    /// Source image (PNG, or SVG) the favicon set is generated from
+    pub favicon: Option<PathBuf>,
+    /// Publish images with their EXIF/GPS metadata intact
+    pub keep_image_metadata: bool,
+    /// Reflink or hard-link large static files into the output instead of copying them
+    pub link_assets: bool,
+    /// Publish pages marked `draft: true`
+    pub drafts: bool,
+    /// Publish pages whose `publish_date` is still to come
+    pub future: bool,
+    /// Set `page.updated` from each source's last git commit, or its modification time
+    pub git_dates: bool,
+    /// Set `page.contributors` from the authors of commits touching each source
+    pub git_contributors: bool,
+    /// Also list `redirect_to` pages in a `_redirects` file for Netlify or Cloudflare Pages
+    pub redirects_file: bool,
+    /// Also write each page's metadata, HTML body and plain text to `<page>.json`, plus a `pages.json` index
+    pub json_pages: bool,
+    /// Templates shared with other sites; the site's own templates of the same name take precedence
+    pub theme_dir: Option<PathBuf>,
+    /// Data files shared with other sites; the site's own data files of the same stem take precedence
+    pub shared_data_dir: Option<PathBuf>,
+    /// Password for pages marked `protected: true` that don't set their own
+    pub protected_password: Option<String>,
+    /// Secret mixed into the URLs of `unlisted: true` pages, so they can't be guessed from their paths
+    pub unlisted_key: String,
+    /// Whether video and social embeds load immediately or wait for a click
+    pub embed_mode: EmbedMode,
+    /// Asset commands run, in order, before static files are copied
+    pub pipeline: Vec<PipelineStep>,
+    /// JavaScript entry points bundled into hashed files under `js/`
+    pub bundle: Vec<Bundle>,
+    /// Shell commands run before and after the build
+    pub hooks: Hooks,
+    /// External programs invoked at pipeline stages with page JSON on stdin
+    pub plugins: Vec<Plugin>,
+    /// Classes used for `> [!NOTE]` / `:::note` callouts
+    pub admonitions: AdmonitionConfig,
+    /// Built-in HTML post-processing rules
+    pub rewrite: RewriteConfig,
+    /// What section and taxonomy feeds include
+    pub feeds: FeedConfig,
+    /// Podcast feed settings; the feed is only generated when present
+    pub podcast: Option<PodcastConfig>,
+    /// A read-only fediverse account for the site's posts; generated only when present
+    pub activitypub: Option<ActivityPubConfig>,
+    /// Paginated JSON endpoints for sections and taxonomy terms under `/api`; generated only when present
+    pub api: Option<ApiConfig>,
+    /// A gemtext copy of the site for Gemini; generated only when present
+    pub gemini: Option<GeminiConfig>,
+    /// Open Graph card images; generated only when present
+    pub social_cards: Option<SocialCardConfig>,
+    /// Publish an Obsidian vault: optional front matter, `![[embeds]]`, any callout kind and attachments
+    pub obsidian: bool,
+    /// Webmention endpoints advertised in page heads, and settings for `rustic webmention send`
+    pub webmention: WebmentionConfig,
+    /// Settings for `rustic check`
+    pub check: CheckConfig,
+    /// Environment variables templates may read with `get_env()`
+    pub template_env: Vec<String>,
+    /// Hosts templates may fetch data from with `load_data()`, and how responses are cached
+    pub load_data: LoadDataConfig,
+    /// Navigation menus by name, e.g. `[[menu.main]]`; pages can add themselves with `menu:`
+    pub menu: BTreeMap<String, Vec<MenuEntry>>,
+    /// Taxonomies such as tags, e.g. `[[taxonomies]] name = "tags"`
+    pub taxonomies: Vec<TaxonomyConfig>,
+    /// Per-section settings, e.g. `[sections.docs] sort_by = "weight"`
+    pub sections: BTreeMap<String, SectionConfig>,
+    /// Free-form values exposed to templates as `site.extra`
+    pub extra: toml::value::Table,
+}
+
+impl Config {
+    /// Loads the config file, falling back to the defaults when it doesn't exist.
+    ///
+    /// With a `profile`, the `[profile.<name>]` table is merged over the rest of the file,
+    /// and `overrides` from the command line are applied last.
+    pub fn load(path: &Path, profile: Option<&str>, overrides: &[Override]) -> Result<Self> {
+        let mut table = if path.exists() {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file: {:?}", path))?;
+            toml::from_str(&raw).with_context(|| format!("Failed to parse config file: {:?}", path))?
+        } else if let Some(name) = profile {
+            bail!("Profile {:?} selected, but there is no config file at {:?}", name, path);
+        } else {
+            Table::new()
+        };
+        let mut profiles = match table.remove("profile") {
+            Some(Value::Table(profiles)) => profiles,
+            Some(_) => bail!("`profile` in {:?} must be a table of profiles", path),
+            None => Table::new(),
+        };
+        if let Some(name) = profile {
+            match profiles.remove(name) {
+                Some(Value::Table(overrides)) => merge(&mut table, overrides),
+                Some(_) => bail!("[profile.{}] in {:?} must be a table", name, path),
+                None => bail!("Profile {:?} is not defined in {:?}", name, path),
+            }
+        }
+        for Override { key, value } in overrides {
+            set(&mut table, key, value.clone())?;
+        }
+        Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Failed to parse config file: {:?}", path))
+    }
+}
+
+/// Overrides that skip hooks and asset pipeline commands, for builds that only preview,
+/// measure or compare output: those commands can do anything, including deploy.
+pub const SKIP_COMMANDS: [&str; 3] = ["hooks.pre_build=[]", "hooks.post_build=[]", "pipeline=[]"];
+
+/// A `--set key=value` override; dotted keys reach into tables, e.g. `extra.banner=true`.
+#[derive(Debug, Clone)]
+pub struct Override {
+    key: String,
+    value: Value,
+}
+
+impl FromStr for Override {
+    type Err = String;
+
+    /// Values are read as TOML (`true`, `3`, `["a", "b"]`), falling back to a plain string.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let (key, value) = raw.split_once('=').ok_or("expected KEY=VALUE")?;
+        let value = toml::from_str::<Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| Value::String(value.to_string()));
+        Ok(Self { key: key.trim().to_string(), value })
+    }
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// Sets a dotted `key` in `table`, creating intermediate tables as needed.
+fn set(table: &mut Table, key: &str, value: Value) -> Result<()> {
+    match key.split_once('.') {
+        None => {
+            table.insert(key.to_string(), value);
+        }
+        Some((head, rest)) => match table.entry(head.to_string()).or_insert_with(|| Value::Table(Table::new())) {
+            Value::Table(inner) => set(inner, rest, value)?,
+            _ => bail!("Can't set {:?}: `{}` is not a table", key, head),
+        },
+    }
+    Ok(())
+}
+
+/// Merges `overrides` into `base`, recursing into tables and replacing everything else.
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_config_uses_defaults() {
+        // Act
+        let config = Config::load(Path::new("does-not-exist.toml"), None, &[]).unwrap();
+
+        // Assert
+        assert!(config.critical_css.is_none());
+    }
+
+    #[test]
+    fn test_profile_and_overrides_replace_base_settings() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("rustic-profile-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "base_url = \"http://localhost:8000\"\n[extra]\nbanner = \"preview\"\nauthor = \"Ada\"\n\n\
+             [profile.production]\nbase_url = \"https://example.com\"\nextra = { banner = \"\" }\n",
+        )
+        .unwrap();
+
+        // Act
+        let dev = Config::load(&path, None, &[]).unwrap();
+        let overrides = ["base_url=https://staging.example.com".parse().unwrap(), "extra.draft_count=3".parse().unwrap()];
+        let staging = Config::load(&path, Some("production"), &overrides).unwrap();
+        let missing = Config::load(&path, Some("staging"), &[]);
+
+        // Assert
+        assert_eq!(dev.base_url, "http://localhost:8000");
+        assert_eq!(staging.base_url, "https://staging.example.com");
+        assert_eq!(staging.extra["banner"].as_str(), Some(""));
+        assert_eq!(staging.extra["author"].as_str(), Some("Ada"));
+        assert_eq!(staging.extra["draft_count"].as_integer(), Some(3));
+        assert!(missing.is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
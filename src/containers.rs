@@ -0,0 +1,240 @@
+// Rustic - `:::name` fenced containers and GitHub-style `> [!NOTE]` callouts
+
+use anyhow::Result;
+use pulldown_cmark::{html, Parser as MarkdownParser};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tera::Context as TeraContext;
+
+use crate::{shortcodes, Site};
+
+/// Callout kinds recognised in both syntaxes.
+const KINDS: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// Styling for callouts, declared under `[admonitions]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct AdmonitionConfig {
+    /// Class every callout gets, alongside its kind (e.g. `admonition warning`)
+    pub class: String,
+    /// Replaces the default classes for specific kinds, e.g. `warning = "alert alert-danger"`
+    pub classes: HashMap<String, String>,
+}
+
+impl Default for AdmonitionConfig {
+    fn default() -> Self {
+        Self {
+            class: "admonition".to_string(),
+            classes: HashMap::new(),
+        }
+    }
+}
+
+/// Rewrites callouts and `:::` containers into HTML wrappers around their markdown body.
+///
+/// Callout kinds (`note`, `warning`, ...) become styled admonitions. Any other
+/// `:::name` is rendered through `templates/shortcodes/<name>.html` when it exists,
+/// and otherwise wrapped in `<div class="name">`. Bodies are left as markdown,
+/// separated from the wrapper by blank lines, so they are still rendered normally.
+/// A `:::name` that is never closed is reported with its line in `page`'s body and left
+/// as written.
+pub fn expand(markdown: &str, page: &Path, site: &Site) -> Result<String> {
+    expand_from(markdown, page, 1, site)
+}
+
+/// `expand` for markdown starting at line `first_line` of the page body.
+fn expand_from(markdown: &str, page: &Path, first_line: usize, site: &Site) -> Result<String> {
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut out = Vec::with_capacity(lines.len());
+    let mut fence: Option<&str> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        // Leave fenced code blocks untouched
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        // > [!NOTE] Optional title
+        if let Some((kind, title)) = alert_header(trimmed, site.config.obsidian) {
+            let mut body = Vec::new();
+            i += 1;
+            let body_line = first_line + i;
+            while i < lines.len() && lines[i].trim_start().starts_with('>') {
+                let quoted = &lines[i].trim_start()[1..];
+                body.push(quoted.strip_prefix(' ').unwrap_or(quoted).to_string());
+                i += 1;
+            }
+            let body = expand_from(&body.join("\n"), page, body_line, site)?;
+            out.push(admonition(&kind, title, &body, &site.config.admonitions));
+            continue;
+        }
+
+        // :::name rest ... :::
+        if let Some((name, rest)) = container_header(trimmed) {
+            let Some(end) = closing_line(&lines, i) else {
+                eprintln!("⚠️  Unclosed :::{} container in {:?} at line {} of the body; add a closing :::", name, page, first_line + i);
+                out.push(line.to_string());
+                i += 1;
+                continue;
+            };
+            let body = expand_from(&lines[i + 1..end].join("\n"), page, first_line + i + 1, site)?;
+            i = end + 1;
+            if KINDS.contains(&name.as_str()) {
+                out.push(admonition(&name, rest, &body, &site.config.admonitions));
+            } else {
+                out.push(container(&name, rest, &body, site)?);
+            }
+            continue;
+        }
+
+        out.push(line.to_string());
+        i += 1;
+    }
+
+    Ok(out.join("\n"))
+}
+
+/// The index of the `:::` that closes the container opened at `lines[start]`, counting
+/// the containers nested inside it.
+fn closing_line(lines: &[&str], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let inner = line.trim();
+        if inner == ":::" {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        } else if container_header(inner).is_some() {
+            depth += 1;
+        }
+    }
+    None
+}
+
+/// Parses `> [!KIND] title` into a lowercase kind and optional title.
+///
+/// With `any_kind`, as in Obsidian vaults, any alphabetic kind is accepted and a
+/// trailing `+`/`-` fold marker is ignored.
+fn alert_header(line: &str, any_kind: bool) -> Option<(String, Option<&str>)> {
+    let rest = line.strip_prefix('>')?.trim_start().strip_prefix("[!")?;
+    let (kind, title) = rest.split_once(']')?;
+    let kind = kind.to_ascii_lowercase();
+    if any_kind {
+        let title = title.strip_prefix(['+', '-']).unwrap_or(title);
+        let valid = !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic() || c == '-');
+        return valid.then(|| (kind, non_empty(title)));
+    }
+    KINDS.contains(&kind.as_str()).then(|| (kind, non_empty(title)))
+}
+
+/// Parses `:::name rest` into a lowercase name and the optional rest of the line.
+fn container_header(line: &str) -> Option<(String, Option<&str>)> {
+    let rest = line.strip_prefix(":::")?.trim_start();
+    let (name, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| (name.to_ascii_lowercase(), non_empty(rest)))
+}
+
+fn non_empty(text: &str) -> Option<&str> {
+    let text = text.trim();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Wraps a callout body in its HTML container and title.
+fn admonition(kind: &str, title: Option<&str>, body: &str, config: &AdmonitionConfig) -> String {
+    let class = config
+        .classes
+        .get(kind)
+        .cloned()
+        .unwrap_or_else(|| format!("{} {}", config.class, kind));
+    let default_title = format!("{}{}", kind[..1].to_ascii_uppercase(), &kind[1..]);
+    let title = tera::escape_html(title.unwrap_or(&default_title));
+
+    format!(
+        "<div class=\"{}\">\n<p class=\"{}-title\">{}</p>\n\n{}\n\n</div>\n",
+        class, config.class, title, body
+    )
+}
+
+/// Renders a generic container, either through its shortcode template or as a plain div.
+///
+/// Templates receive the rendered `body` HTML plus `classes` from the rest of the
+/// header line; the plain div gets those extra classes appended.
+fn container(name: &str, rest: Option<&str>, body: &str, site: &Site) -> Result<String> {
+    let mut body_html = String::new();
+    html::push_html(&mut body_html, MarkdownParser::new(body));
+
+    let mut context = TeraContext::new();
+    context.insert("body", &body_html);
+    context.insert("classes", &rest.unwrap_or_default());
+    if let Some(rendered) = shortcodes::render_template(name, &context, site)? {
+        return Ok(format!("{}\n", rendered.trim()));
+    }
+
+    let class = match rest {
+        Some(extra) => format!("{} {}", name, extra),
+        None => name.to_string(),
+    };
+    Ok(format!(
+        "<div class=\"{}\">\n\n{}\n\n</div>\n",
+        shortcodes::escape_attr(&class),
+        body
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admonition_markup() {
+        // Act
+        let html = admonition("warning", None, "Mind the **gap**.", &AdmonitionConfig::default());
+
+        // Assert
+        assert_eq!(
+            html,
+            "<div class=\"admonition warning\">\n<p class=\"admonition-title\">Warning</p>\n\nMind the **gap**.\n\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        // Act & Assert
+        assert_eq!(alert_header("> [!NOTE] Heads up", false), Some(("note".to_string(), Some("Heads up"))));
+        assert_eq!(alert_header("> just a quote", false), None);
+        assert_eq!(alert_header("> [!bug]- Crash", false), None);
+        assert_eq!(alert_header("> [!bug]- Crash", true), Some(("bug".to_string(), Some("Crash"))));
+        assert_eq!(container_header(":::two-column wide"), Some(("two-column".to_string(), Some("wide"))));
+        assert_eq!(container_header(":::"), None);
+    }
+
+    #[test]
+    fn test_closing_line() {
+        // Arrange
+        let lines = [":::tabs", ":::tab One", "First", ":::", ":::", ":::aside", "Never closed"];
+
+        // Act & Assert
+        assert_eq!(closing_line(&lines, 0), Some(4));
+        assert_eq!(closing_line(&lines, 1), Some(3));
+        assert_eq!(closing_line(&lines, 5), None);
+    }
+}
@@ -0,0 +1,138 @@
+// Rustic - JSON, TOML and CSV data files exposed to templates
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// Loads every `.json`, `.toml` and `.csv` file in `dir` into an object keyed by file stem.
+///
+/// A missing directory yields an empty object, so sites without data files work as before.
+pub fn load(dir: &Path) -> Result<Value> {
+    let mut data = Map::new();
+    if !dir.is_dir() {
+        return Ok(Value::Object(data));
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read data directory: {:?}", dir))?
+        .collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Some(format @ ("json" | "toml" | "csv")) = path.extension().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let raw = fs::read_to_string(&path).with_context(|| format!("Failed to read data file: {:?}", path))?;
+        let value = parse(&raw, format).with_context(|| format!("Failed to parse data file: {:?}", path))?;
+        data.insert(stem.to_string(), value);
+    }
+
+    Ok(Value::Object(data))
+}
+
+/// Loads `dir` over the data files shared with other sites, so the site's own file
+/// wins when both have one with the same stem.
+pub fn load_with_shared(dir: &Path, shared: Option<&Path>) -> Result<Value> {
+    let mut data = match shared {
+        Some(shared) => load(shared)?,
+        None => Value::Object(Map::new()),
+    };
+    if let (Value::Object(data), Value::Object(own)) = (&mut data, load(dir)?) {
+        data.extend(own);
+    }
+    Ok(data)
+}
+
+/// Parses a data file's contents; CSV becomes an array of objects keyed by the header row.
+pub fn parse(raw: &str, format: &str) -> Result<Value> {
+    match format {
+        "json" => Ok(serde_json::from_str(raw)?),
+        "toml" => Ok(serde_json::to_value(toml::from_str::<toml::Value>(raw)?)?),
+        "csv" => {
+            let mut rows = parse_csv(raw)?.into_iter();
+            let header = rows.next().unwrap_or_default();
+            let records = rows
+                .map(|row| {
+                    let fields = header.iter().cloned().zip(row.into_iter().map(Value::String));
+                    Value::Object(fields.collect())
+                })
+                .collect();
+            Ok(Value::Array(records))
+        }
+        _ => bail!("Unknown data format {:?}; expected json, toml or csv", format),
+    }
+}
+
+/// Splits CSV into rows of fields, following RFC 4180: fields may be quoted, and quoted
+/// fields may hold commas, newlines and doubled quotes. Blank lines are skipped.
+pub fn parse_csv(raw: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let (mut row, mut field) = (Vec::new(), String::new());
+    let (mut quoted, mut line) = (false, 1);
+    let mut chars = raw.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                line += 1;
+                row.push(std::mem::take(&mut field));
+                let finished = std::mem::take(&mut row);
+                if finished != [""] {
+                    rows.push(finished);
+                }
+            }
+            (_, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        bail!("Unterminated quoted field at line {}", line);
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_json_and_toml() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-data-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("authors.json"), r#"[{"name": "Ada"}]"#).unwrap();
+        fs::write(dir.join("links.toml"), "home = \"https://example.com\"").unwrap();
+        fs::write(dir.join("results.csv"), "name,time\r\n\"Ada, L.\",\"1\"\"2\"\r\n\r\nBob,3\n").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        // Act
+        let data = load(&dir).unwrap();
+
+        // Assert
+        assert_eq!(data["authors"][0]["name"], "Ada");
+        assert_eq!(data["links"]["home"], "https://example.com");
+        assert_eq!(data["results"], serde_json::json!([{ "name": "Ada, L.", "time": "1\"2" }, { "name": "Bob", "time": "3" }]));
+        assert!(data.get("notes").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
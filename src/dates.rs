@@ -0,0 +1,55 @@
+// Rustic - date parsing for front matter values
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+/// Parses a front matter date written as RFC 3339, `YYYY-MM-DD HH:MM[:SS]` or `YYYY-MM-DD`.
+///
+/// Dates without an offset are taken to be UTC.
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+/// Reads `SOURCE_DATE_EPOCH`, the reproducible-builds convention for pinning embedded timestamps.
+pub fn source_date_epoch() -> Result<Option<DateTime<Utc>>> {
+    let Ok(value) = std::env::var("SOURCE_DATE_EPOCH") else {
+        return Ok(None);
+    };
+    let seconds: i64 = value
+        .trim()
+        .parse()
+        .with_context(|| format!("SOURCE_DATE_EPOCH must be a Unix timestamp, got `{}`", value))?;
+    DateTime::from_timestamp(seconds, 0)
+        .map(Some)
+        .with_context(|| format!("SOURCE_DATE_EPOCH is out of range: {}", seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_formats() {
+        // Act
+        let day = parse_date("2024-05-01").unwrap();
+        let with_time = parse_date("2024-05-01 08:30").unwrap();
+        let rfc3339 = parse_date("2024-05-01T10:30:00+02:00").unwrap();
+
+        // Assert
+        assert_eq!(day.to_rfc3339(), "2024-05-01T00:00:00+00:00");
+        assert_eq!(with_time, rfc3339);
+        assert!(parse_date("May 1st").is_none());
+    }
+}
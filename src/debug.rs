@@ -0,0 +1,84 @@
+// Rustic - dumps of the template context, for debugging templates
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tera::Context as TeraContext;
+
+/// Writes the context a page's template receives to `<dir>/<page>.json`, e.g.
+/// `notes/rust.json` for `notes/rust.md`.
+pub fn write_context(dir: &Path, relative_path: &Path, context: &TeraContext) -> Result<()> {
+    let path = dir.join(relative_path).with_extension("json");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", path))?;
+    }
+    let json = serde_json::to_string_pretty(&context.clone().into_json())?;
+    fs::write(&path, json).with_context(|| format!("Failed to write template context: {:?}", path))
+}
+
+/// Prints the template context of one page as JSON on stdout.
+///
+/// The page is rendered by a partial build in a child process, so the context is exactly
+/// what a full build would pass and the build's own output stays off stdout.
+pub fn context(input_dir: &Path, out_dir: &Path, config: &Path, config_args: &[String], page: &Path) -> Result<()> {
+    let relative_path = page.strip_prefix(input_dir).unwrap_or(page);
+    if !input_dir.join(relative_path).is_file() {
+        bail!("{:?} is not a page under {:?}", page, input_dir);
+    }
+    let dump_dir = std::env::temp_dir().join(format!("rustic-context-{}", std::process::id()));
+    let exe = std::env::current_exe().context("Failed to locate the rustic executable")?;
+    let output = Command::new(exe)
+        .arg("--input-dir")
+        .arg(input_dir)
+        .arg("--out-dir")
+        .arg(out_dir)
+        .arg("--config")
+        .arg(config)
+        .args(config_args)
+        .arg("--dump-context")
+        .arg(&dump_dir)
+        .arg("build")
+        .arg("--only")
+        .arg(globset::escape(&relative_path.to_string_lossy().replace('\\', "/")))
+        .stdout(Stdio::null())
+        .output()
+        .context("Failed to run the build")?;
+    if !output.status.success() {
+        let _ = fs::remove_dir_all(&dump_dir);
+        bail!("Build failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let dump = dump_dir.join(relative_path).with_extension("json");
+    let json = fs::read_to_string(&dump);
+    let _ = fs::remove_dir_all(&dump_dir);
+    let Ok(json) = json else {
+        bail!("{:?} wasn't rendered; it may be a draft or not yet published", page);
+    };
+    writeln!(io::stdout(), "{}", json).context("Failed to write the context to stdout")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_context() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-debug-{}", std::process::id()));
+        let mut context = TeraContext::new();
+        context.insert("title", "Rust");
+        context.insert("path_prefix", "..");
+
+        // Act
+        write_context(&dir, Path::new("notes/rust.md"), &context).unwrap();
+
+        // Assert
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("notes/rust.json")).unwrap()).unwrap();
+        assert_eq!(json["title"], "Rust");
+        assert_eq!(json["path_prefix"], "..");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
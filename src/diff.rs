@@ -0,0 +1,183 @@
+// Rustic - `rustic diff`, comparing two builds of the site page by page
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::snapshot::files;
+
+/// Splits HTML into comments, tags and the text between them.
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->|<[^>]*>|[^<]+").unwrap());
+
+/// Files with more differing tokens than this, multiplied, are shown as replaced wholesale.
+const MAX_TABLE: usize = 16_000_000;
+
+/// One step of an edit script turning the old tokens into the new ones.
+#[derive(Debug, PartialEq)]
+enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Prints the files added to, removed from and changed between the `old` and `new`
+/// output directories, with a diff of each changed page around its changes.
+pub fn run(old: &Path, new: &Path, context: usize) -> Result<()> {
+    for dir in [old, new] {
+        if !dir.is_dir() {
+            bail!("{:?} isn't a directory", dir);
+        }
+    }
+    let (old_files, new_files) = (files(old)?, files(new)?);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+    for (path, old_file) in &old_files {
+        let Some(new_file) = new_files.get(path) else {
+            println!("- {}", path);
+            removed += 1;
+            continue;
+        };
+        let read = |file: &Path| fs::read(file).with_context(|| format!("Failed to read {:?}", file));
+        let (old_bytes, new_bytes) = (read(old_file)?, read(new_file)?);
+        if old_bytes == new_bytes {
+            continue;
+        }
+        let (Ok(old_text), Ok(new_text)) = (String::from_utf8(old_bytes), String::from_utf8(new_bytes)) else {
+            println!("~ {} (binary content differs)", path);
+            changed += 1;
+            continue;
+        };
+        let html = path.ends_with(".html") || path.ends_with(".htm");
+        let (old_tokens, new_tokens) = if html {
+            (html_tokens(&old_text), html_tokens(&new_text))
+        } else {
+            (text_lines(&old_text), text_lines(&new_text))
+        };
+        let old_tokens: Vec<&str> = old_tokens.iter().map(String::as_str).collect();
+        let new_tokens: Vec<&str> = new_tokens.iter().map(String::as_str).collect();
+        let edits = diff_tokens(&old_tokens, &new_tokens);
+        // Pages that only differ in formatting have no edits once tokenized
+        if edits.iter().all(|edit| matches!(edit, Edit::Same(_))) {
+            continue;
+        }
+        println!("~ {}", path);
+        for line in hunks(&edits, context) {
+            println!("    {}", line);
+        }
+        changed += 1;
+    }
+    for path in new_files.keys().filter(|path| !old_files.contains_key(*path)) {
+        println!("+ {}", path);
+        added += 1;
+    }
+
+    println!("{} added, {} removed, {} changed", added, removed, changed);
+    Ok(())
+}
+
+/// Tokens of an HTML page that matter to a reader: each tag, comment and run of text,
+/// with whitespace collapsed so reformatting doesn't show up as a change.
+fn html_tokens(html: &str) -> Vec<String> {
+    TOKEN
+        .find_iter(html)
+        .map(|m| m.as_str().split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+fn text_lines(text: &str) -> Vec<String> {
+    text.lines().map(|line| line.trim_end().to_string()).collect()
+}
+
+/// The shortest edit script between two token lists, from their longest common subsequence.
+fn diff_tokens<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut edits: Vec<Edit> = old[..prefix].iter().map(|&t| Edit::Same(t)).collect();
+    if a.len() * b.len() > MAX_TABLE {
+        edits.extend(a.iter().map(|&t| Edit::Removed(t)));
+        edits.extend(b.iter().map(|&t| Edit::Added(t)));
+    } else {
+        // lengths[i][j] is the LCS length of a[i..] and b[j..]
+        let width = b.len() + 1;
+        let mut lengths = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i * width + j] = if a[i] == b[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                edits.push(Edit::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                edits.push(Edit::Removed(a[i]));
+                i += 1;
+            } else {
+                edits.push(Edit::Added(b[j]));
+                j += 1;
+            }
+        }
+        edits.extend(a[i..].iter().map(|&t| Edit::Removed(t)));
+        edits.extend(b[j..].iter().map(|&t| Edit::Added(t)));
+    }
+    edits.extend(old[old.len() - suffix..].iter().map(|&t| Edit::Same(t)));
+    edits
+}
+
+/// Formats the changes with `context` unchanged tokens around each, `...` between hunks.
+fn hunks(edits: &[Edit], context: usize) -> Vec<String> {
+    let mut shown = vec![false; edits.len()];
+    for (i, _) in edits.iter().enumerate().filter(|(_, edit)| !matches!(edit, Edit::Same(_))) {
+        let end = (i + context).min(edits.len() - 1);
+        shown[i.saturating_sub(context)..=end].fill(true);
+    }
+    let mut lines = Vec::new();
+    let mut skipped = false;
+    for (i, edit) in edits.iter().enumerate() {
+        if !shown[i] {
+            skipped = true;
+            continue;
+        }
+        if skipped && !lines.is_empty() {
+            lines.push("...".to_string());
+        }
+        skipped = false;
+        lines.push(match edit {
+            Edit::Same(token) => format!("  {}", token),
+            Edit::Removed(token) => format!("- {}", token),
+            Edit::Added(token) => format!("+ {}", token),
+        });
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_diff() {
+        // Arrange
+        let old = html_tokens("<article>\n  <h1>Rust</h1>\n  <p>Fast and <em>safe</em>.</p>\n  <footer>2024</footer>\n</article>");
+        let new = html_tokens("<article><h1>Rust</h1><p>Fast,   safe and <em>fun</em>.</p><footer>2024</footer></article>");
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+        // Act
+        let lines = hunks(&diff_tokens(&old, &new), 1);
+
+        // Assert
+        assert_eq!(lines, ["  <p>", "- Fast and", "+ Fast, safe and", "  <em>", "- safe", "+ fun", "  </em>"]);
+    }
+}
@@ -0,0 +1,178 @@
+// Rustic - `rustic doctor`, checking the project layout and suggesting fixes
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tera::Tera;
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::{data, load_templates, shortcodes, Cli};
+
+/// Collects the outcome of each check, printing it as it goes.
+#[derive(Default)]
+struct Report {
+    problems: usize,
+}
+
+impl Report {
+    fn ok(&mut self, message: impl AsRef<str>) {
+        println!("✅ {}", message.as_ref());
+    }
+
+    fn problem(&mut self, message: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.problems += 1;
+        println!("⚠️  {}", message.as_ref());
+        println!("    → {}", fix.as_ref());
+    }
+}
+
+/// Checks that the config parses, the templates and theme load, there is content, and
+/// every shortcode the content calls has an implementation.
+pub fn run(cli: &Cli) -> Result<()> {
+    let mut report = Report::default();
+
+    let config = match Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides) {
+        Ok(config) if cli.config.exists() => {
+            report.ok(format!("{:?} parses", cli.config));
+            config
+        }
+        Ok(config) => {
+            report.ok(format!("No {:?}, so every setting has its default", cli.config));
+            config
+        }
+        Err(err) => {
+            report.problem(format!("{:#}", err), format!("Fix {:?}, or pick another file with --config", cli.config));
+            Config::default()
+        }
+    };
+
+    let mut theme = config.theme_dir.as_deref();
+    if let Some(dir) = theme {
+        if dir.is_dir() {
+            report.ok(format!("Theme {:?} found", dir));
+        } else {
+            report.problem(
+                format!("theme_dir {:?} doesn't exist", dir),
+                "Point theme_dir in the config at a directory of templates, or remove it",
+            );
+            theme = None;
+        }
+    }
+
+    let tera = if !Path::new("templates").is_dir() && theme.is_none() {
+        report.problem(
+            "There is no templates/ directory",
+            "Run rustic from the site's root, or create templates/ with a template.html",
+        );
+        None
+    } else {
+        match load_templates(theme) {
+            Ok(tera) => {
+                report.ok(format!("{} template(s) load", tera.get_template_names().count()));
+                Some(tera)
+            }
+            Err(err) => {
+                report.problem(format!("{:#}", err), "Fix the template named above");
+                None
+            }
+        }
+    };
+    if let Some(tera) = &tera {
+        if tera.get_template_names().any(|name| name == "template.html") {
+            report.ok("template.html found");
+        } else {
+            report.problem("There is no template.html", "Add templates/template.html, which renders every page");
+        }
+    }
+
+    let pages = markdown_files(&cli.input_dir);
+    if pages.is_empty() {
+        report.problem(
+            format!("No markdown pages in {:?}", cli.input_dir),
+            "Add pages there, or point --input-dir at the content directory",
+        );
+    } else {
+        report.ok(format!("{} page(s) in {:?}", pages.len(), cli.input_dir));
+    }
+
+    if let Some(tera) = &tera {
+        let missing = missing_shortcodes(&pages, tera)?;
+        if missing.is_empty() {
+            report.ok("Every shortcode the pages call has a template");
+        }
+        for (name, pages) in missing {
+            let shown: Vec<String> = pages.iter().take(3).map(|page| format!("{:?}", page)).collect();
+            let more = if pages.len() > 3 { format!(" and {} more", pages.len() - 3) } else { String::new() };
+            report.problem(
+                format!("The `{}` shortcode has no template; used in {}{}", name, shown.join(", "), more),
+                format!("Add templates/shortcodes/{}.html, or correct the shortcode's name", name),
+            );
+        }
+    }
+
+    match data::load_with_shared(Path::new("data"), config.shared_data_dir.as_deref()) {
+        Ok(_) => report.ok("Data files parse"),
+        Err(err) => report.problem(format!("{:#}", err), "Fix the data file named above"),
+    }
+
+    if report.problems > 0 {
+        bail!("Found {} problem(s)", report.problems);
+    }
+    println!("✅ Everything looks good");
+    Ok(())
+}
+
+fn markdown_files(input_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(input_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|path| path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect()
+}
+
+/// Shortcodes called by `pages` that are neither built in nor have a template, with
+/// the pages calling each.
+fn missing_shortcodes(pages: &[PathBuf], tera: &Tera) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+    let mut missing: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for page in pages {
+        let markdown = fs::read_to_string(page).with_context(|| format!("Failed to read markdown file: {:?}", page))?;
+        for name in shortcodes::calls(&markdown) {
+            let template = format!("shortcodes/{}.html", name);
+            if shortcodes::is_built_in(name) || tera.get_template_names().any(|t| t == template) {
+                continue;
+            }
+            let callers = missing.entry(name.to_string()).or_default();
+            if !callers.contains(page) {
+                callers.push(page.clone());
+            }
+        }
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_shortcodes() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-doctor-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("trip.md");
+        fs::write(&page, "{{ gallery(path=\"trip\") }}\n{{ figure(src=\"a.png\") }}\n{{ map(lat=1) }}\n{{ map(lat=2) }}\n").unwrap();
+        let mut tera = Tera::default();
+        tera.add_raw_template("shortcodes/figure.html", "<figure></figure>").unwrap();
+
+        // Act
+        let missing = missing_shortcodes(std::slice::from_ref(&page), &tera).unwrap();
+
+        // Assert
+        assert_eq!(missing, BTreeMap::from([("map".to_string(), vec![page])]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
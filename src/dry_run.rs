@@ -0,0 +1,151 @@
+// Rustic - `--dry-run`, previewing what a build would change in the output directory
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, Override, SKIP_COMMANDS};
+use crate::snapshot::files;
+use crate::{build, Cli, Command};
+
+/// What a build would do to an output directory.
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+    /// Files the build would create or change
+    pub write: Vec<String>,
+    /// Static assets the build would copy in, new or changed
+    pub copy: Vec<String>,
+    pub delete: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// Builds into a scratch directory and reports how that output differs from the real one.
+///
+/// Pages are parsed and rendered and plugins run as usual, but hooks and asset pipeline
+/// commands are skipped, since they can do anything.
+pub fn run(cli: &Cli) -> Result<()> {
+    let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+    let scratch = std::env::temp_dir().join(format!("rustic-dry-run-{}", std::process::id()));
+    let mut overrides = cli.overrides.clone();
+    for skipped in SKIP_COMMANDS {
+        overrides.push(skipped.parse::<Override>().map_err(anyhow::Error::msg)?);
+    }
+    if config.gemini.is_some() {
+        let value = toml::Value::String(scratch.join("gemini").to_string_lossy().into_owned());
+        overrides.push(format!("gemini.out_dir={}", value).parse::<Override>().map_err(anyhow::Error::msg)?);
+    }
+    // Partial builds leave the rest of the output alone, so nothing else would be deleted
+    let (command, partial) = match &cli.command {
+        Some(Command::Build { only, .. }) if !only.is_empty() => {
+            (Some(Command::Build { all: false, workspace: Default::default(), only: only.clone() }), true)
+        }
+        _ => (None, false),
+    };
+    let scratch_cli = Cli {
+        command,
+        input_dir: cli.input_dir.clone(),
+        out_dir: scratch.join("public"),
+        config: cli.config.clone(),
+        profile: cli.profile.clone(),
+        overrides,
+        timings: false,
+        unused_templates: false,
+        archive: None,
+        dump_context: None,
+        dry_run: false,
+    };
+
+    let result = build(&scratch_cli).and_then(|_| {
+        let mut plans = vec![(cli.out_dir.clone(), plan(&cli.out_dir, &scratch.join("public"), partial)?)];
+        if let Some(gemini) = &config.gemini {
+            plans.push((gemini.out_dir.clone(), plan(&gemini.out_dir, &scratch.join("gemini"), partial)?));
+        }
+        Ok(plans)
+    });
+    let _ = fs::remove_dir_all(&scratch);
+
+    for (out_dir, plan) in result? {
+        println!("\nDry run, nothing was written to {:?}:", out_dir);
+        for path in &plan.write {
+            println!("  write  {}", path);
+        }
+        for path in &plan.copy {
+            println!("  copy   {}", path);
+        }
+        for path in &plan.delete {
+            println!("  delete {}", path);
+        }
+        println!(
+            "Would write {} file(s), copy {} asset(s) and delete {} file(s); {} unchanged",
+            plan.write.len(),
+            plan.copy.len(),
+            plan.delete.len(),
+            plan.unchanged
+        );
+    }
+    Ok(())
+}
+
+/// Compares the current output in `out_dir` with a fresh build in `built`.
+pub fn plan(out_dir: &Path, built: &Path, partial: bool) -> Result<Plan> {
+    let existing = if out_dir.is_dir() { files(out_dir)? } else { Default::default() };
+    let mut plan = Plan::default();
+    for (path, file) in files(built)? {
+        let unchanged = match existing.get(&path) {
+            Some(current) => {
+                let read = |file: &Path| fs::read(file).with_context(|| format!("Failed to read {:?}", file));
+                read(current)? == read(&file)?
+            }
+            None => false,
+        };
+        if unchanged {
+            plan.unchanged += 1;
+        } else if path.starts_with("static/") {
+            plan.copy.push(path);
+        } else {
+            plan.write.push(path);
+        }
+    }
+    if !partial {
+        let built = files(built)?;
+        plan.delete = existing.into_keys().filter(|path| !built.contains_key(path)).collect();
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-dry-run-test-{}", std::process::id()));
+        let (out_dir, built) = (dir.join("public"), dir.join("scratch"));
+        for root in [&out_dir, &built] {
+            fs::create_dir_all(root.join("static")).unwrap();
+            fs::write(root.join("index.html"), "<h1>Home</h1>").unwrap();
+        }
+        fs::write(out_dir.join("about.html"), "<h1>About</h1>").unwrap();
+        fs::write(out_dir.join("old.html"), "").unwrap();
+        fs::write(built.join("about.html"), "<h1>About us</h1>").unwrap();
+        fs::write(built.join("static/logo.svg"), "<svg/>").unwrap();
+
+        // Act
+        let full = plan(&out_dir, &built, false).unwrap();
+        let partial = plan(&out_dir, &built, true).unwrap();
+
+        // Assert
+        assert_eq!(
+            full,
+            Plan {
+                write: vec!["about.html".to_string()],
+                copy: vec!["static/logo.svg".to_string()],
+                delete: vec!["old.html".to_string()],
+                unchanged: 1,
+            }
+        );
+        assert!(partial.delete.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
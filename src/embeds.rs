@@ -0,0 +1,106 @@
+// Rustic - YouTube, Vimeo and Mastodon embed shortcodes with click-to-load placeholders
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tera::Context as TeraContext;
+
+use crate::shortcodes::{self, escape_attr, string_arg, Args};
+use crate::Site;
+
+/// How third-party embeds are rendered, set with `embed_mode` in rustic.toml.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbedMode {
+    /// Render a placeholder; nothing is requested from the provider until the reader clicks
+    #[default]
+    Privacy,
+    /// Render the provider's iframe directly
+    Full,
+}
+
+/// Swaps the placeholder for the real iframe once the reader consents.
+const LOAD_ON_CLICK: &str = "var e=this.parentNode,f=document.createElement('iframe');f.src=e.dataset.embedSrc;f.title=e.dataset.embedTitle;f.allow='autoplay; fullscreen; picture-in-picture';f.allowFullscreen=true;f.className=e.className;e.replaceWith(f)";
+
+/// A resolved embed, as exposed to `templates/shortcodes/<provider>.html`.
+#[derive(Debug, Serialize)]
+struct Embed {
+    provider: &'static str,
+    /// URL of the iframe
+    src: String,
+    /// Link to the content on the provider's own site
+    link: String,
+    title: String,
+    privacy: bool,
+}
+
+/// Renders `{{ youtube(id="...") }}`, `{{ vimeo(id="...") }}` or `{{ mastodon(url="...") }}`.
+pub fn render(name: &str, args: &Args, site: &Site) -> Result<String> {
+    let privacy = site.config.embed_mode == EmbedMode::Privacy;
+    let title = args.get("title").and_then(|v| v.as_str());
+
+    let embed = match name {
+        "youtube" => {
+            let id = string_arg(args, "id")?;
+            let host = if privacy { "www.youtube-nocookie.com" } else { "www.youtube.com" };
+            Embed {
+                provider: "YouTube",
+                src: format!("https://{}/embed/{}{}", host, id, if privacy { "?autoplay=1" } else { "" }),
+                link: format!("https://www.youtube.com/watch?v={}", id),
+                title: title.unwrap_or("YouTube video").to_string(),
+                privacy,
+            }
+        }
+        "vimeo" => {
+            let id = string_arg(args, "id")?;
+            Embed {
+                provider: "Vimeo",
+                src: format!("https://player.vimeo.com/video/{}?dnt=1{}", id, if privacy { "&autoplay=1" } else { "" }),
+                link: format!("https://vimeo.com/{}", id),
+                title: title.unwrap_or("Vimeo video").to_string(),
+                privacy,
+            }
+        }
+        "mastodon" => {
+            let url = string_arg(args, "url")?.trim_end_matches('/');
+            if !url.starts_with("https://") {
+                bail!("Mastodon embeds need an https:// post URL, got `{}`", url);
+            }
+            Embed {
+                provider: "Mastodon",
+                src: format!("{}/embed", url),
+                link: url.to_string(),
+                title: title.unwrap_or("Mastodon post").to_string(),
+                privacy,
+            }
+        }
+        _ => bail!("Unknown embed provider `{}`", name),
+    };
+
+    let context = TeraContext::from_serialize(&embed)?;
+    if let Some(html) = shortcodes::render_template(name, &context, site)? {
+        return Ok(html);
+    }
+
+    let class = format!("embed embed-{}", name);
+    if !privacy {
+        return Ok(format!(
+            "<iframe class=\"{}\" src=\"{}\" title=\"{}\" allow=\"fullscreen; picture-in-picture\" allowfullscreen loading=\"lazy\"></iframe>",
+            class,
+            escape_attr(&embed.src),
+            escape_attr(&embed.title),
+        ));
+    }
+
+    Ok(format!(
+        "<div class=\"{class}\" data-embed-src=\"{src}\" data-embed-title=\"{title}\">\n\
+         <button type=\"button\" class=\"embed-consent\" onclick=\"{script}\">Load {provider} content</button>\n\
+         <p class=\"embed-notice\">The button loads content from {provider}, which may set cookies. <a href=\"{link}\">Open on {provider}</a> instead.</p>\n\
+         </div>",
+        class = class,
+        src = escape_attr(&embed.src),
+        title = escape_attr(&embed.title),
+        script = LOAD_ON_CLICK,
+        provider = embed.provider,
+        link = escape_attr(&embed.link),
+    ))
+}
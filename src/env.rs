@@ -0,0 +1,64 @@
+// Rustic - `get_env()` for environment variables allow-listed in rustic.toml
+
+use std::collections::HashMap;
+use tera::{Function, Value};
+
+/// Tera function reading environment variables named in the config's `template_env`.
+///
+/// `{{ get_env(name="CI_COMMIT_SHA", default="dev") }}` fails the build for names that
+/// aren't allow-listed, so templates can't leak secrets from the environment by accident.
+pub struct GetEnv {
+    allowed: Vec<String>,
+}
+
+impl GetEnv {
+    pub fn new(allowed: Vec<String>) -> Self {
+        Self { allowed }
+    }
+}
+
+impl Function for GetEnv {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let name = args
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or("get_env needs a `name` argument")?;
+        if !self.allowed.iter().any(|allowed| allowed == name) {
+            return Err(format!("get_env: {:?} is not listed in `template_env` in rustic.toml", name).into());
+        }
+
+        match (std::env::var(name), args.get("default")) {
+            (Ok(value), _) => Ok(Value::String(value)),
+            (Err(_), Some(default)) => Ok(default.clone()),
+            (Err(_), None) => Err(format!("get_env: {:?} is not set and has no default", name).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_env_only_reads_allowed_names() {
+        // Arrange
+        let get_env = GetEnv::new(vec!["PATH".to_string(), "RUSTIC_TEST_UNSET_VARIABLE".to_string()]);
+        let args = |name: &str, default: Option<&str>| {
+            let mut args = HashMap::from([("name".to_string(), Value::from(name))]);
+            if let Some(default) = default {
+                args.insert("default".to_string(), Value::from(default));
+            }
+            args
+        };
+
+        // Act
+        let path = get_env.call(&args("PATH", None));
+        let unset = get_env.call(&args("RUSTIC_TEST_UNSET_VARIABLE", Some("dev")));
+        let home = get_env.call(&args("HOME", Some("nope")));
+
+        // Assert
+        assert_eq!(path.unwrap(), Value::from(std::env::var("PATH").unwrap()));
+        assert_eq!(unset.unwrap(), Value::from("dev"));
+        assert!(home.is_err());
+    }
+}
@@ -0,0 +1,264 @@
+// Rustic - EPUB export of a section, for reading documentation offline on e-readers
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::bundle::short_hash;
+use crate::config::Config;
+use crate::dates::source_date_epoch;
+use crate::export::{self, Chapter};
+use crate::feed::escape_xml;
+use crate::zip::ZipWriter;
+
+/// Book metadata, from `rustic export epub` options and the site config.
+pub struct Book {
+    pub title: String,
+    pub author: Option<String>,
+    pub language: String,
+    /// Image shown on the cover page; a title page is generated without one
+    pub cover: Option<PathBuf>,
+}
+
+/// Writes the published pages of `section` (or the whole site) to an EPUB 3 file.
+pub fn export(input_dir: &Path, config: &Config, section: Option<&str>, book: &Book, output: &Path) -> Result<()> {
+    let chapters = export::chapters(input_dir, config, section)?;
+    let file = File::create(output).with_context(|| format!("Failed to create EPUB: {:?}", output))?;
+    write(&chapters, book, input_dir, BufWriter::new(file))
+        .with_context(|| format!("Failed to write EPUB: {:?}", output))?;
+    println!("✅ Wrote {} pages to {:?}", chapters.len(), output);
+    Ok(())
+}
+
+/// An image embedded in the book, as `OEBPS/images/<name>`.
+struct Image {
+    name: String,
+    media_type: &'static str,
+    data: Vec<u8>,
+}
+
+/// Assembles the book: its `mimetype` first and uncompressed, as readers require,
+/// then the container, package document, navigation, cover and one file per chapter.
+pub fn write<W: std::io::Write>(chapters: &[Chapter], book: &Book, input_dir: &Path, out: W) -> Result<W> {
+    let files: BTreeMap<&str, String> = chapters
+        .iter()
+        .enumerate()
+        .map(|(i, chapter)| (chapter.url.as_str(), chapter_file(i)))
+        .collect();
+    let mut images: BTreeMap<PathBuf, Image> = BTreeMap::new();
+
+    let cover = match &book.cover {
+        Some(path) => Some(embed(path, &mut images)?.context("The cover must be a PNG, JPEG, GIF, SVG or WebP image")?),
+        None => None,
+    };
+    let mut bodies = Vec::new();
+    for chapter in chapters {
+        bodies.push(chapter_body(chapter, &files, input_dir, &mut images)?);
+    }
+
+    let mut zip = ZipWriter::new(out);
+    zip.add("mimetype", b"application/epub+zip", false)?;
+    zip.add("META-INF/container.xml", CONTAINER.as_bytes(), true)?;
+    zip.add("OEBPS/content.opf", package(chapters, book, cover.as_deref(), &images)?.as_bytes(), true)?;
+    zip.add("OEBPS/nav.xhtml", nav(chapters, book).as_bytes(), true)?;
+    zip.add("OEBPS/cover.xhtml", cover_page(book, cover.as_deref()).as_bytes(), true)?;
+    for (i, (chapter, body)) in chapters.iter().zip(bodies).enumerate() {
+        let title = escape_xml(&chapter.title);
+        let xhtml = xhtml(&book.language, &title, &format!("<h1>{}</h1>\n{}", title, body));
+        zip.add(&format!("OEBPS/{}", chapter_file(i)), xhtml.as_bytes(), true)?;
+    }
+    for image in images.values() {
+        zip.add(&format!("OEBPS/images/{}", image.name), &image.data, true)?;
+    }
+    zip.finish()
+}
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n</rootfiles>\n\
+</container>\n";
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+/// Points local images at embedded copies and links between exported pages at their chapters.
+fn chapter_body(
+    chapter: &Chapter,
+    files: &BTreeMap<&str, String>,
+    input_dir: &Path,
+    images: &mut BTreeMap<PathBuf, Image>,
+) -> Result<String> {
+    let images = RefCell::new(images);
+    let html = rewrite_str(
+        &chapter.html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("img[src]", |el| {
+                let src = el.get_attribute("src").unwrap_or_default();
+                let Some(path) = export::local_file(&src, &chapter.dir, input_dir) else {
+                    eprintln!("⚠️  Image {:?} in {} isn't a local file and won't be in the book", src, chapter.url);
+                    return Ok(());
+                };
+                match embed(&path, &mut images.borrow_mut())? {
+                    Some(name) => el.set_attribute("src", &name)?,
+                    None => eprintln!("⚠️  Image {:?} in {} has an unsupported format", src, chapter.url),
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("a[href]", |el| {
+                let href = el.get_attribute("href").unwrap_or_default();
+                if let Some(file) = export::resolve_link(&href, &chapter.url).and_then(|url| files.get(url.as_str())) {
+                    let fragment = href.find('#').map(|i| &href[i..]).unwrap_or("");
+                    el.set_attribute("href", &format!("{}{}", file, fragment))?;
+                }
+                Ok(())
+            })),
+    )?;
+    Ok(html)
+}
+
+/// Adds an image to the book once, returning its path relative to the chapters.
+fn embed(path: &Path, images: &mut BTreeMap<PathBuf, Image>) -> Result<Option<String>> {
+    if let Some(image) = images.get(path) {
+        return Ok(Some(format!("images/{}", image.name)));
+    }
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    let media_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => return Ok(None),
+    };
+    let data = fs::read(path).with_context(|| format!("Failed to read image: {:?}", path))?;
+    let name = format!("image-{:03}.{}", images.len() + 1, extension);
+    images.insert(path.to_path_buf(), Image { name: name.clone(), media_type, data });
+    Ok(Some(format!("images/{}", name)))
+}
+
+/// The package document listing the book's metadata, files and reading order.
+fn package(chapters: &[Chapter], book: &Book, cover: Option<&str>, images: &BTreeMap<PathBuf, Image>) -> Result<String> {
+    // Stable across rebuilds of the same pages, so readers treat a new export as an update
+    let key: Vec<&str> = std::iter::once(book.title.as_str()).chain(chapters.iter().map(|c| c.url.as_str())).collect();
+    let identifier = format!("urn:rustic:{}", short_hash(key.join("\n").as_bytes()));
+    let modified = source_date_epoch()?.unwrap_or_else(Utc::now);
+
+    let mut opf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opf.push_str(&format!(
+        "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{}\">\n",
+        escape_xml(&book.language)
+    ));
+    opf.push_str("<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    opf.push_str(&format!("<dc:identifier id=\"book-id\">{}</dc:identifier>\n", identifier));
+    opf.push_str(&format!("<dc:title>{}</dc:title>\n", escape_xml(&book.title)));
+    opf.push_str(&format!("<dc:language>{}</dc:language>\n", escape_xml(&book.language)));
+    if let Some(author) = &book.author {
+        opf.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape_xml(author)));
+    }
+    opf.push_str(&format!("<meta property=\"dcterms:modified\">{}</meta>\n", modified.format("%Y-%m-%dT%H:%M:%SZ")));
+    opf.push_str("</metadata>\n<manifest>\n");
+    opf.push_str("<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n");
+    opf.push_str("<item id=\"cover\" href=\"cover.xhtml\" media-type=\"application/xhtml+xml\"/>\n");
+    for i in 0..chapters.len() {
+        opf.push_str(&format!(
+            "<item id=\"chapter-{:03}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter_file(i)
+        ));
+    }
+    for image in images.values() {
+        let href = format!("images/{}", image.name);
+        let properties = if cover == Some(href.as_str()) { " properties=\"cover-image\"" } else { "" };
+        opf.push_str(&format!(
+            "<item id=\"{}\" href=\"{}\" media-type=\"{}\"{}/>\n",
+            image.name.replace('.', "-"),
+            href,
+            image.media_type,
+            properties
+        ));
+    }
+    opf.push_str("</manifest>\n<spine>\n<itemref idref=\"cover\"/>\n<itemref idref=\"nav\"/>\n");
+    for i in 0..chapters.len() {
+        opf.push_str(&format!("<itemref idref=\"chapter-{:03}\"/>\n", i + 1));
+    }
+    opf.push_str("</spine>\n</package>\n");
+    Ok(opf)
+}
+
+/// The table of contents, one entry per chapter.
+fn nav(chapters: &[Chapter], book: &Book) -> String {
+    let mut body = String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+    for (i, chapter) in chapters.iter().enumerate() {
+        body.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", chapter_file(i), escape_xml(&chapter.title)));
+    }
+    body.push_str("</ol>\n</nav>");
+    xhtml(&book.language, "Contents", &body)
+}
+
+/// The cover image if there is one, otherwise a page with the title and author.
+fn cover_page(book: &Book, cover: Option<&str>) -> String {
+    let title = escape_xml(&book.title);
+    let body = match cover {
+        Some(src) => format!("<img src=\"{}\" alt=\"{}\" style=\"max-width: 100%; max-height: 100%\"/>", src, title),
+        None => {
+            let author = book.author.as_deref().map(|a| format!("\n<p>{}</p>", escape_xml(a))).unwrap_or_default();
+            format!("<h1>{}</h1>{}", title, author)
+        }
+    };
+    xhtml(&book.language, &title, &format!("<section epub:type=\"cover\">\n{}\n</section>", body))
+}
+
+/// Wraps a body in an XHTML document; `title` must already be escaped.
+fn xhtml(language: &str, title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"{0}\" xml:lang=\"{0}\">\n\
+         <head>\n<meta charset=\"UTF-8\"/>\n<title>{1}</title>\n</head>\n<body>\n{2}\n</body>\n</html>\n",
+        escape_xml(language),
+        title,
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epub_layout() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-epub-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("diagram.png"), b"png").unwrap();
+        let chapters = vec![
+            Chapter {
+                title: "Intro".to_string(),
+                url: "docs/index.html".to_string(),
+                dir: dir.clone(),
+                html: "<p><img src=\"diagram.png\" alt=\"\" /> See <a href=\"setup.html#run\">setup</a>.</p>\n".to_string(),
+            },
+            Chapter { title: "Setup".to_string(), url: "docs/setup.html".to_string(), dir: dir.clone(), html: String::new() },
+        ];
+        let book = Book { title: "Docs".to_string(), author: None, language: "en".to_string(), cover: None };
+
+        // Act
+        let files = BTreeMap::from([("docs/index.html", chapter_file(0)), ("docs/setup.html", chapter_file(1))]);
+        let body = chapter_body(&chapters[0], &files, &dir, &mut BTreeMap::new()).unwrap();
+        let bytes = write(&chapters, &book, &dir, Vec::new()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Assert
+        assert_eq!(
+            body,
+            "<p><img src=\"images/image-001.png\" alt=\"\" /> See <a href=\"chapter-002.xhtml#run\">setup</a>.</p>\n"
+        );
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..58], b"application/epub+zip");
+    }
+}
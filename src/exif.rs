@@ -0,0 +1,279 @@
+// Rustic - strips EXIF/GPS metadata from published images, keeping their orientation
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// JPEG segments that carry camera and location metadata: APP1 (EXIF, XMP) and APP13 (IPTC).
+const JPEG_METADATA_MARKERS: [u8; 2] = [0xE1, 0xED];
+
+/// PNG chunks that carry EXIF data or free-form text such as XMP packets.
+const PNG_METADATA_CHUNKS: [&[u8; 4]; 4] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt"];
+
+/// WebP chunks that carry EXIF data and XMP packets.
+const WEBP_METADATA_CHUNKS: [&[u8; 4]; 2] = [b"EXIF", b"XMP "];
+
+/// The EXIF tag saying how a photo must be rotated or flipped to display upright.
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// Strips metadata in place from a JPEG, PNG or WebP, returning whether the file changed.
+///
+/// Other file types are left alone. Files that can't be parsed are published as they are,
+/// with a warning.
+pub fn strip_file(path: &Path) -> Result<bool> {
+    let Some(strip) = stripper(path) else {
+        return Ok(false);
+    };
+
+    let bytes = fs::read(path).with_context(|| format!("Failed to read image: {:?}", path))?;
+    match strip(&bytes) {
+        Some(clean) if clean == bytes => Ok(false),
+        Some(clean) => {
+            fs::write(path, clean)
+                .with_context(|| format!("Failed to write stripped image: {:?}", path))?;
+            Ok(true)
+        }
+        None => {
+            eprintln!("⚠️  Couldn't parse {:?}, so it is published with its metadata", path);
+            Ok(false)
+        }
+    }
+}
+
+/// The bytes of the image at `path` without its metadata, or `None` if it isn't a type
+/// metadata is stripped from or can't be parsed.
+pub fn stripped(path: &Path, bytes: &[u8]) -> Option<Vec<u8>> {
+    stripper(path)?(bytes)
+}
+
+/// Whether `path` is an image type metadata is stripped from.
+pub fn is_image(path: &Path) -> bool {
+    stripper(path).is_some()
+}
+
+/// Removes metadata from an image's bytes, or returns `None` if the file isn't one we understand.
+type Stripper = fn(&[u8]) -> Option<Vec<u8>>;
+
+fn stripper(path: &Path) -> Option<Stripper> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Some(strip_jpeg),
+        "png" => Some(strip_png),
+        "webp" => Some(strip_webp),
+        _ => None,
+    }
+}
+
+/// Removes metadata segments from a JPEG, or returns `None` if it isn't a JPEG we understand.
+///
+/// An EXIF Orientation other than upright survives in a minimal APP1 segment of its own,
+/// so portrait photos still display the right way up.
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..2]);
+    let mut pos = 2;
+
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+
+        // Start of scan: the rest is entropy-coded image data
+        if marker == 0xDA {
+            break;
+        }
+
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let end = pos + 2 + length;
+        if length < 2 || end > bytes.len() {
+            return None;
+        }
+
+        if JPEG_METADATA_MARKERS.contains(&marker) {
+            if let Some(orientation) = (marker == 0xE1).then(|| orientation(&bytes[pos + 4..end])).flatten() {
+                out.extend_from_slice(&orientation_segment(orientation));
+            }
+        } else {
+            out.extend_from_slice(&bytes[pos..end]);
+        }
+        pos = end;
+    }
+
+    out.extend_from_slice(&bytes[pos..]);
+    Some(out)
+}
+
+/// The Orientation in an APP1 segment's EXIF data, unless it is missing or upright.
+fn orientation(app1: &[u8]) -> Option<u16> {
+    let tiff = app1.strip_prefix(b"Exif\0\0")?;
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    };
+    let u32_at = |at: usize| {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    };
+
+    // Orientation lives in the first IFD, as a single SHORT stored in the entry itself
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    let entry = (0..entries).map(|n| ifd + 2 + n * 12).find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))?;
+    u16_at(entry + 8).filter(|orientation| (2..=8).contains(orientation))
+}
+
+/// An APP1 segment whose EXIF data holds nothing but `orientation`.
+pub(crate) fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut segment = vec![0xFF, 0xE1, 0x00, 34];
+    segment.extend_from_slice(b"Exif\0\0");
+    // Big-endian TIFF header, then IFD0 with one entry and no next IFD
+    segment.extend_from_slice(b"MM\x00\x2A\x00\x00\x00\x08\x00\x01");
+    segment.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    segment.extend_from_slice(&[0x00, 0x03, 0x00, 0x00, 0x00, 0x01]);
+    segment.extend_from_slice(&orientation.to_be_bytes());
+    segment.extend_from_slice(&[0x00; 6]);
+    segment
+}
+
+/// Removes metadata chunks from a PNG, or returns `None` if it isn't a PNG we understand.
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !bytes.starts_with(SIGNATURE) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(SIGNATURE);
+    let mut pos = SIGNATURE.len();
+
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        let end = pos + 12 + length; // length + type + data + crc
+        if end > bytes.len() {
+            return None;
+        }
+
+        let chunk_type = &bytes[pos + 4..pos + 8];
+        if !PNG_METADATA_CHUNKS.iter().any(|t| t.as_slice() == chunk_type) {
+            out.extend_from_slice(&bytes[pos..end]);
+        }
+        pos = end;
+    }
+
+    out.extend_from_slice(&bytes[pos..]);
+    Some(out)
+}
+
+/// Removes metadata chunks from a WebP, or returns `None` if it isn't a WebP we understand.
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..12]);
+    let mut pos = 12;
+
+    while pos + 8 <= bytes.len() {
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        if pos + 8 + size > bytes.len() {
+            return None;
+        }
+        // Chunks are padded to an even length
+        let end = (pos + 8 + size + size % 2).min(bytes.len());
+
+        let fourcc = &bytes[pos..pos + 4];
+        if !WEBP_METADATA_CHUNKS.iter().any(|t| t.as_slice() == fourcc) {
+            let start = out.len();
+            out.extend_from_slice(&bytes[pos..end]);
+            // The extended header flags which metadata chunks follow
+            if fourcc == b"VP8X" && size > 0 {
+                out[start + 8] &= !0x0C;
+            }
+        }
+        pos = end;
+    }
+
+    out.extend_from_slice(&bytes[pos..]);
+    let riff_size = u32::try_from(out.len() - 8).ok()?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_jpeg_removes_app1() {
+        // Arrange
+        let jfif = [0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46];
+        let exif = [0xFF, 0xE1, 0x00, 0x06, b'E', b'x', b'i', b'f'];
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+        let jpeg = [&[0xFF, 0xD8][..], &jfif, &exif, &scan].concat();
+
+        // Act
+        let stripped = strip_jpeg(&jpeg).unwrap();
+
+        // Assert
+        assert_eq!(stripped, [&[0xFF, 0xD8][..], &jfif, &scan].concat());
+        assert_eq!(strip_jpeg(&stripped).unwrap(), stripped);
+        assert!(strip_jpeg(b"GIF89a").is_none());
+    }
+
+    #[test]
+    fn test_strip_jpeg_keeps_orientation() {
+        // Arrange: little-endian EXIF with a camera model and Orientation 6 (rotate 90°)
+        let mut tiff = b"II\x2A\x00\x08\x00\x00\x00\x02\x00".to_vec();
+        tiff.extend_from_slice(&[0x10, 0x01, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, b'A', b'B', b'C', 0x00]);
+        tiff.extend_from_slice(&[0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00]);
+        tiff.extend_from_slice(&[0x00; 4]);
+        let length = (2 + 6 + tiff.len()) as u16;
+        let exif = [&[0xFF, 0xE1][..], &length.to_be_bytes(), b"Exif\0\0", &tiff].concat();
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+        let jpeg = [&[0xFF, 0xD8][..], &exif, &scan].concat();
+
+        // Act
+        let stripped = strip_jpeg(&jpeg).unwrap();
+
+        // Assert
+        assert_eq!(stripped, [&[0xFF, 0xD8][..], &orientation_segment(6), &scan].concat());
+        assert_eq!(orientation(&stripped[6..]), Some(6));
+        assert!(!stripped.windows(3).any(|w| w == b"ABC"));
+        assert_eq!(strip_jpeg(&stripped).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_strip_webp_removes_exif_and_xmp() {
+        // Arrange
+        let chunk = |fourcc: &[u8], data: &[u8]| {
+            let padding = if data.len() % 2 == 1 { &[0u8][..] } else { &[] };
+            [fourcc, &(data.len() as u32).to_le_bytes(), data, padding].concat()
+        };
+        let body = [
+            chunk(b"VP8X", &[0x0C, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            chunk(b"VP8 ", b"pixels"),
+            chunk(b"EXIF", b"GPS"),
+            chunk(b"XMP ", b"<x/>"),
+        ]
+        .concat();
+        let webp = [b"RIFF", &((body.len() + 4) as u32).to_le_bytes(), b"WEBP", &body[..]].concat();
+
+        // Act
+        let stripped = strip_webp(&webp).unwrap();
+
+        // Assert
+        let body = [chunk(b"VP8X", &[0; 10]), chunk(b"VP8 ", b"pixels")].concat();
+        assert_eq!(stripped, [b"RIFF", &((body.len() + 4) as u32).to_le_bytes(), b"WEBP", &body[..]].concat());
+    }
+}
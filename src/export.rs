@@ -0,0 +1,138 @@
+// Rustic - pages gathered for `rustic export`, rendered without the site's templates
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use pulldown_cmark::{html, Parser as MarkdownParser};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::{include, read_page};
+
+/// A published page, rendered to an HTML fragment.
+#[derive(Debug)]
+pub struct Chapter {
+    pub title: String,
+    /// Output path of the page, relative to the site root, e.g. `docs/install.html`
+    pub url: String,
+    /// Directory of the markdown source, which relative image paths start from
+    pub dir: PathBuf,
+    /// The markdown body as HTML; shortcodes and wiki links are left as written
+    pub html: String,
+}
+
+/// Reads the published pages of `section` (or of the whole site), the section's
+/// `index.md` first and the rest by `weight`, lightest first, then by path.
+///
+/// Redirects, unlisted and protected pages are left out, as they are from feeds.
+pub fn chapters(input_dir: &Path, config: &Config, section: Option<&str>) -> Result<Vec<Chapter>> {
+    let root = match section {
+        Some(section) => input_dir.join(section),
+        None => input_dir.to_path_buf(),
+    };
+    if !root.is_dir() {
+        bail!("There is no section at {:?}", root);
+    }
+
+    let cache = Cache::new(config.cache_dir.clone());
+    let now = Utc::now();
+    let mut pages = Vec::new();
+    for entry in WalkDir::new(&root).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+        let (front_matter, markdown) = read_page(path, config, &cache)?;
+        if !front_matter.is_published(config.drafts, config.future, now)? || front_matter.redirect_to.is_some()
+            || front_matter.unlisted
+            || front_matter.protected
+        {
+            continue;
+        }
+        let markdown = include::expand(&markdown, path)?;
+        let mut html = String::new();
+        html::push_html(&mut html, MarkdownParser::new(&markdown));
+        let is_index = path == root.join("index.md");
+        let chapter = Chapter {
+            title: front_matter.title,
+            url: path.strip_prefix(input_dir)?.with_extension("html").to_string_lossy().replace('\\', "/"),
+            dir: path.parent().unwrap_or(input_dir).to_path_buf(),
+            html,
+        };
+        pages.push((!is_index, front_matter.weight.is_none(), front_matter.weight, chapter));
+    }
+    // Stable, so pages without a weight keep path order
+    pages.sort_by_key(|(not_index, unweighted, weight, _)| (*not_index, *unweighted, *weight));
+    Ok(pages.into_iter().map(|(.., chapter)| chapter).collect())
+}
+
+/// Finds the file a page's image `src` refers to, looking next to the page, then
+/// from the content root and the project root for site-relative paths like `/static/a.png`.
+pub fn local_file(src: &str, page_dir: &Path, input_dir: &Path) -> Option<PathBuf> {
+    if src.is_empty() || src.starts_with("//") || src.contains(':') {
+        return None;
+    }
+    let src = src.split(['?', '#']).next().unwrap_or(src);
+    let candidates = match src.strip_prefix('/') {
+        Some(site_relative) => vec![input_dir.join(site_relative), PathBuf::from(site_relative)],
+        None => vec![page_dir.join(src)],
+    };
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+/// Resolves a link from the page at `url` to a site-relative path, e.g. `../a.html`
+/// from `docs/guide/b.html` to `docs/a.html`. Returns `None` for links off the site.
+pub fn resolve_link(href: &str, url: &str) -> Option<String> {
+    if href.is_empty() || href.starts_with("//") || href.contains(':') || href.starts_with('#') {
+        return None;
+    }
+    let path = href.split(['?', '#']).next().unwrap_or(href);
+    let joined = match path.strip_prefix('/') {
+        Some(site_relative) => PathBuf::from(site_relative),
+        None => Path::new(url).parent().unwrap_or(Path::new("")).join(path),
+    };
+    let mut parts: Vec<String> = Vec::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => {}
+        }
+    }
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_chapters_leave_out_protected_pages() {
+        // Arrange
+        let root = std::env::temp_dir().join(format!("rustic-export-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("open.md"), "---\ntitle: Open\nstinger: s\n---\nHello\n").unwrap();
+        fs::write(root.join("members.md"), "---\ntitle: Members\nstinger: s\nprotected: true\n---\nSecret\n").unwrap();
+
+        // Act
+        let chapters = chapters(&root, &Config::default(), None).unwrap();
+
+        // Assert
+        let titles: Vec<_> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Open"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_link() {
+        // Act & Assert
+        assert_eq!(resolve_link("../a.html#top", "docs/guide/b.html").as_deref(), Some("docs/a.html"));
+        assert_eq!(resolve_link("/docs/c.html", "docs/guide/b.html").as_deref(), Some("docs/c.html"));
+        assert_eq!(resolve_link("https://example.com/", "docs/b.html"), None);
+        assert_eq!(resolve_link("../../../x.html", "docs/b.html"), None);
+    }
+}
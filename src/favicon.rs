@@ -0,0 +1,69 @@
+// Rustic - favicon set generated from a single source image
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::ImageFormat;
+use std::fs;
+use std::path::Path;
+
+/// PNG icons written to the site root: (file name, size in pixels).
+const PNG_ICONS: [(&str, u32); 5] = [
+    ("favicon-16x16.png", 16),
+    ("favicon-32x32.png", 32),
+    ("apple-touch-icon.png", 180),
+    ("android-chrome-192x192.png", 192),
+    ("android-chrome-512x512.png", 512),
+];
+
+/// Generates the favicon set from `source` into `out_dir` and returns the `<link>`
+/// tags that reference it.
+///
+/// SVG sources are published as-is, since rustic can't rasterize them; use a PNG
+/// (ideally 512x512 or larger) to get the full set of sizes.
+pub fn generate(source: &Path, out_dir: &Path) -> Result<Vec<String>> {
+    let is_svg = source
+        .extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    if is_svg {
+        fs::copy(source, out_dir.join("favicon.svg"))
+            .with_context(|| format!("Failed to copy favicon: {:?}", source))?;
+        return Ok(vec![
+            r#"<link rel="icon" type="image/svg+xml" href="/favicon.svg" />"#.to_string(),
+        ]);
+    }
+
+    let image = image::open(source)
+        .with_context(|| format!("Failed to read favicon source: {:?}", source))?;
+    for (file_name, size) in PNG_ICONS {
+        let path = out_dir.join(file_name);
+        image
+            .resize_to_fill(size, size, FilterType::Lanczos3)
+            .save_with_format(&path, ImageFormat::Png)
+            .with_context(|| format!("Failed to write favicon: {:?}", path))?;
+    }
+
+    // Legacy favicon.ico for browsers and tools that request it directly
+    let ico_path = out_dir.join("favicon.ico");
+    image
+        .resize_to_fill(32, 32, FilterType::Lanczos3)
+        .save_with_format(&ico_path, ImageFormat::Ico)
+        .with_context(|| format!("Failed to write favicon: {:?}", ico_path))?;
+
+    let manifest = serde_json::json!({
+        "icons": [
+            { "src": "/android-chrome-192x192.png", "sizes": "192x192", "type": "image/png" },
+            { "src": "/android-chrome-512x512.png", "sizes": "512x512", "type": "image/png" },
+        ]
+    });
+    let manifest_path = out_dir.join("site.webmanifest");
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write web manifest: {:?}", manifest_path))?;
+
+    Ok(vec![
+        r#"<link rel="icon" type="image/png" sizes="32x32" href="/favicon-32x32.png" />"#.to_string(),
+        r#"<link rel="icon" type="image/png" sizes="16x16" href="/favicon-16x16.png" />"#.to_string(),
+        r#"<link rel="apple-touch-icon" sizes="180x180" href="/apple-touch-icon.png" />"#.to_string(),
+        r#"<link rel="manifest" href="/site.webmanifest" />"#.to_string(),
+    ])
+}
@@ -0,0 +1,444 @@
+// Rustic - RSS feed generation
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tera::Context as TeraContext;
+
+use crate::dates::{parse_date, source_date_epoch};
+use crate::listing::PageListing;
+use crate::{PageSummary, Site};
+
+/// Settings for the podcast feed, declared under `[podcast]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+pub struct PodcastConfig {
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    /// Cover art URL, ideally a 3000x3000 JPEG or PNG
+    pub image: Option<String>,
+    #[serde(default = "default_language")]
+    pub language: String,
+    /// Apple Podcasts category, e.g. "Technology"
+    pub category: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    /// Contact address published in `<itunes:owner>`
+    pub owner_email: Option<String>,
+    /// Where the feed is written, relative to the output directory
+    #[serde(default = "default_podcast_path")]
+    pub path: String,
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_podcast_path() -> String {
+    "podcast.xml".to_string()
+}
+
+/// Writes an iTunes-compatible podcast feed with one item per page that sets `audio`.
+/// Unlisted pages are left out, since their item would publish the secret URL.
+pub fn write_podcast(
+    podcast: &PodcastConfig,
+    base_url: &str,
+    pages: &[PageSummary],
+    out_dir: &Path,
+) -> Result<()> {
+    if base_url.is_empty() {
+        bail!("The podcast feed needs `base_url` to be set in rustic.toml");
+    }
+
+    // Newest episodes first
+    let mut episodes = Vec::new();
+    for page in pages.iter().filter(|page| !page.front_matter.unlisted) {
+        let Some(audio) = &page.front_matter.audio else {
+            continue;
+        };
+        let date = page
+            .front_matter
+            .date
+            .as_deref()
+            .and_then(parse_date)
+            .with_context(|| format!("Podcast episode {} needs a valid `date`", page.url))?;
+        episodes.push((date, page, audio));
+    }
+    episodes.sort_by_key(|(date, ..)| std::cmp::Reverse(*date));
+
+    let feed_url = absolute_url(base_url, &podcast.path);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\" xmlns:podcast=\"https://podcastindex.org/namespace/1.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<channel>\n");
+    xml.push_str(&format!("<title>{}</title>\n", escape_xml(&podcast.title)));
+    xml.push_str(&format!("<link>{}</link>\n", escape_xml(base_url)));
+    xml.push_str(&format!("<description>{}</description>\n", escape_xml(&podcast.description)));
+    xml.push_str(&format!("<language>{}</language>\n", escape_xml(&podcast.language)));
+    // Dated by SOURCE_DATE_EPOCH or the newest episode rather than the clock, so rebuilds are byte-identical
+    let last_build = source_date_epoch()?.or_else(|| episodes.first().map(|(date, ..)| *date));
+    if let Some(last_build) = last_build {
+        xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", last_build.to_rfc2822()));
+    }
+    xml.push_str(&format!(
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_xml(&feed_url)
+    ));
+    xml.push_str(&format!("<itunes:author>{}</itunes:author>\n", escape_xml(&podcast.author)));
+    xml.push_str(&format!("<itunes:explicit>{}</itunes:explicit>\n", podcast.explicit));
+    if let Some(image) = &podcast.image {
+        xml.push_str(&format!(
+            "<itunes:image href=\"{}\"/>\n",
+            escape_xml(&absolute_url(base_url, image))
+        ));
+    }
+    if let Some(category) = &podcast.category {
+        xml.push_str(&format!("<itunes:category text=\"{}\"/>\n", escape_xml(category)));
+    }
+    if let Some(email) = &podcast.owner_email {
+        xml.push_str(&format!(
+            "<itunes:owner><itunes:name>{}</itunes:name><itunes:email>{}</itunes:email></itunes:owner>\n",
+            escape_xml(&podcast.author),
+            escape_xml(email)
+        ));
+    }
+    xml.push_str("<podcast:locked>no</podcast:locked>\n");
+
+    for (date, page, audio) in episodes {
+        let link = absolute_url(base_url, &page.url);
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape_xml(&page.front_matter.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape_xml(&link)));
+        xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", escape_xml(&link)));
+        xml.push_str(&format!("<pubDate>{}</pubDate>\n", date.to_rfc2822()));
+        if let Some(description) = &page.front_matter.description {
+            xml.push_str(&format!("<description>{}</description>\n", escape_xml(description)));
+        }
+        for author in &page.authors {
+            xml.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape_xml(&author.name)));
+        }
+        xml.push_str(&format!(
+            "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            escape_xml(&absolute_url(base_url, audio)),
+            audio_length(audio, out_dir),
+            audio_mime_type(audio)
+        ));
+        if let Some(duration) = &page.front_matter.duration {
+            xml.push_str(&format!("<itunes:duration>{}</itunes:duration>\n", escape_xml(duration)));
+        }
+        xml.push_str("<itunes:episodeType>full</itunes:episodeType>\n");
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+
+    let feed_path = out_dir.join(&podcast.path);
+    fs::write(&feed_path, xml)
+        .with_context(|| format!("Failed to write podcast feed: {:?}", feed_path))
+}
+
+/// What section and taxonomy feeds include, set under `[feeds]` in rustic.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Most items a feed lists, newest first; every page when unset
+    pub limit: Option<usize>,
+    /// Whether items carry the page description or its whole rendered body
+    pub content: FeedContent,
+    /// List pages marked `draft: true` when drafts are published, e.g. in a preview profile
+    pub drafts: bool,
+    /// Sections whose pages no feed lists
+    pub exclude_sections: Vec<String>,
+    /// Write `rss.xml` at the root of each language, e.g. `/rss.xml` and `/fr/rss.xml`
+    pub languages: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// The page's `description`
+    #[default]
+    Summary,
+    /// The rendered page body in `<content:encoded>`, as well as the description
+    Full,
+}
+
+/// Channel details of a plain RSS feed of pages.
+pub struct Channel<'a> {
+    pub title: String,
+    pub description: String,
+    /// Page the feed belongs to, site-relative
+    pub link: &'a str,
+    /// Where the feed is written, site-relative
+    pub path: &'a str,
+    pub language: &'a str,
+}
+
+/// A feed ready to render, exposed to `templates/feed.xml` as `feed`.
+#[derive(Debug, Serialize)]
+pub struct Feed<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    /// Absolute URL of the page the feed belongs to
+    pub link: String,
+    /// Absolute URL of the feed itself
+    pub feed_url: String,
+    pub language: &'a str,
+    /// RFC 2822 date of the newest item, or `SOURCE_DATE_EPOCH`
+    pub last_build_date: Option<String>,
+    pub items: Vec<Item<'a>>,
+}
+
+/// One page in a feed.
+#[derive(Debug, Serialize)]
+pub struct Item<'a> {
+    pub title: &'a str,
+    /// Absolute URL of the page, also used as its guid
+    pub link: String,
+    pub pub_date: Option<String>,
+    pub description: Option<&'a str>,
+    /// Rendered page body, with `content = "full"`
+    pub content: Option<&'a str>,
+    /// The page as listed in `site.pages`
+    pub page: &'a PageListing,
+}
+
+impl<'a> Feed<'a> {
+    /// Picks and describes the items of a feed of `pages`, in the order given, shaped by `config`.
+    ///
+    /// `bodies` holds each page's rendered HTML by URL, for feeds with full content.
+    pub fn new(
+        channel: &'a Channel,
+        pages: &[&'a PageListing],
+        config: &FeedConfig,
+        bodies: &'a HashMap<String, String>,
+        base_url: &str,
+    ) -> Result<Self> {
+        if base_url.is_empty() {
+            bail!("Feeds need `base_url` to be set in rustic.toml");
+        }
+
+        let pages = pages
+            .iter()
+            .filter(|page| config.drafts || !page.draft)
+            .filter(|page| page.section.as_ref().is_none_or(|section| !config.exclude_sections.contains(section)))
+            .take(config.limit.unwrap_or(usize::MAX));
+        let mut newest = None;
+        let mut items = Vec::new();
+        for page in pages {
+            let date = page.date.as_deref().and_then(parse_date);
+            newest = newest.max(date);
+            items.push(Item {
+                title: &page.title,
+                link: absolute_url(base_url, &page.url),
+                pub_date: date.map(|date| date.to_rfc2822()),
+                description: page.description.as_deref(),
+                content: bodies.get(&page.url).filter(|_| config.content == FeedContent::Full).map(String::as_str),
+                page,
+            });
+        }
+        // Dated like the podcast feed, so rebuilds are byte-identical
+        let last_build = source_date_epoch()?.or(newest);
+
+        Ok(Self {
+            title: &channel.title,
+            description: &channel.description,
+            link: absolute_url(base_url, channel.link),
+            feed_url: absolute_url(base_url, channel.path),
+            language: channel.language,
+            last_build_date: last_build.map(|date| date.to_rfc2822()),
+            items,
+        })
+    }
+
+    /// The feed as RSS 2.0, when there is no `templates/feed.xml`.
+    pub fn to_rss(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\">\n<channel>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape_xml(self.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape_xml(&self.link)));
+        xml.push_str(&format!("<description>{}</description>\n", escape_xml(self.description)));
+        xml.push_str(&format!("<language>{}</language>\n", escape_xml(self.language)));
+        if let Some(last_build) = &self.last_build_date {
+            xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", last_build));
+        }
+        xml.push_str(&format!(
+            "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+            escape_xml(&self.feed_url)
+        ));
+
+        for item in &self.items {
+            xml.push_str("<item>\n");
+            xml.push_str(&format!("<title>{}</title>\n", escape_xml(item.title)));
+            xml.push_str(&format!("<link>{}</link>\n", escape_xml(&item.link)));
+            xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", escape_xml(&item.link)));
+            if let Some(date) = &item.pub_date {
+                xml.push_str(&format!("<pubDate>{}</pubDate>\n", date));
+            }
+            if let Some(description) = item.description {
+                xml.push_str(&format!("<description>{}</description>\n", escape_xml(description)));
+            }
+            if let Some(content) = item.content {
+                xml.push_str(&format!("<content:encoded>{}</content:encoded>\n", escape_xml(content)));
+            }
+            xml.push_str("</item>\n");
+        }
+        xml.push_str("</channel>\n</rss>\n");
+        xml
+    }
+}
+
+/// Writes a feed of `pages` to `channel.path`, through `templates/feed.xml` when the site has one.
+pub fn write_rss(channel: &Channel, pages: &[&PageListing], bodies: &HashMap<String, String>, site: &Site) -> Result<()> {
+    let feed = Feed::new(channel, pages, &site.config.feeds, bodies, &site.config.base_url)?;
+    let xml = if site.tera.get_template_names().any(|name| name == "feed.xml") {
+        let mut context = TeraContext::new();
+        context.insert("feed", &feed);
+        context.insert("site", site.globals_for(channel.language));
+        context.insert("data", &site.data);
+        site.render("feed.xml", &context)
+            .with_context(|| format!("Failed to render feed.xml for {}", channel.path))?
+    } else {
+        feed.to_rss()
+    };
+
+    let feed_path = site.out_dir.join(channel.path.trim_start_matches('/'));
+    if let Some(parent) = feed_path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", feed_path))?;
+    }
+    fs::write(&feed_path, xml).with_context(|| format!("Failed to write feed: {:?}", feed_path))
+}
+
+/// Size in bytes of a locally published audio file, or 0 when it is hosted elsewhere.
+fn audio_length(audio: &str, out_dir: &Path) -> u64 {
+    if audio.contains("://") {
+        return 0;
+    }
+    fs::metadata(out_dir.join(audio.trim_start_matches('/')))
+        .map(|m| m.len())
+        .unwrap_or(0)
+}
+
+/// Guesses the enclosure MIME type from the audio file extension.
+fn audio_mime_type(audio: &str) -> &'static str {
+    let extension = audio.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    match extension.as_str() {
+        "m4a" => "audio/x-m4a",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "aac" => "audio/aac",
+        "flac" => "audio/flac",
+        _ => "audio/mpeg",
+    }
+}
+
+/// Joins a site-relative URL onto the configured base URL.
+pub fn absolute_url(base_url: &str, url: &str) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    format!("{}/{}", base_url.trim_end_matches('/'), url.trim_start_matches('/'))
+}
+
+/// Escapes text for use in XML element content and attribute values.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute_url() {
+        // Act & Assert
+        assert_eq!(absolute_url("https://example.com/", "/ep/1.mp3"), "https://example.com/ep/1.mp3");
+        assert_eq!(absolute_url("https://example.com", "podcast.xml"), "https://example.com/podcast.xml");
+        assert_eq!(absolute_url("https://example.com", "https://cdn.example.com/1.mp3"), "https://cdn.example.com/1.mp3");
+    }
+
+    #[test]
+    fn test_podcast_leaves_out_unlisted_episodes() {
+        // Arrange
+        let out_dir = std::env::temp_dir().join(format!("rustic-podcast-{}", std::process::id()));
+        fs::create_dir_all(&out_dir).unwrap();
+        let episode = |url: &str, title: &str, unlisted: bool| PageSummary {
+            url: url.to_string(),
+            front_matter: serde_json::from_value(serde_json::json!({
+                "title": title,
+                "stinger": "",
+                "date": "2024-05-01",
+                "audio": "/ep/1.mp3",
+                "unlisted": unlisted,
+            }))
+            .unwrap(),
+            authors: Vec::new(),
+            content: String::new(),
+        };
+        let pages = [
+            episode("/ep/one.html", "Episode one", false),
+            episode("/ep/two-4bdba79f81cf1694.html", "Secret episode", true),
+        ];
+        let podcast: PodcastConfig =
+            toml::from_str("title = \"Show\"\ndescription = \"\"\nauthor = \"Me\"").unwrap();
+
+        // Act
+        write_podcast(&podcast, "https://example.com", &pages, &out_dir).unwrap();
+
+        // Assert
+        let xml = fs::read_to_string(out_dir.join("podcast.xml")).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+        assert!(xml.contains("Episode one"));
+        assert!(!xml.contains("Secret episode"));
+        assert!(!xml.contains("4bdba79f81cf1694"));
+    }
+
+    #[test]
+    fn test_rss_applies_feed_config() {
+        // Arrange
+        let page = |url: &str, section: &str, draft: bool| PageListing {
+            title: url.to_string(),
+            url: url.to_string(),
+            date: None,
+            description: Some("Summary".to_string()),
+            lang: "en".to_string(),
+            section: Some(section.to_string()),
+            weight: None,
+            series: None,
+            draft,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: Default::default(),
+            menu: None,
+            sitemap: None,
+        };
+        let pages = [
+            page("/blog/draft.html", "blog", true),
+            page("/notes/a.html", "notes", false),
+            page("/blog/c.html", "blog", false),
+            page("/blog/b.html", "blog", false),
+        ];
+        let config = FeedConfig {
+            limit: Some(1),
+            content: FeedContent::Full,
+            exclude_sections: vec!["notes".to_string()],
+            ..Default::default()
+        };
+        let bodies = HashMap::from([("/blog/c.html".to_string(), "<p>Body & more</p>".to_string())]);
+        let channel = Channel { title: "Blog".to_string(), description: String::new(), link: "/", path: "rss.xml", language: "en" };
+
+        // Act
+        let feed = Feed::new(&channel, &pages.iter().collect::<Vec<_>>(), &config, &bodies, "https://example.com").unwrap();
+        let xml = feed.to_rss();
+
+        // Assert
+        assert_eq!(feed.items.len(), 1);
+        assert_eq!(feed.feed_url, "https://example.com/rss.xml");
+        assert!(xml.contains("<link>https://example.com/blog/c.html</link>"));
+        assert!(xml.contains("<content:encoded>&lt;p&gt;Body &amp; more&lt;/p&gt;</content:encoded>"));
+    }
+}
@@ -0,0 +1,83 @@
+// Rustic - `cached_include()` renders shared template fragments once per build
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tera::{Context as TeraContext, Function, Tera, Value};
+
+/// Tera function that renders a partial once for each distinct set of arguments.
+///
+/// `{{ cached_include(template="partials/nav.html", section=section) }}` sees only the
+/// arguments it is given plus site-wide values (`bundles`, `data`, `site`), so every
+/// page passing the same arguments reuses the first render.
+pub struct CachedInclude {
+    tera: Tera,
+    globals: TeraContext,
+    renders: Mutex<HashMap<String, String>>,
+}
+
+impl CachedInclude {
+    pub fn new(tera: Tera, globals: TeraContext) -> Self {
+        Self {
+            tera,
+            globals,
+            renders: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Function for CachedInclude {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let template = args
+            .get("template")
+            .and_then(Value::as_str)
+            .ok_or("cached_include needs a `template` argument")?;
+
+        // Sorted, so the key doesn't depend on argument order
+        let args: BTreeMap<&String, &Value> = args.iter().collect();
+        let key = serde_json::to_string(&args).map_err(tera::Error::json)?;
+        if let Some(html) = self.renders.lock().unwrap().get(&key) {
+            return Ok(Value::String(html.clone()));
+        }
+
+        let mut context = self.globals.clone();
+        for (name, value) in args.iter().filter(|(name, _)| name.as_str() != "template") {
+            context.insert(name.as_str(), value);
+        }
+        let html = self.tera.render(template, &context)?;
+        self.renders.lock().unwrap().insert(key, html.clone());
+        Ok(Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_include_renders_once_per_arguments() {
+        // Arrange
+        let mut partials = Tera::default();
+        partials.add_raw_template("nav.html", "<nav>{{ section }}</nav>").unwrap();
+        let include = CachedInclude::new(partials, TeraContext::new());
+        let args = |section: &str| {
+            HashMap::from([
+                ("template".to_string(), Value::from("nav.html")),
+                ("section".to_string(), Value::from(section)),
+            ])
+        };
+
+        // Act
+        let blog = include.call(&args("blog")).unwrap();
+        include.call(&args("blog")).unwrap();
+        let docs = include.call(&args("docs")).unwrap();
+
+        // Assert
+        assert_eq!(blog, "<nav>blog</nav>");
+        assert_eq!(docs, "<nav>docs</nav>");
+        assert_eq!(include.renders.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,163 @@
+// Rustic - gallery shortcode that turns a content folder into a thumbnail grid
+
+use anyhow::{bail, Context, Result};
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use tera::Context as TeraContext;
+
+use crate::shortcodes::{self, escape_attr, string_arg, Args};
+use crate::{exif, Site};
+
+/// Thumbnail width used when the shortcode doesn't pass `thumb_width`.
+const DEFAULT_THUMB_WIDTH: u32 = 400;
+
+/// File extensions picked up from the gallery folder.
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "gif", "webp"];
+
+/// One image in the grid, as exposed to `templates/shortcodes/gallery.html`.
+#[derive(Debug, Serialize)]
+struct GalleryImage {
+    url: String,
+    thumb_url: String,
+    alt: String,
+    width: u32,
+    height: u32,
+}
+
+/// Renders `{{ gallery(path="trip-2024") }}`: publishes every image in the content
+/// subfolder along with a thumbnail, and returns lightbox-ready grid markup.
+pub fn render(args: &Args, site: &Site) -> Result<String> {
+    let folder = string_arg(args, "path")?.trim_matches('/');
+    let thumb_width = args
+        .get("thumb_width")
+        .and_then(Value::as_u64)
+        .map_or(DEFAULT_THUMB_WIDTH, |w| w as u32);
+
+    let source_dir = site.input_dir.join(folder);
+    if !source_dir.is_dir() {
+        bail!("Gallery folder not found: {:?}", source_dir);
+    }
+    let target_dir = site.out_dir.join(folder);
+    let thumbs_dir = target_dir.join("thumbs");
+    fs::create_dir_all(&thumbs_dir)
+        .with_context(|| format!("Failed to create gallery directory: {:?}", thumbs_dir))?;
+
+    let mut sources: Vec<_> = fs::read_dir(&source_dir)
+        .with_context(|| format!("Failed to read gallery folder: {:?}", source_dir))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .and_then(|s| s.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    sources.sort();
+
+    let mut images = Vec::new();
+    for source in &sources {
+        let file_name = source.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        // Publish the full-size image
+        let published = target_dir.join(&file_name);
+        fs::copy(source, &published)
+            .with_context(|| format!("Failed to copy gallery image: {:?}", source))?;
+        if !site.config.keep_image_metadata {
+            exif::strip_file(&published)?;
+        }
+
+        // Generate the thumbnail shown in the grid, unless one newer than the image exists
+        let thumb_path = thumbs_dir.join(&file_name);
+        let (width, height) = if is_newer(&thumb_path, source) {
+            let size = imagesize::size(&thumb_path)
+                .with_context(|| format!("Failed to read thumbnail size: {:?}", thumb_path))?;
+            (size.width as u32, size.height as u32)
+        } else {
+            let image = decode_upright(source)
+                .with_context(|| format!("Failed to decode gallery image: {:?}", source))?;
+            let thumb = if image.width() > thumb_width {
+                image.thumbnail(thumb_width, u32::MAX)
+            } else {
+                image
+            };
+            thumb
+                .save(&thumb_path)
+                .with_context(|| format!("Failed to write thumbnail: {:?}", thumb_path))?;
+            (thumb.width(), thumb.height())
+        };
+
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        images.push(GalleryImage {
+            url: format!("/{}/{}", folder, file_name),
+            thumb_url: format!("/{}/thumbs/{}", folder, file_name),
+            alt: stem.replace(['-', '_'], " "),
+            width,
+            height,
+        });
+    }
+
+    let mut context = TeraContext::new();
+    context.insert("path", folder);
+    context.insert("images", &images);
+    if let Some(html) = shortcodes::render_template("gallery", &context, site)? {
+        return Ok(html);
+    }
+
+    let mut html = String::from("<div class=\"gallery\">\n");
+    for image in &images {
+        html.push_str(&format!(
+            "<a class=\"gallery-item\" href=\"{}\" data-gallery=\"{}\"><img src=\"{}\" alt=\"{}\" width=\"{}\" height=\"{}\" loading=\"lazy\" decoding=\"async\" /></a>\n",
+            escape_attr(&image.url),
+            escape_attr(folder),
+            escape_attr(&image.thumb_url),
+            escape_attr(&image.alt),
+            image.width,
+            image.height,
+        ));
+    }
+    html.push_str("</div>");
+    Ok(html)
+}
+
+/// Decodes an image turned the way its EXIF orientation says, as viewers show it.
+fn decode_upright(path: &Path) -> Result<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?.with_guessed_format()?.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Whether `path` exists and was written after `source` last changed.
+fn is_newer(path: &Path, source: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    matches!((modified(path), modified(source)), (Some(made), Some(changed)) if made > changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_upright_applies_exif_orientation() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("rustic-gallery-{}.jpg", std::process::id()));
+        let mut jpeg = Vec::new();
+        DynamicImage::new_rgb8(4, 2)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        // Orientation 6: the camera was turned a quarter clockwise
+        let rotated = [&jpeg[..2], &exif::orientation_segment(6), &jpeg[2..]].concat();
+        fs::write(&path, rotated).unwrap();
+
+        // Act
+        let image = decode_upright(&path).unwrap();
+
+        // Assert
+        assert_eq!((image.width(), image.height()), (2, 4));
+        fs::remove_file(&path).unwrap();
+    }
+}
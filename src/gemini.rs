@@ -0,0 +1,245 @@
+// Rustic - gemtext copy of the site for serving over the Gemini protocol
+
+use anyhow::{Context, Result};
+use pulldown_cmark::{Event, HeadingLevel, Parser as MarkdownParser, Tag};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dates::{parse_date, source_date_epoch};
+use crate::feed::{absolute_url, escape_xml};
+use crate::listing::{Listing, PageListing};
+
+/// Settings for the Gemini copy of the site, declared under `[gemini]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct GeminiConfig {
+    /// Directory the `.gmi` files are written to
+    pub out_dir: PathBuf,
+    /// Public `gemini://` URL of the capsule, needed for its Atom feed
+    pub base_url: String,
+}
+
+impl Default for GeminiConfig {
+    fn default() -> Self {
+        Self { out_dir: PathBuf::from("public_gemini"), base_url: String::new() }
+    }
+}
+
+/// Converts markdown to gemtext.
+///
+/// Gemtext has no inline markup, so emphasis is dropped and links are listed as `=>`
+/// lines after the block they appear in. Raw HTML, such as expanded shortcodes, is left out.
+pub fn to_gemtext(markdown: &str) -> String {
+    let mut writer = Writer::default();
+    for event in MarkdownParser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading(level, ..)) => {
+                let depth = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    _ => 3,
+                };
+                writer.prefix = format!("{} ", "#".repeat(depth));
+            }
+            Event::Start(Tag::BlockQuote) => writer.quotes += 1,
+            Event::End(Tag::BlockQuote) => writer.quotes -= 1,
+            Event::Start(Tag::Item) => {
+                // Text of an enclosing item, when lists are nested
+                writer.flush_line();
+                writer.prefix = "* ".to_string();
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                writer.lines.push("```".to_string());
+                writer.in_code = true;
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                writer.in_code = false;
+                writer.lines.push("```".to_string());
+                writer.lines.push(String::new());
+            }
+            Event::Start(Tag::Link(..) | Tag::Image(..)) => writer.link_start = writer.line.len(),
+            Event::End(Tag::Link(_, url, _) | Tag::Image(_, url, _)) => {
+                let text = writer.line[writer.link_start..].trim().to_string();
+                writer.links.push(format!("=> {} {}", gemini_url(&url), text).trim_end().to_string());
+            }
+            Event::End(Tag::Paragraph | Tag::Heading(..) | Tag::Item) => writer.flush(),
+            Event::Start(Tag::List(_)) => writer.lists += 1,
+            Event::End(Tag::List(_)) => {
+                writer.lists -= 1;
+                if writer.lists == 0 {
+                    writer.blank();
+                }
+            }
+            Event::Text(text) if writer.in_code => writer.lines.extend(text.lines().map(str::to_string)),
+            Event::Text(text) | Event::Code(text) => writer.line.push_str(&text),
+            Event::SoftBreak => writer.line.push(' '),
+            Event::HardBreak => writer.flush_line(),
+            Event::Rule => {
+                writer.lines.push("---".to_string());
+                writer.blank();
+            }
+            _ => {}
+        }
+    }
+    writer.flush();
+    let mut gemtext = writer.lines.join("\n");
+    gemtext.truncate(gemtext.trim_end().len());
+    gemtext.push('\n');
+    gemtext
+}
+
+/// Gemtext being assembled line by line.
+#[derive(Default)]
+struct Writer {
+    lines: Vec<String>,
+    /// Text of the block being read
+    line: String,
+    /// Marker for the block's first line, such as `# ` or `* `
+    prefix: String,
+    /// Link lines to write once the block ends
+    links: Vec<String>,
+    link_start: usize,
+    quotes: usize,
+    lists: usize,
+    in_code: bool,
+}
+
+impl Writer {
+    fn flush_line(&mut self) {
+        let line = self.line.trim();
+        if !line.is_empty() {
+            let quote = if self.quotes > 0 { "> " } else { "" };
+            self.lines.push(format!("{}{}{}", quote, std::mem::take(&mut self.prefix), line));
+        }
+        self.line.clear();
+    }
+
+    fn flush(&mut self) {
+        self.flush_line();
+        self.lines.append(&mut self.links);
+        if self.lists == 0 {
+            self.blank();
+        }
+        self.prefix.clear();
+    }
+
+    fn blank(&mut self) {
+        if self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+}
+
+/// Points links at other pages of the site to their `.gmi` copies.
+fn gemini_url(url: &str) -> String {
+    if url.contains(':') {
+        return url.to_string();
+    }
+    let (path, rest) = url.split_at(url.find(['#', '?']).unwrap_or(url.len()));
+    match path.strip_suffix(".html") {
+        Some(stem) => format!("{}.gmi{}", stem, rest),
+        None => url.to_string(),
+    }
+}
+
+/// Removes the previous build's copy, which is generated from scratch each time.
+pub fn clean(config: &GeminiConfig) -> Result<()> {
+    if config.out_dir.exists() {
+        fs::remove_dir_all(&config.out_dir)
+            .with_context(|| format!("Failed to remove existing Gemini output: {:?}", config.out_dir))?;
+    }
+    Ok(())
+}
+
+/// Writes a page's gemtext next to where its HTML would be, e.g. `notes/rust.gmi`.
+pub fn write_page(config: &GeminiConfig, relative_path: &Path, title: &str, markdown: &str) -> Result<()> {
+    let path = config.out_dir.join(relative_path).with_extension("gmi");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", path))?;
+    }
+    fs::write(&path, format!("# {}\n\n{}", title, to_gemtext(markdown)))
+        .with_context(|| format!("Failed to write gemtext: {:?}", path))
+}
+
+/// Lists every page in `index.gmi`, newest first, under the home page's own text if it has one.
+///
+/// Dated pages are listed as `=> url YYYY-MM-DD Title`, so Gemini clients can subscribe
+/// to the index as a feed. An Atom feed is also written when `base_url` is set.
+pub fn write_index(config: &GeminiConfig, site_title: &str, listing: &Listing) -> Result<()> {
+    let mut pages: Vec<&PageListing> = listing.pages.iter().filter(|page| page.url != "/index.html").collect();
+    pages.sort_by_key(|page| std::cmp::Reverse(page.date.as_deref().and_then(parse_date)));
+
+    let path = config.out_dir.join("index.gmi");
+    let mut index = fs::read_to_string(&path).unwrap_or_else(|_| format!("# {}\n", site_title));
+    index.push_str("\n## Pages\n\n");
+    for page in &pages {
+        let url = gemini_url(&page.url);
+        match page.date.as_deref().and_then(parse_date) {
+            Some(date) => index.push_str(&format!("=> {} {} - {}\n", url, date.format("%Y-%m-%d"), page.title)),
+            None => index.push_str(&format!("=> {} {}\n", url, page.title)),
+        }
+    }
+    fs::create_dir_all(&config.out_dir)?;
+    fs::write(&path, index).with_context(|| format!("Failed to write gemtext: {:?}", path))?;
+
+    if !config.base_url.is_empty() {
+        write_atom(config, site_title, &pages)?;
+    }
+    Ok(())
+}
+
+/// Writes `atom.xml` with the dated pages, for feed readers that speak Gemini.
+fn write_atom(config: &GeminiConfig, site_title: &str, pages: &[&PageListing]) -> Result<()> {
+    let dated: Vec<_> = pages
+        .iter()
+        .filter_map(|page| Some((page.date.as_deref().and_then(parse_date)?, page)))
+        .collect();
+    // Dated like the RSS feeds, so rebuilds are byte-identical
+    let updated = source_date_epoch()?.or_else(|| dated.first().map(|(date, _)| *date));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<title>{}</title>\n", escape_xml(site_title)));
+    xml.push_str(&format!("<id>{}</id>\n", escape_xml(&absolute_url(&config.base_url, "/"))));
+    xml.push_str(&format!("<link href=\"{}\"/>\n", escape_xml(&absolute_url(&config.base_url, "/"))));
+    xml.push_str(&format!("<link rel=\"self\" href=\"{}\"/>\n", escape_xml(&absolute_url(&config.base_url, "atom.xml"))));
+    if let Some(updated) = updated {
+        xml.push_str(&format!("<updated>{}</updated>\n", updated.to_rfc3339()));
+    }
+    for (date, page) in dated {
+        let url = escape_xml(&absolute_url(&config.base_url, &gemini_url(&page.url)));
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<title>{}</title>\n<id>{}</id>\n<link href=\"{}\"/>\n", escape_xml(&page.title), url, url));
+        xml.push_str(&format!("<updated>{}</updated>\n", date.to_rfc3339()));
+        if let Some(description) = &page.description {
+            xml.push_str(&format!("<summary>{}</summary>\n", escape_xml(description)));
+        }
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+
+    let path = config.out_dir.join("atom.xml");
+    fs::write(&path, xml).with_context(|| format!("Failed to write feed: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_gemtext() {
+        // Arrange
+        let markdown = "## Setup\n\nRead [the guide](guide.html#install) and *then* [Rust](https://rust-lang.org).\n\n\
+                        - one\n- two\n\n> Quoted\n\n```\nfn main() {}\n```\n\n<div>shortcode</div>\n";
+
+        // Act
+        let gemtext = to_gemtext(markdown);
+
+        // Assert
+        assert_eq!(
+            gemtext,
+            "## Setup\n\nRead the guide and then Rust.\n=> guide.gmi#install the guide\n=> https://rust-lang.org Rust\n\n\
+             * one\n* two\n\n> Quoted\n\n```\nfn main() {}\n```\n"
+        );
+    }
+}
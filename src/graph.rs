@@ -0,0 +1,208 @@
+// Rustic - page-to-page link graph of the built site
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::postprocess::{resolve_local_url, PageInfo};
+
+/// Output formats for `rustic graph`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// `{ "nodes": [...], "edges": [...] }`
+    Json,
+}
+
+/// Pages of the built site and the local links between them, keyed by path relative to the output directory.
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    /// Page path to its `<title>`
+    pub pages: BTreeMap<String, String>,
+    /// Page path to the pages it links to
+    pub links: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LinkGraph {
+    /// Scans every HTML file in `out_dir` for links to other pages.
+    pub fn scan(out_dir: &Path) -> Result<Self> {
+        if !out_dir.is_dir() {
+            bail!("{:?} doesn't exist; build the site first", out_dir);
+        }
+
+        let mut graph = Self::default();
+        let mut raw_links = Vec::new();
+        for entry in WalkDir::new(out_dir).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("html") {
+                continue;
+            }
+            let html = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read page: {:?}", path))?;
+            let (title, hrefs) = scan_page(&html)?;
+            let id = page_id(out_dir, path);
+
+            let page = PageInfo { out_dir, output_path: path, head: &[], entry: &[] };
+            let targets: Vec<PathBuf> = hrefs.iter().filter_map(|href| resolve_local_url(href, &page)).collect();
+            raw_links.push((id.clone(), targets));
+            graph.pages.insert(id, title.trim().to_string());
+        }
+
+        // Only links that land on another page of the site become edges
+        for (id, targets) in raw_links {
+            let mut edges = BTreeSet::new();
+            for target in targets {
+                let mut target = normalize(&target);
+                if target.is_dir() {
+                    target.push("index.html");
+                }
+                let target_id = page_id(out_dir, &target);
+                if target_id != id && graph.pages.contains_key(&target_id) {
+                    edges.insert(target_id);
+                }
+            }
+            graph.links.insert(id, edges);
+        }
+
+        Ok(graph)
+    }
+
+    /// Renders the graph as Graphviz DOT.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph site {\n");
+        for (id, title) in &self.pages {
+            dot.push_str(&format!("  {:?} [label={:?}];\n", id, if title.is_empty() { id } else { title }));
+        }
+        for (from, targets) in &self.links {
+            for to in targets {
+                dot.push_str(&format!("  {:?} -> {:?};\n", from, to));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as JSON nodes and edges, the shape most graph libraries accept.
+    pub fn to_json(&self) -> Result<String> {
+        let nodes: Vec<_> = self
+            .pages
+            .iter()
+            .map(|(id, title)| serde_json::json!({ "id": id, "title": title }))
+            .collect();
+        let edges: Vec<_> = self
+            .links
+            .iter()
+            .flat_map(|(from, targets)| targets.iter().map(move |to| serde_json::json!({ "source": from, "target": to })))
+            .collect();
+        Ok(serde_json::to_string_pretty(&serde_json::json!({ "nodes": nodes, "edges": edges }))?)
+    }
+}
+
+/// Prints the link graph of the site in `out_dir` to stdout.
+pub fn print(out_dir: &Path, format: GraphFormat) -> Result<()> {
+    let graph = LinkGraph::scan(out_dir)?;
+    match format {
+        GraphFormat::Dot => print!("{}", graph.to_dot()),
+        GraphFormat::Json => println!("{}", graph.to_json()?),
+    }
+    Ok(())
+}
+
+/// Extracts a page's title and the `href` of every link in it.
+pub fn scan_page(html: &str) -> Result<(String, Vec<String>)> {
+    let title = RefCell::new(String::new());
+    let hrefs = RefCell::new(Vec::new());
+    rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("a[href]", |el| {
+                if let Some(href) = el.get_attribute("href") {
+                    hrefs.borrow_mut().push(percent_decode(&href));
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(text!("head > title", |chunk| {
+                title.borrow_mut().push_str(chunk.as_str());
+                Ok(())
+            })),
+    )?;
+    Ok((unescape_html(&title.into_inner()), hrefs.into_inner()))
+}
+
+/// Identifies a page by its forward-slashed path relative to the output directory.
+fn page_id(out_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(out_dir).unwrap_or(path);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Decodes `%20`-style escapes in a URL path.
+pub fn percent_decode(url: &str) -> String {
+    let bytes = url.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reverses the entity escaping Tera applies to text such as page titles.
+pub fn unescape_html(text: &str) -> String {
+    text.replace("&#x27;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_page_links() {
+        // Act
+        let (title, hrefs) = scan_page(
+            "<html><head><title>Tom &amp; Jerry</title></head><body><a href=\"../Other%20Note.html\">x</a><a>y</a></body></html>",
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(title, "Tom & Jerry");
+        assert_eq!(hrefs, ["../Other Note.html"]);
+        assert_eq!(normalize(Path::new("public/notes/../a.html")), Path::new("public/a.html"));
+    }
+}
@@ -0,0 +1,91 @@
+// Rustic - JSON copies of every page, for apps and other frontends
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::authors::Author;
+use crate::graph::unescape_html;
+use crate::listing::PageListing;
+
+/// Matches a tag, capturing the slash of closing tags and the element name.
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<(/?)([a-zA-Z0-9]*)[^>]*>").unwrap());
+
+/// Elements whose end also ends a line of plain text.
+const BLOCKS: &[&str] = &[
+    "p", "h1", "h2", "h3", "h4", "h5", "h6", "li", "pre", "blockquote", "div", "tr", "figure", "figcaption", "section",
+    "article",
+];
+
+/// A page as written to `<page>.json`.
+#[derive(Serialize)]
+struct PageJson<'a> {
+    #[serde(flatten)]
+    page: &'a PageListing,
+    stinger: &'a str,
+    authors: &'a [Author],
+    /// The rendered markdown body, without the page template
+    html: &'a str,
+    /// The body as plain text, one line per paragraph, heading or list item
+    text: String,
+}
+
+/// An entry of `pages.json`, pointing at the page's full JSON.
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    #[serde(flatten)]
+    page: &'a PageListing,
+    json: String,
+}
+
+/// URL of a page's JSON copy, e.g. `/notes/rust.json` for `/notes/rust.html`.
+fn json_url(url: &str) -> String {
+    format!("{}.json", url.strip_suffix(".html").unwrap_or(url))
+}
+
+/// Writes a page's metadata, HTML body and plain text next to its HTML.
+pub fn write_page(page: &PageListing, stinger: &str, authors: &[Author], html: &str, out_dir: &Path) -> Result<()> {
+    let json = PageJson { page, stinger, authors, html, text: plain_text(html) };
+    let path = out_dir.join(json_url(&page.url).trim_start_matches('/'));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", path))?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&json)?).with_context(|| format!("Failed to write page JSON: {:?}", path))
+}
+
+/// Writes `pages.json`, listing every page's metadata and the URL of its JSON.
+pub fn write_index(pages: &[PageListing], out_dir: &Path) -> Result<()> {
+    let entries: Vec<IndexEntry> = pages.iter().map(|page| IndexEntry { page, json: json_url(&page.url) }).collect();
+    let path = out_dir.join("pages.json");
+    fs::write(&path, serde_json::to_string_pretty(&serde_json::json!({ "pages": entries }))?)
+        .with_context(|| format!("Failed to write page index: {:?}", path))
+}
+
+/// Strips the markup from rendered HTML, keeping block elements on lines of their own.
+pub fn plain_text(html: &str) -> String {
+    let text = TAG.replace_all(html, |caps: &regex::Captures| {
+        let name = caps[2].to_lowercase();
+        let ends_line = (!caps[1].is_empty() && BLOCKS.contains(&name.as_str())) || name == "br";
+        if ends_line { "\n" } else { "" }
+    });
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    unescape_html(&lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text() {
+        // Act
+        let text = plain_text("<h2>Tom &amp; Jerry</h2>\n<p>A <em>cat</em><br />\nand a mouse.</p>\n<ul>\n<li>One</li>\n<li>Two</li>\n</ul>\n");
+
+        // Assert
+        assert_eq!(text, "Tom & Jerry\nA cat\nand a mouse.\nOne\nTwo");
+        assert_eq!(json_url("/notes/rust.html"), "/notes/rust.json");
+    }
+}
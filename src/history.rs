@@ -0,0 +1,127 @@
+// Rustic - page modification dates and contributors from git history
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// When each file under a directory was last committed, and by whom, read with a single `git log`.
+#[derive(Debug, Default)]
+pub struct History {
+    updated: HashMap<PathBuf, DateTime<Utc>>,
+    /// Commit counts per author name, for each file
+    authors: HashMap<PathBuf, HashMap<String, usize>>,
+}
+
+/// Someone who committed changes to a page, as exposed in `page.contributors`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Contributor {
+    pub name: String,
+    pub commits: usize,
+}
+
+impl History {
+    /// Reads the history of `dir`, or returns an empty one with a warning when git can't.
+    pub fn load(dir: &Path) -> Self {
+        match Self::from_git(dir) {
+            Ok(history) => history,
+            Err(e) => {
+                eprintln!("⚠️  Couldn't read git history, using file modification times instead: {:#}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn from_git(dir: &Path) -> Result<Self> {
+        let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+        let log = git(dir, &["log", "--format=%x00%cI%x09%aN", "--name-only", "--no-renames", "--", "."])?;
+        let mut history = Self::default();
+        for (path, date, author) in parse_log(&log) {
+            let path = Path::new(root.trim()).join(path);
+            *history.authors.entry(path.clone()).or_default().entry(author.to_string()).or_default() += 1;
+            // Commits are listed newest first, so the first date seen for a file is its latest
+            history.updated.entry(path).or_insert(date);
+        }
+        Ok(history)
+    }
+
+    /// The file's last commit date, or its modification time if it has none.
+    pub fn updated(&self, path: &Path) -> Option<DateTime<Utc>> {
+        let committed = fs::canonicalize(path).ok().and_then(|path| self.updated.get(&path).copied());
+        committed.or_else(|| Some(fs::metadata(path).ok()?.modified().ok()?.into()))
+    }
+}
+
+impl History {
+    /// Everyone who committed to the file, most commits first.
+    pub fn contributors(&self, path: &Path) -> Vec<Contributor> {
+        let Some(authors) = fs::canonicalize(path).ok().and_then(|path| self.authors.get(&path)) else {
+            return Vec::new();
+        };
+        let mut contributors: Vec<Contributor> = authors
+            .iter()
+            .map(|(name, &commits)| Contributor { name: name.clone(), commits })
+            .collect();
+        contributors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+        contributors
+    }
+}
+
+/// Runs git in `dir`, returning its output.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Splits `git log --format=%x00%cI%x09%aN --name-only` output into (path, commit date, author) triples.
+fn parse_log(log: &str) -> Vec<(&str, DateTime<Utc>, &str)> {
+    let mut files = Vec::new();
+    for commit in log.split('\0').filter(|c| !c.trim().is_empty()) {
+        let mut lines = commit.lines();
+        let Some((date, author)) = lines.next().and_then(|header| header.split_once('\t')) else {
+            continue;
+        };
+        let Ok(date) = DateTime::parse_from_rfc3339(date.trim()) else {
+            continue;
+        };
+        let date = date.with_timezone(&Utc);
+        files.extend(lines.filter(|l| !l.is_empty()).map(|path| (path, date, author.trim())));
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        // Arrange
+        let log = "\x002024-03-02T10:00:00+01:00\tAda Lovelace\n\ncontent/a.md\ncontent/b.md\n\x002024-01-01T00:00:00Z\tAlan\n\ncontent/a.md\n";
+
+        // Act
+        let files = parse_log(log);
+
+        // Assert
+        let dates: Vec<(&str, String, &str)> =
+            files.iter().map(|(path, date, author)| (*path, date.to_rfc3339(), *author)).collect();
+        assert_eq!(
+            dates,
+            [
+                ("content/a.md", "2024-03-02T09:00:00+00:00".to_string(), "Ada Lovelace"),
+                ("content/b.md", "2024-03-02T09:00:00+00:00".to_string(), "Ada Lovelace"),
+                ("content/a.md", "2024-01-01T00:00:00+00:00".to_string(), "Alan"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+// Rustic - the site's languages, their translations of each page, and `trans()` for theme strings
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tera::{Function, Value};
+
+use crate::config::Config;
+use crate::listing::{Listing, PageListing};
+use crate::menus::MenuEntry;
+
+/// Settings for one of the site's languages, declared under `[languages.<code>]` in rustic.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Shown in language switchers, e.g. "Français"; defaults to the code
+    pub name: Option<String>,
+    /// The site's title in this language, instead of the top-level `title`
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Menus for pages in this language, e.g. `[[languages.fr.menu.main]]`, instead of the top-level ones
+    pub menu: BTreeMap<String, Vec<MenuEntry>>,
+    /// Publish untranslated pages in the default language at this language's URLs
+    pub fallback: bool,
+}
+
+/// One of the site's languages, as listed in `site.languages`.
+#[derive(Debug, Serialize)]
+pub struct Language<'a> {
+    pub code: &'a str,
+    pub name: &'a str,
+    /// The language's home page
+    pub url: String,
+    pub default: bool,
+}
+
+/// A version of a page in one language, as listed in `page.translations`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Translation<'a> {
+    pub lang: &'a str,
+    pub name: &'a str,
+    pub url: String,
+    pub title: &'a str,
+    /// Whether this is the page being rendered
+    pub current: bool,
+    /// Whether this is the default-language page standing in for a missing translation
+    pub fallback: bool,
+}
+
+/// The site's languages: the default one, whose pages are at the root of `content/`,
+/// and any others, whose pages are in a directory named after the language code.
+///
+/// A page's translations are the pages at the same path in the other languages'
+/// directories, e.g. `content/about.md` and `content/fr/about.md`.
+pub struct Languages<'a> {
+    pub default: &'a str,
+    config: &'a BTreeMap<String, LanguageConfig>,
+}
+
+impl<'a> Languages<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            default: config.language.as_deref().unwrap_or("en"),
+            config: &config.languages,
+        }
+    }
+
+    /// Every language code, the default first.
+    pub fn codes(&self) -> impl Iterator<Item = &'a str> {
+        let default = self.default;
+        std::iter::once(default).chain(self.config.keys().map(String::as_str).filter(move |&code| code != default))
+    }
+
+    pub fn name(&self, code: &'a str) -> &'a str {
+        self.config.get(code).and_then(|language| language.name.as_deref()).unwrap_or(code)
+    }
+
+    pub fn config(&self, code: &str) -> Option<&'a LanguageConfig> {
+        self.config.get(code)
+    }
+
+    fn falls_back(&self, code: &str) -> bool {
+        code != self.default && self.config(code).is_some_and(|language| language.fallback)
+    }
+
+    /// The language of the page at the site-relative `url`, from its first directory.
+    pub fn of(&self, url: &str) -> &'a str {
+        let first = url.trim_start_matches('/').split_once('/').map(|(dir, _)| dir);
+        self.codes().skip(1).find(|&code| Some(code) == first).unwrap_or(self.default)
+    }
+
+    /// The URL of the page at `url` in the language `code`, whether or not it exists.
+    pub fn url_in(&self, url: &str, code: &str) -> String {
+        let lang = self.of(url);
+        let path = if lang == self.default { url } else { &url[lang.len() + 1..] };
+        if code == self.default { path.to_string() } else { format!("/{}{}", code, path) }
+    }
+
+    /// Every language, for `site.languages`.
+    pub fn list(&self) -> Vec<Language<'a>> {
+        self.codes()
+            .map(|code| Language {
+                code,
+                name: self.name(code),
+                url: self.url_in("/index.html", code),
+                default: code == self.default,
+            })
+            .collect()
+    }
+
+    /// The page at `url` in every language it is published in, for `page.translations`.
+    pub fn translations(&self, url: &str, listing: &'a Listing) -> Vec<Translation<'a>> {
+        self.codes()
+            .filter_map(|code| {
+                let translated = self.url_in(url, code);
+                let (page, fallback) = match listing.get(&translated) {
+                    Some(page) => (page, false),
+                    None if self.falls_back(code) => (listing.get(&self.url_in(url, self.default))?, true),
+                    None => return None,
+                };
+                Some(Translation {
+                    lang: code,
+                    name: self.name(code),
+                    title: &page.title,
+                    current: translated == url,
+                    url: translated,
+                    fallback,
+                })
+            })
+            .collect()
+    }
+
+    /// Default-language pages with no translation in a language that falls back to the
+    /// default one, each with that language's code.
+    pub fn fallbacks(&self, listing: &'a Listing) -> Vec<(&'a str, &'a PageListing)> {
+        let mut fallbacks = Vec::new();
+        for code in self.codes().filter(|&code| self.falls_back(code)) {
+            let untranslated = listing
+                .pages
+                .iter()
+                .filter(|page| page.lang == self.default && listing.get(&self.url_in(&page.url, code)).is_none());
+            fallbacks.extend(untranslated.map(|page| (code, page)));
+        }
+        fallbacks
+    }
+}
+
+/// Reads every `<lang>.toml` in `dir`, keyed by language code.
+///
+/// A missing directory yields no translations, so sites that don't use `trans()` work as before.
+pub fn load(dir: &Path) -> Result<BTreeMap<String, toml::Value>> {
+    let mut strings = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(strings);
+    }
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read translations directory: {:?}", dir))? {
+        let path = entry?.path();
+        let Some(lang) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+            continue;
+        }
+        let raw = fs::read_to_string(&path).with_context(|| format!("Failed to read translations: {:?}", path))?;
+        let table = toml::from_str(&raw).with_context(|| format!("Failed to parse translations: {:?}", path))?;
+        strings.insert(lang.to_string(), table);
+    }
+    Ok(strings)
+}
+
+/// Tera function looking up theme strings in `i18n/<lang>.toml`.
+///
+/// `{{ trans(key="read_more", lang=page.lang) }}` finds `read_more` in `i18n/fr.toml` for a
+/// French page; without `lang`, the site's language is used. Dotted keys reach into
+/// tables, other arguments fill `{name}` placeholders, and strings a language lacks
+/// fall back to the site's language.
+pub struct Trans {
+    strings: BTreeMap<String, toml::Value>,
+    default_lang: String,
+}
+
+impl Trans {
+    pub fn new(strings: BTreeMap<String, toml::Value>, default_lang: &str) -> Self {
+        Self { strings, default_lang: default_lang.to_string() }
+    }
+
+    fn lookup(&self, lang: &str, key: &str) -> Option<&str> {
+        key.split('.').try_fold(self.strings.get(lang)?, |value, part| value.get(part))?.as_str()
+    }
+}
+
+impl Function for Trans {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let key = args
+            .get("key")
+            .and_then(Value::as_str)
+            .ok_or("trans needs a `key` argument")?;
+        let lang = args.get("lang").and_then(Value::as_str).unwrap_or(&self.default_lang);
+        let text = self.lookup(lang, key).or_else(|| self.lookup(&self.default_lang, key)).ok_or_else(|| {
+            format!("trans: {:?} is in neither i18n/{}.toml nor i18n/{}.toml", key, lang, self.default_lang)
+        })?;
+
+        let mut text = text.to_string();
+        for (name, value) in args.iter().filter(|(name, _)| !matches!(name.as_str(), "key" | "lang")) {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            text = text.replace(&format!("{{{}}}", name), &value);
+        }
+        Ok(Value::String(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_languages_find_translations() {
+        // Arrange
+        let config = Config {
+            languages: BTreeMap::from([
+                ("de".to_string(), LanguageConfig { fallback: true, ..Default::default() }),
+                ("fr".to_string(), LanguageConfig { name: Some("Français".to_string()), ..Default::default() }),
+            ]),
+            ..Default::default()
+        };
+        let languages = Languages::new(&config);
+        let page = |title: &str, url: &str| PageListing {
+            title: title.to_string(),
+            url: url.to_string(),
+            date: None,
+            description: None,
+            lang: languages.of(url).to_string(),
+            section: None,
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: BTreeMap::new(),
+            menu: None,
+            sitemap: None,
+        };
+        let listing = Listing::new(
+            vec![page("About", "/about.html"), page("À propos", "/fr/about.html"), page("Français", "/fr/index.html")],
+            &BTreeMap::new(),
+        );
+
+        // Act
+        let translations = languages.translations("/fr/about.html", &listing);
+        let fallbacks: Vec<(&str, &str)> = languages.fallbacks(&listing).iter().map(|(code, page)| (*code, page.url.as_str())).collect();
+
+        // Assert
+        assert_eq!(languages.codes().collect::<Vec<_>>(), ["en", "de", "fr"]);
+        assert_eq!(languages.of("/fr/about.html"), "fr");
+        assert_eq!(languages.of("/fresh/about.html"), "en");
+        assert_eq!(languages.url_in("/fr/about.html", "de"), "/de/about.html");
+        assert_eq!(
+            translations,
+            [
+                Translation { lang: "en", name: "en", url: "/about.html".to_string(), title: "About", current: false, fallback: false },
+                Translation { lang: "de", name: "de", url: "/de/about.html".to_string(), title: "About", current: false, fallback: true },
+                Translation { lang: "fr", name: "Français", url: "/fr/about.html".to_string(), title: "À propos", current: true, fallback: false },
+            ]
+        );
+        assert_eq!(fallbacks, [("de", "/about.html")]);
+    }
+
+    #[test]
+    fn test_trans_falls_back_to_the_default_language() {
+        // Arrange
+        let strings = BTreeMap::from([
+            ("en".to_string(), toml::from_str("read_more = \"Read more\"\n[dates]\nupdated = \"Updated {date}\"\n").unwrap()),
+            ("fr".to_string(), toml::from_str("read_more = \"Lire la suite\"\n").unwrap()),
+        ]);
+        let trans = Trans::new(strings, "en");
+        let call = |args: &[(&str, &str)]| {
+            let args = args.iter().map(|(name, value)| (name.to_string(), Value::from(*value))).collect();
+            trans.call(&args)
+        };
+
+        // Act
+        let french = call(&[("key", "read_more"), ("lang", "fr")]);
+        let fallback = call(&[("key", "dates.updated"), ("lang", "fr"), ("date", "2024-05-01")]);
+        let missing = call(&[("key", "share"), ("lang", "fr")]);
+
+        // Assert
+        assert_eq!(french.unwrap(), "Lire la suite");
+        assert_eq!(fallback.unwrap(), "Updated 2024-05-01");
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,206 @@
+// Rustic - `{{ include(...) }}` and `{{ code(...) }}` directives that splice files into markdown
+
+use anyhow::{bail, Context, Result};
+use gray_matter::{engine::YAML, Matter};
+use regex::Regex;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::shortcodes::{parse_args, string_arg, Args};
+use crate::wikilinks;
+
+/// Matches `{{ include(path="snippets/disclaimer.md") }}` or `{{ code(path="examples/main.rs") }}`.
+static INCLUDE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{\{\s*(include|code)\(((?:[^()"]|"(?:[^"\\]|\\.)*")*)\)\s*\}\}"#).unwrap()
+});
+
+/// Replaces include directives in `markdown` with the contents of the named files.
+///
+/// `include` splices in markdown, while `code` splices in a fenced code block
+/// built from a source file, optionally cut down to `lines="10-25"`.
+///
+/// Paths are relative to the project root, like `templates/` and `static/`, so
+/// snippets kept outside the content directory aren't published as pages of
+/// their own. Included files may include others; cycles are reported as errors.
+pub fn expand(markdown: &str, page: &Path) -> Result<String> {
+    let mut stack = vec![page.to_path_buf()];
+    splice(markdown, &mut stack)
+        .with_context(|| format!("Failed to expand includes in {:?}", page))
+}
+
+/// Files the include and code directives in `markdown` read, directly or through the
+/// files they include, for `rustic serve` to watch. Directives that can't be read are
+/// skipped; the build reports them.
+pub fn files(markdown: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    collect_files(markdown, &mut found);
+    found
+}
+
+fn collect_files(markdown: &str, found: &mut Vec<PathBuf>) {
+    for (_, run) in wikilinks::prose_runs(markdown).into_iter().filter(|(prose, _)| *prose) {
+        for caps in INCLUDE.captures_iter(run) {
+            let Some(path) = parse_args(&caps[2]).ok().and_then(|args| string_arg(&args, "path").ok().map(PathBuf::from)) else {
+                continue;
+            };
+            if found.contains(&path) {
+                continue;
+            }
+            found.push(path.clone());
+            if &caps[1] == "include"
+                && let Ok(content) = fs::read_to_string(&path)
+            {
+                collect_files(&Matter::<YAML>::new().parse(&content).content, found);
+            }
+        }
+    }
+}
+
+/// Expands includes outside fenced code recursively, with `stack` holding the chain of
+/// files being included.
+fn splice(markdown: &str, stack: &mut Vec<PathBuf>) -> Result<String> {
+    let mut out = String::with_capacity(markdown.len());
+    for (prose, run) in wikilinks::prose_runs(markdown) {
+        if prose {
+            splice_run(run, stack, &mut out)?;
+        } else {
+            out.push_str(run);
+        }
+    }
+    Ok(out)
+}
+
+/// Expands the includes in a run of prose onto `out`.
+fn splice_run(markdown: &str, stack: &mut Vec<PathBuf>, out: &mut String) -> Result<()> {
+    let mut last = 0;
+
+    for caps in INCLUDE.captures_iter(markdown) {
+        let call = caps.get(0).unwrap();
+        out.push_str(&markdown[last..call.start()]);
+        last = call.end();
+
+        let args = parse_args(&caps[2])
+            .with_context(|| format!("Invalid arguments to `{}`", &caps[1]))?;
+        let path = PathBuf::from(string_arg(&args, "path")?);
+        if &caps[1] == "code" {
+            out.push_str(&code_block(&path, &args)?);
+            continue;
+        }
+
+        let canonical = fs::canonicalize(&path)
+            .with_context(|| format!("Failed to find included file: {:?}", path))?;
+        if stack.iter().any(|p| fs::canonicalize(p).is_ok_and(|p| p == canonical)) {
+            let chain: Vec<String> = stack.iter().map(|p| p.display().to_string()).collect();
+            bail!("Include cycle: {} -> {}", chain.join(" -> "), path.display());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read included file: {:?}", path))?;
+        // Snippets may carry front matter of their own; only the body is spliced in
+        let body = Matter::<YAML>::new().parse(&content).content;
+
+        stack.push(path);
+        let expanded = splice(&body, stack)?;
+        stack.pop();
+        out.push_str(expanded.trim_end_matches('\n'));
+    }
+
+    out.push_str(&markdown[last..]);
+    Ok(())
+}
+
+/// Reads a source file (or a range of its lines) into a fenced code block.
+fn code_block(path: &Path, args: &Args) -> Result<String> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read code file: {:?}", path))?;
+    let lines: Vec<&str> = source.lines().collect();
+
+    let (start, end) = match args.get("lines").and_then(Value::as_str) {
+        Some(range) => parse_range(range, lines.len())
+            .with_context(|| format!("Invalid line range `{}` for {:?}", range, path))?,
+        None => (1, lines.len()),
+    };
+    let snippet = dedent(&lines[start - 1..end]);
+
+    // Default the language to the file extension, which most highlighters understand
+    let lang = match args.get("lang").and_then(Value::as_str) {
+        Some(lang) => lang.to_string(),
+        None => path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_string(),
+    };
+
+    // The fence must be longer than any backtick run inside the snippet
+    let longest = snippet.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    Ok(format!("{fence}{lang}\n{snippet}\n{fence}"))
+}
+
+/// Parses a 1-based inclusive range such as `10-25`, `10-` or `7`.
+fn parse_range(range: &str, line_count: usize) -> Result<(usize, usize)> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let start: usize = start.trim().parse()?;
+    let end: usize = match end.trim() {
+        "" => line_count,
+        end => end.parse()?,
+    };
+    if start == 0 || start > end || end > line_count {
+        bail!("the file has {} lines", line_count);
+    }
+    Ok((start, end))
+}
+
+/// Removes the indentation shared by every non-blank line.
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_line_range() {
+        // Act & Assert
+        assert_eq!(parse_range("10-25", 40).unwrap(), (10, 25));
+        assert_eq!(parse_range("10-", 40).unwrap(), (10, 40));
+        assert_eq!(parse_range("7", 40).unwrap(), (7, 7));
+        assert!(parse_range("30-50", 40).is_err());
+        assert_eq!(dedent(&["    fn main() {", "", "        run();", "    }"]), "fn main() {\n\n    run();\n}");
+    }
+
+    #[test]
+    fn test_include_cycle_is_an_error() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.md");
+        let b = dir.join("b.md");
+        fs::write(&a, format!("A {{{{ include(path={:?}) }}}}", b)).unwrap();
+        fs::write(&b, format!("B {{{{ include(path={:?}) }}}}", a)).unwrap();
+
+        // Act
+        let result = expand(&fs::read_to_string(&a).unwrap(), &a);
+        let missing = expand(&format!("x {{{{ include(path={:?}) }}}} y", dir.join("missing.md")), &a);
+        let fenced = format!("```\n{{{{ include(path={:?}) }}}}\n```\n", dir.join("missing.md"));
+        let watched = files(&fs::read_to_string(&a).unwrap());
+
+        // Assert
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(message.contains("Include cycle"), "{}", message);
+        assert!(missing.is_err());
+        assert_eq!(expand(&fenced, &a).unwrap(), fenced);
+        assert_eq!(watched, [b, a]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+// Rustic - the published pages behind `site.pages`, breadcrumbs and pager links
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::dates::parse_date;
+use crate::history::Contributor;
+use crate::menus::PageMenus;
+use crate::sitemap::PageSitemap;
+
+/// A page as listed in `site.pages` and `site.sections`.
+#[derive(Debug, Serialize)]
+pub struct PageListing {
+    pub title: String,
+    /// Path from the site root, e.g. `/notes/rust.html`
+    pub url: String,
+    pub date: Option<String>,
+    pub description: Option<String>,
+    /// Code of the page's language, from the language directory it is in
+    pub lang: String,
+    /// Top-level content directory the page is in, if any; within a language's directory,
+    /// the directory below it, prefixed with the language code, e.g. `fr/blog`
+    pub section: Option<String>,
+    /// Position in sections sorted by weight, lightest first
+    pub weight: Option<i64>,
+    /// Name of the multi-part series the page belongs to
+    pub series: Option<String>,
+    /// Set for pages marked `draft: true`, which are only listed when drafts are published
+    pub draft: bool,
+    /// When the source was last committed (or modified), with `git_dates` on
+    pub updated: Option<String>,
+    /// Commit authors of the source, with `git_contributors` on
+    #[serde(skip)]
+    pub contributors: Vec<Contributor>,
+    /// Terms by taxonomy name, e.g. `tags: ["rust"]`
+    pub taxonomies: BTreeMap<String, Vec<String>>,
+    #[serde(skip)]
+    pub menu: Option<PageMenus>,
+    #[serde(skip)]
+    pub sitemap: Option<PageSitemap>,
+}
+
+/// Settings for one section, declared under `[sections.<name>]` in rustic.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SectionConfig {
+    pub sort_by: SortBy,
+    /// Write an RSS feed of the section's pages to `<name>/rss.xml`
+    pub feed: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Oldest first; undated pages come first
+    #[default]
+    Date,
+    /// Lightest first; pages without a weight come last
+    Weight,
+    /// Alphabetically, ignoring case
+    Title,
+}
+
+/// The settings of `section`; a translated section such as `fr/blog` shares those of `blog`
+/// unless it has its own.
+pub fn section_config<'a>(config: &'a BTreeMap<String, SectionConfig>, section: &str) -> Option<&'a SectionConfig> {
+    config.get(section).or_else(|| config.get(section.split_once('/')?.1))
+}
+
+/// One step of a breadcrumb trail; directories without an index page have no URL.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Crumb {
+    pub title: String,
+    pub url: Option<String>,
+}
+
+/// Every published page, in path order, looked up by URL.
+#[derive(Debug, Default)]
+pub struct Listing {
+    pub pages: Vec<PageListing>,
+    by_url: HashMap<String, usize>,
+    /// Each section's pages, in its configured order
+    sections: BTreeMap<String, Vec<usize>>,
+    /// Each series' pages, oldest first
+    series: BTreeMap<String, Vec<usize>>,
+    /// The pages before and after each page in its section
+    neighbours: HashMap<usize, (Option<usize>, Option<usize>)>,
+}
+
+impl Listing {
+    /// Orders each section as configured, by date unless `config` says otherwise.
+    pub fn new(pages: Vec<PageListing>, config: &BTreeMap<String, SectionConfig>) -> Self {
+        let by_url = pages.iter().enumerate().map(|(i, page)| (page.url.clone(), i)).collect();
+
+        // A section's index page isn't part of its sequence
+        let mut sections: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, page) in pages.iter().enumerate() {
+            if let Some(section) = page.section.clone().filter(|_| !page.url.ends_with("/index.html")) {
+                sections.entry(section).or_default().push(i);
+            }
+        }
+        let mut neighbours = HashMap::new();
+        for (section, order) in &mut sections {
+            // Ties keep path order, as the sorts are stable
+            match section_config(config, section).map(|c| c.sort_by).unwrap_or_default() {
+                SortBy::Date => order.sort_by_key(|&i| pages[i].date.as_deref().and_then(parse_date)),
+                SortBy::Weight => order.sort_by_key(|&i| (pages[i].weight.is_none(), pages[i].weight)),
+                SortBy::Title => order.sort_by_key(|&i| pages[i].title.to_lowercase()),
+            }
+            for (position, &i) in order.iter().enumerate() {
+                let previous = position.checked_sub(1).map(|p| order[p]);
+                neighbours.insert(i, (previous, order.get(position + 1).copied()));
+            }
+        }
+
+        let mut series: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, page) in pages.iter().enumerate() {
+            if let Some(name) = &page.series {
+                series.entry(name.clone()).or_default().push(i);
+            }
+        }
+        for order in series.values_mut() {
+            order.sort_by_key(|&i| pages[i].date.as_deref().and_then(parse_date));
+        }
+
+        Self { pages, by_url, sections, series, neighbours }
+    }
+
+    /// Every series by name, with its pages oldest first.
+    pub fn series(&self) -> impl Iterator<Item = (&str, Vec<&PageListing>)> {
+        self.series
+            .iter()
+            .map(|(name, order)| (name.as_str(), order.iter().map(|&i| &self.pages[i]).collect()))
+    }
+
+    /// The pages of the series `name`, oldest first.
+    pub fn series_pages(&self, name: &str) -> Vec<&PageListing> {
+        self.series.get(name).into_iter().flatten().map(|&i| &self.pages[i]).collect()
+    }
+
+    /// Each section's pages, in order, for `site.sections`.
+    pub fn sections(&self) -> BTreeMap<&str, Vec<&PageListing>> {
+        self.sections
+            .iter()
+            .map(|(name, order)| (name.as_str(), order.iter().map(|&i| &self.pages[i]).collect()))
+            .collect()
+    }
+
+    /// The pages before and after `url` in its section, for pager links.
+    pub fn neighbours(&self, url: &str) -> (Option<&PageListing>, Option<&PageListing>) {
+        let Some((previous, next)) = self.by_url.get(url).and_then(|i| self.neighbours.get(i)) else {
+            return (None, None);
+        };
+        (previous.map(|i| &self.pages[i]), next.map(|i| &self.pages[i]))
+    }
+
+    pub fn get(&self, url: &str) -> Option<&PageListing> {
+        self.by_url.get(url).map(|&i| &self.pages[i])
+    }
+
+    /// The trail from the home page through each enclosing directory to the page at `url`.
+    ///
+    /// Directories are titled by their `index.md` when they have one, and otherwise by name.
+    pub fn breadcrumbs(&self, url: &str, home_title: &str) -> Vec<Crumb> {
+        let crumb = |index: String, fallback: &str| match self.get(&index) {
+            Some(page) => Crumb { title: page.title.clone(), url: Some(index) },
+            None => Crumb { title: fallback.to_string(), url: None },
+        };
+
+        let mut crumbs = vec![crumb("/index.html".to_string(), home_title)];
+        let segments: Vec<&str> = url.trim_start_matches('/').split('/').collect();
+        let (file, dirs) = segments.split_last().unwrap_or((&"", &[]));
+        for depth in 1..=dirs.len() {
+            crumbs.push(crumb(format!("/{}/index.html", dirs[..depth].join("/")), dirs[depth - 1]));
+        }
+        // An index page is already the last crumb
+        if *file != "index.html" {
+            let title = self.get(url).map_or(*file, |page| page.title.as_str());
+            crumbs.push(Crumb { title: title.to_string(), url: Some(url.to_string()) });
+        }
+        crumbs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(title: &str, url: &str) -> PageListing {
+        PageListing {
+            title: title.to_string(),
+            url: url.to_string(),
+            date: None,
+            description: None,
+            lang: "en".to_string(),
+            section: url[1..].split_once('/').map(|(dir, _)| dir.to_string()),
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: BTreeMap::new(),
+            menu: None,
+            sitemap: None,
+        }
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        // Arrange
+        let listing = Listing::new(vec![
+            page("Docs", "/docs/index.html"),
+            page("Installing", "/docs/guide/install.html"),
+            page("Welcome", "/index.html"),
+        ], &BTreeMap::new());
+
+        // Act
+        let crumbs = listing.breadcrumbs("/docs/guide/install.html", "Home");
+
+        // Assert
+        let trail: Vec<(&str, Option<&str>)> =
+            crumbs.iter().map(|crumb| (crumb.title.as_str(), crumb.url.as_deref())).collect();
+        assert_eq!(
+            trail,
+            [
+                ("Welcome", Some("/index.html")),
+                ("Docs", Some("/docs/index.html")),
+                ("guide", None),
+                ("Installing", Some("/docs/guide/install.html")),
+            ]
+        );
+        assert_eq!(listing.breadcrumbs("/docs/index.html", "Home").len(), 2);
+    }
+
+    #[test]
+    fn test_neighbours_follow_dates_within_a_section() {
+        // Arrange
+        let dated = |title: &str, url: &str, date: &str| PageListing { date: Some(date.to_string()), ..page(title, url) };
+        let listing = Listing::new(vec![
+            dated("Second", "/blog/a.html", "2024-03-01"),
+            dated("First", "/blog/b.html", "2024-01-15"),
+            page("Blog", "/blog/index.html"),
+            dated("Guide", "/docs/guide.html", "2024-02-01"),
+        ], &BTreeMap::new());
+
+        // Act
+        let (previous, next) = listing.neighbours("/blog/b.html");
+        let (last_previous, last_next) = listing.neighbours("/blog/a.html");
+
+        // Assert
+        assert!(previous.is_none());
+        assert_eq!(next.map(|p| p.title.as_str()), Some("Second"));
+        assert_eq!(last_previous.map(|p| p.title.as_str()), Some("First"));
+        assert!(last_next.is_none());
+        assert!(matches!(listing.neighbours("/blog/index.html"), (None, None)));
+    }
+
+    #[test]
+    fn test_sections_sort_by_weight() {
+        // Arrange
+        let weighted = |title: &str, weight: Option<i64>| PageListing { weight, ..page(title, &format!("/docs/{}.html", title)) };
+        let config = BTreeMap::from([("docs".to_string(), SectionConfig { sort_by: SortBy::Weight, ..Default::default() })]);
+
+        // Act
+        let listing = Listing::new(
+            vec![weighted("appendix", None), weighted("install", Some(1)), weighted("usage", Some(2)), weighted("faq", Some(-1))],
+            &config,
+        );
+
+        // Assert
+        let titles: Vec<&str> = listing.sections()["docs"].iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, ["faq", "install", "usage", "appendix"]);
+    }
+}
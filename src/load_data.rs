@@ -0,0 +1,167 @@
+// Rustic - `load_data()` for JSON, TOML and CSV fetched over HTTP(S) at build time
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tera::{Function, Value};
+use url::Url;
+
+use crate::data;
+
+/// Settings for `load_data()`, declared under `[load_data]` in rustic.toml.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoadDataConfig {
+    /// Hosts templates may fetch from, e.g. `api.github.com`; nothing else is fetched
+    pub allowed_hosts: Vec<String>,
+    /// Seconds to wait for a response before failing the build
+    pub timeout: u64,
+    /// Seconds a response saved under `cache_dir` is reused before fetching it again
+    pub cache_for: u64,
+}
+
+impl Default for LoadDataConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: Vec::new(),
+            timeout: 10,
+            cache_for: 3600,
+        }
+    }
+}
+
+/// Tera function fetching data files from hosts allow-listed in the config.
+///
+/// `{% set release = load_data(url="https://api.github.com/repos/o/r/releases/latest") %}`
+/// parses the response as JSON, or as `format="csv"` or `"toml"`; URLs ending in `.csv` or
+/// `.toml` are detected. Each URL is fetched once per build, and with `cache_dir` set the
+/// response is reused across builds for `cache_for` seconds, or for as long as the host
+/// can't be reached.
+pub struct LoadData {
+    agent: ureq::Agent,
+    config: LoadDataConfig,
+    cache_dir: Option<PathBuf>,
+    loaded: Mutex<HashMap<String, Value>>,
+}
+
+impl LoadData {
+    pub fn new(config: &LoadDataConfig, cache_dir: Option<PathBuf>) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout)))
+            .build()
+            .new_agent();
+        Self {
+            agent,
+            config: config.clone(),
+            cache_dir: cache_dir.map(|dir| dir.join("load_data")),
+            loaded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The response body for `url`, from the cache when it is fresh enough.
+    fn body(&self, url: &str) -> Result<String> {
+        let cached = self.cache_dir.as_ref().map(|dir| {
+            let key: String = Sha256::digest(url).iter().map(|b| format!("{:02x}", b)).collect();
+            dir.join(key)
+        });
+        let age = cached
+            .as_ref()
+            .and_then(|path| fs::metadata(path).ok())
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if let (Some(path), Some(age)) = (&cached, age)
+            && age < Duration::from_secs(self.config.cache_for)
+        {
+            return fs::read_to_string(path).with_context(|| format!("Failed to read cached response: {:?}", path));
+        }
+
+        let fetched = self
+            .agent
+            .get(url)
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string());
+        match (fetched, &cached) {
+            (Ok(body), Some(path)) => {
+                let parent = path.parent().unwrap_or(path);
+                fs::create_dir_all(parent).with_context(|| format!("Failed to create cache directory: {:?}", parent))?;
+                fs::write(path, &body).with_context(|| format!("Failed to write cached response: {:?}", path))?;
+                Ok(body)
+            }
+            (Ok(body), None) => Ok(body),
+            // A stale copy beats failing the build while offline
+            (Err(e), Some(path)) if age.is_some() => {
+                eprintln!("⚠️  Failed to fetch {}, using the copy from an earlier build: {}", url, e);
+                fs::read_to_string(path).with_context(|| format!("Failed to read cached response: {:?}", path))
+            }
+            (Err(e), _) => Err(e).with_context(|| format!("Failed to fetch {}", url)),
+        }
+    }
+
+    fn load(&self, url: &str, format: Option<&str>) -> Result<Value> {
+        let parsed = Url::parse(url).with_context(|| format!("Invalid URL {:?}", url))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!("Only http and https URLs can be loaded, not {:?}", url);
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        if !self.config.allowed_hosts.iter().any(|allowed| allowed == host) {
+            bail!("{:?} is not listed in `load_data.allowed_hosts` in rustic.toml", host);
+        }
+        let format = format.unwrap_or_else(|| match parsed.path().rsplit_once('.') {
+            Some((_, extension @ ("csv" | "toml"))) => extension,
+            _ => "json",
+        });
+
+        let key = format!("{} {}", format, url);
+        if let Some(value) = self.loaded.lock().unwrap().get(&key) {
+            return Ok(value.clone());
+        }
+        let value = data::parse(&self.body(url)?, format).with_context(|| format!("Failed to parse {}", url))?;
+        self.loaded.lock().unwrap().insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+impl Function for LoadData {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let url = args
+            .get("url")
+            .and_then(Value::as_str)
+            .ok_or("load_data needs a `url` argument")?;
+        let format = args.get("format").and_then(Value::as_str);
+        self.load(url, format).map_err(|e| format!("load_data: {:#}", e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_data_reads_fresh_cache_and_checks_hosts() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-load-data-{}", std::process::id()));
+        let config = LoadDataConfig { allowed_hosts: vec!["example.com".to_string()], ..Default::default() };
+        let load_data = LoadData::new(&config, Some(dir.clone()));
+        let url = "https://example.com/results.csv";
+        let key: String = Sha256::digest(url).iter().map(|b| format!("{:02x}", b)).collect();
+        fs::create_dir_all(dir.join("load_data")).unwrap();
+        fs::write(dir.join("load_data").join(key), "name,time\nAda,12\n").unwrap();
+        let args = |url: &str| HashMap::from([("url".to_string(), Value::from(url))]);
+
+        // Act
+        let results = load_data.call(&args(url));
+        let elsewhere = load_data.call(&args("https://example.org/results.csv"));
+        let local = load_data.call(&args("file:///etc/passwd"));
+
+        // Assert
+        assert_eq!(results.unwrap(), serde_json::json!([{ "name": "Ada", "time": "12" }]));
+        assert!(elsewhere.unwrap_err().to_string().contains("not listed in `load_data.allowed_hosts`"));
+        assert!(local.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,116 +2,1178 @@
 // Rustic - A simple static site generator in Rust
 // December 2025
 
+mod a11y;
+mod activitypub;
+mod api;
+mod archive;
+mod assets;
+mod authors;
+mod bench;
+mod blogroll;
+mod bundle;
+mod cache;
+mod chart;
+mod check;
+mod config;
+mod containers;
+mod data;
+mod dates;
+mod debug;
+mod diff;
+mod doctor;
+mod dry_run;
+mod embeds;
+mod env;
+mod epub;
+mod exif;
+mod export;
+mod favicon;
+mod feed;
+mod fragments;
+mod gallery;
+mod gemini;
+mod graph;
+mod headless;
+mod history;
+mod i18n;
+mod listing;
+mod menus;
+mod microformats;
+mod include;
+mod load_data;
+mod newsletter;
+mod obsidian;
+mod pipeline;
+mod plugins;
+mod postprocess;
+mod print;
+mod protect;
+mod redirects;
+mod render;
+mod seo;
+mod serve;
+mod shortcodes;
+mod sitemap;
+mod slug;
+mod snapshot;
+mod spelling;
+mod standalone;
+mod stats;
+mod table;
+mod taxonomies;
+mod template_error;
+mod template_tests;
+mod template_usage;
+mod timings;
+mod unused_assets;
+mod webmention;
+mod workspace;
+mod social;
+mod wikilinks;
+mod zip;
+
 use anyhow::{Context, Result};
-use clap::Parser;
-use fs_extra::dir::{copy, CopyOptions};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Parser, Subcommand};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use gray_matter::{engine::YAML, Matter};
 use pulldown_cmark::{html, Parser as MarkdownParser};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use tera::{Context as TeraContext, Tera};
 use walkdir::WalkDir;
 
+use cache::Cache;
+use config::Config;
+use listing::{Listing, PageListing};
+use plugins::Stage;
+use postprocess::{PageInfo, Rewriter};
+use shortcodes::escape_attr;
+use social::SocialCards;
+use timings::Timings;
+
 /// A simple static site generator that builds HTML from Markdown files.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The path to the directory containing Markdown files
-    #[arg(short, long, default_value = "content")]
+    #[arg(short, long, global = true, default_value = "content")]
     input_dir: PathBuf,
 
     /// The output directory where HTML and CSS files will be generated
-    #[arg(short, long, default_value = "public")]
+    #[arg(short, long, global = true, default_value = "public")]
     out_dir: PathBuf,
+
+    /// The site configuration file
+    #[arg(short, long, global = true, default_value = "rustic.toml")]
+    config: PathBuf,
+
+    /// The `[profile.<name>]` section of the config to apply, e.g. `production`
+    #[arg(long, global = true, env = "RUSTIC_PROFILE")]
+    profile: Option<String>,
+
+    /// Override a config value, e.g. `--set extra.banner=true`; may be repeated
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<config::Override>,
+
+    /// Print how long each build stage took and the slowest pages
+    #[arg(long)]
+    timings: bool,
+
+    /// Also pack the built site into this `.tar.gz` or `.zip` file, e.g. for a release
+    #[arg(long, value_name = "FILE")]
+    archive: Option<PathBuf>,
+
+    /// After the build, list templates, partials and macros that no rendered page reached
+    #[arg(long)]
+    unused_templates: bool,
+
+    /// Write the template context each page receives to this directory, one JSON file per page
+    #[arg(long, value_name = "DIR")]
+    dump_context: Option<PathBuf>,
+
+    /// Render the site, but only report what would be written, copied or deleted in the output directory
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Tasks other than building the site, which is the default.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build the site; the default when no command is given
+    Build {
+        /// Build every site listed in the workspace file instead
+        #[arg(long)]
+        all: bool,
+        /// The workspace file listing the sites, used with `--all`
+        #[arg(long, default_value = "rustic-workspace.toml")]
+        workspace: PathBuf,
+        /// Render only the pages matching this glob under the content directory, e.g.
+        /// `blog/2024/**`; may be repeated. Feeds and other site-wide files are left as they are
+        #[arg(long, value_name = "GLOB")]
+        only: Vec<String>,
+    },
+    /// Build the site, serve it locally and rebuild and reload open pages when sources change
+    Serve {
+        /// The address to listen on; `0.0.0.0` makes the site reachable from other devices
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+        /// The port to listen on; the next free one is used when it is busy
+        #[arg(long, default_value_t = 1111)]
+        port: u16,
+        /// Open the site in the default browser once it is served
+        #[arg(long)]
+        open: bool,
+        /// Leave out drafts and future-dated pages, as a production build does
+        #[arg(long)]
+        no_drafts: bool,
+        /// A PEM certificate chain to serve the site over HTTPS with; needs `--tls-key`
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// The PEM private key for `--tls-cert`
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+    },
+    /// Compare two builds of the site, listing added, removed and changed pages with their changes
+    Diff {
+        /// The earlier output directory
+        #[arg(required_unless_present = "against")]
+        old: Option<PathBuf>,
+        /// The later output directory
+        #[arg(required_unless_present = "against")]
+        new: Option<PathBuf>,
+        /// Compare the output directory with this earlier build instead, e.g. `previous-build/`
+        #[arg(long, conflicts_with_all = ["old", "new"])]
+        against: Option<PathBuf>,
+        /// Unchanged tags and lines of text shown around each change
+        #[arg(long, default_value_t = 3)]
+        context: usize,
+    },
+    /// Print the page-to-page link graph of the built site
+    Graph {
+        #[arg(long, value_enum, default_value = "dot")]
+        format: graph::GraphFormat,
+    },
+    /// Build the site repeatedly and report how long it takes; hooks and pipeline commands are skipped
+    Bench {
+        /// Number of timed builds
+        #[arg(long, default_value_t = 10)]
+        runs: usize,
+        /// Benchmark a generated corpus of this many pages instead of the content directory
+        #[arg(long)]
+        pages: Option<usize>,
+    },
+    /// Convert one markdown document to HTML on stdout, e.g. for shell pipelines and editor previews
+    Render {
+        /// The markdown file, or `-` to read from stdin
+        input: PathBuf,
+        /// Render a whole page with this template, e.g. `template.html`, instead of only the body
+        #[arg(long)]
+        template: Option<String>,
+    },
+    /// Run the site's tests; runs every kind that is set up unless some are selected
+    Test {
+        /// Render the template fixtures in `tests/templates/`, as `rustic test-templates` does
+        #[arg(long)]
+        templates: bool,
+        /// Build into a temporary directory and compare the output with the golden copy
+        #[arg(long)]
+        snapshot: bool,
+        /// Replace the golden copy with this build's output instead of comparing
+        #[arg(long)]
+        update: bool,
+        /// The committed golden output
+        #[arg(long, default_value = "tests/snapshot")]
+        golden: PathBuf,
+    },
+    /// Render templates against the fixture contexts in `tests/templates/` and check their output
+    TestTemplates {
+        /// The directory of `.toml` fixtures
+        #[arg(long, default_value = "tests/templates")]
+        dir: PathBuf,
+    },
+    /// Print word counts, pages per section, reading times and the largest files
+    Stats,
+    /// Check the project layout: config, theme, templates, content, shortcodes and data
+    Doctor,
+    /// Report problems in the built site; runs every check unless some are selected
+    Check {
+        /// Pages that no other page links to
+        #[arg(long)]
+        orphans: bool,
+        /// Missing alt text and lang attributes, skipped headings and empty links
+        #[arg(long)]
+        a11y: bool,
+        /// Unknown words in markdown prose, checked against a dictionary and `words.txt`
+        #[arg(long)]
+        spelling: bool,
+        /// Missing or duplicate titles and descriptions, canonical URLs, sitemap gaps and unsized images
+        #[arg(long)]
+        seo: bool,
+        /// Files under `static/` that no page, stylesheet or script refers to
+        #[arg(long)]
+        assets: bool,
+        /// Fail when anything is reported instead of only warning
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Export pages in formats other than a website
+    Export {
+        #[command(subcommand)]
+        format: ExportCommand,
+    },
+    /// Inspect what the templates are given
+    Debug {
+        #[command(subcommand)]
+        action: DebugCommand,
+    },
+    /// Work with webmentions of other sites
+    Webmention {
+        #[command(subcommand)]
+        action: WebmentionCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportCommand {
+    /// An EPUB book of a section's pages, ordered by weight, with a cover and table of contents
+    Epub {
+        /// Directory under the content directory to export; the whole site if omitted
+        #[arg(long)]
+        section: Option<String>,
+        /// Book title; defaults to the site title
+        #[arg(long)]
+        title: Option<String>,
+        /// Image for the cover; a title page is generated without one
+        #[arg(long)]
+        cover: Option<PathBuf>,
+        /// The EPUB file to write; defaults to `<section>.epub`, or `site.epub`
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// One HTML document of a section's pages, with page breaks and a table of contents, for printing
+    Print {
+        /// Directory under the content directory to export; the whole site if omitted
+        #[arg(long)]
+        section: Option<String>,
+        /// Document title; defaults to the site title
+        #[arg(long)]
+        title: Option<String>,
+        /// Stylesheet to use instead of the built-in print styles
+        #[arg(long)]
+        stylesheet: Option<PathBuf>,
+        /// The HTML file to write; defaults to `<section>-print.html`, or `site-print.html`
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// A page as email-safe HTML, with inlined styles, a table layout and absolute URLs
+    Newsletter {
+        /// The page's markdown source, e.g. `content/posts/hello.md`
+        page: PathBuf,
+        /// The HTML file to write; defaults to `<page>-newsletter.html`
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// A built page as one portable HTML file, with its stylesheets, images and fonts inlined
+    Standalone {
+        /// The page's markdown source, e.g. `content/posts/hello.md`, or its path in the output
+        page: PathBuf,
+        /// The HTML file to write; defaults to `<page>-standalone.html`
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DebugCommand {
+    /// Print the template context a page receives as JSON, e.g. `rustic debug context content/notes/rust.md`
+    Context {
+        /// The markdown page, under the content directory
+        page: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum WebmentionCommand {
+    /// Notify the sites the built pages link to, sending only what changed since the last run
+    Send {
+        /// Discover endpoints, but don't send or record anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Shared state needed to render every page of the site.
+struct Site<'a> {
+    config: &'a Config,
+    input_dir: &'a Path,
+    out_dir: &'a Path,
+    tera: Tera,
+    rewriter: Rewriter,
+    bundles: BTreeMap<String, String>,
+    social_cards: Option<SocialCards>,
+    /// Tags added to every page's `<head>`
+    head: Vec<String>,
+    /// Contents of the `data/` directory, keyed by file stem
+    data: serde_json::Value,
+    /// The `site` object every template sees
+    globals: serde_json::Value,
+    /// `site` as pages in each of the other languages see it, by language code
+    localized: BTreeMap<String, serde_json::Value>,
+    /// Pages are published if this falls between their publish and expiry dates
+    build_time: DateTime<Utc>,
+    /// Every published page, for breadcrumbs and pager links
+    listing: Listing,
+    /// Every page's title, file name and incoming wiki links
+    wiki_index: wikilinks::Index,
+    timings: &'a Timings,
+    cache: Cache,
+    /// Where `--dump-context` writes each page's template context
+    dump_context: Option<&'a Path>,
+    /// Templates the build rendered directly, the roots `--unused-templates` starts from
+    rendered: RefCell<BTreeSet<String>>,
+    /// A copy of `tera` that templated page bodies are rendered from, made once per build
+    body_tera: RefCell<Option<Tera>>,
+}
+
+impl<'a> Site<'a> {
+    /// The site as commands that render one page outside a build see it, such as
+    /// `rustic export newsletter`. No other pages are read, so `site.pages`, the menus
+    /// and the bundles are empty.
+    fn outside_build(config: &'a Config, input_dir: &'a Path, out_dir: &'a Path, timings: &'a Timings) -> Result<Self> {
+        let (tera, shared) = render::templates_outside_build(config)?;
+        let cache = Cache::new(config.cache_dir.clone());
+        let build_time = dates::source_date_epoch()?.unwrap_or_else(Utc::now);
+        let wiki_index = published_wiki_index(input_dir, config, &cache, build_time)?;
+        Ok(Site {
+            config,
+            input_dir,
+            out_dir,
+            tera,
+            rewriter: Rewriter::default(),
+            bundles: BTreeMap::new(),
+            social_cards: None,
+            head: Vec::new(),
+            data: shared.get("data").cloned().unwrap_or_default(),
+            globals: shared.get("site").cloned().unwrap_or_default(),
+            localized: BTreeMap::new(),
+            listing: Listing::new(Vec::new(), &config.sections),
+            build_time,
+            wiki_index,
+            timings,
+            cache,
+            dump_context: None,
+            rendered: RefCell::default(),
+            body_tera: RefCell::default(),
+        })
+    }
+
+    /// Renders one of the site's templates, explaining any error in the template's terms.
+    fn render(&self, template: &str, context: &TeraContext) -> Result<String> {
+        self.rendered.borrow_mut().insert(template.to_string());
+        self.tera.render(template, context).map_err(|e| template_error::explain(e, &self.tera, template, context))
+    }
+
+    /// The `site` object for pages in the language `lang`.
+    fn globals_for(&self, lang: &str) -> &serde_json::Value {
+        self.localized.get(lang).unwrap_or(&self.globals)
+    }
 }
 
 /// Represents the front matter of a markdown file.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct FrontMatter {
     title: String,
     stinger: String,
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    /// Podcast episode audio file, site-relative or absolute
+    #[serde(default)]
+    audio: Option<String>,
+    /// Podcast episode running time, e.g. "42:10"
+    #[serde(default)]
+    duration: Option<String>,
+    /// Run the markdown body through Tera before it is converted
+    #[serde(default)]
+    render_templates: bool,
+    /// Only published when the config sets `drafts = true`
+    #[serde(default)]
+    draft: bool,
+    /// The page is left out of builds before this date
+    #[serde(default)]
+    publish_date: Option<String>,
+    /// The page is left out of builds from this date on
+    #[serde(default)]
+    expiry_date: Option<String>,
+    /// Position in sections sorted by weight
+    #[serde(default)]
+    weight: Option<i64>,
+    /// Ids of the page's authors in `data/authors.toml`
+    #[serde(default)]
+    authors: Vec<String>,
+    /// Name of a multi-part series, e.g. "Building a Compiler"
+    #[serde(default)]
+    series: Option<String>,
+    /// Any other fields, such as taxonomy terms, passed through to templates as `page.*`
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+    /// Menus that link to this page, e.g. `menu: main`
+    #[serde(default)]
+    menu: Option<menus::PageMenus>,
+    /// `false` to leave the page out of the sitemap, or its `priority` and `changefreq`
+    #[serde(default)]
+    sitemap: Option<sitemap::PageSitemap>,
+    /// Directives for crawlers, e.g. "noindex, nofollow"; `noindex` also keeps the page out of the sitemap
+    #[serde(default)]
+    robots: Option<String>,
+    /// URL the page sends visitors to; only a redirect stub is published
+    #[serde(default)]
+    redirect_to: Option<String>,
+    /// Encrypt the body so only readers with the password can read it
+    #[serde(default)]
+    protected: bool,
+    /// Password for a protected page, instead of the config's `protected_password`
+    #[serde(default, skip_serializing)]
+    password: Option<String>,
+    /// Publish the page only at a hard-to-guess URL, left out of listings, feeds and the sitemap
+    #[serde(default)]
+    unlisted: bool,
+}
+
+impl FrontMatter {
+    /// Whether the page belongs in a build made at `now`: not a draft (unless `drafts`),
+    /// past its publish date (unless `future`) and not yet expired.
+    fn is_published(&self, drafts: bool, future: bool, now: DateTime<Utc>) -> Result<bool> {
+        let date = |field: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| dates::parse_date(v).ok_or_else(|| anyhow::anyhow!("Invalid {} {:?}", field, v)))
+                .transpose()
+        };
+        let publish = date("publish_date", &self.publish_date)?;
+        let expiry = date("expiry_date", &self.expiry_date)?;
+        Ok((drafts || !self.draft) && publish.is_none_or(|d| future || d <= now) && expiry.is_none_or(|d| now < d))
+    }
+}
+
+/// What site-wide outputs such as feeds need to know about a rendered page.
+#[derive(Debug)]
+struct PageSummary {
+    url: String,
+    front_matter: FrontMatter,
+    authors: Vec<authors::Author>,
+    /// The rendered markdown body, without the page template
+    content: String,
+}
+
+/// A source file split into front matter and markdown body, as stored in the cache.
+#[derive(Debug, Deserialize, Serialize)]
+struct SourceParts {
+    front_matter: Option<serde_json::Value>,
+    content: String,
+}
+
+/// Page data handed to plugins at the `page_parsed` stage.
+#[derive(Debug, Deserialize, Serialize)]
+struct ParsedPage {
+    path: PathBuf,
+    front_matter: FrontMatter,
+    content: String,
+}
+
+/// Page data handed to plugins at the `html_rendered` stage.
+#[derive(Debug, Deserialize, Serialize)]
+struct RenderedPage {
+    path: PathBuf,
+    html: String,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Diff { old, new, against, context }) => match (against, old, new) {
+            (Some(previous), _, _) => diff::run(previous, &cli.out_dir, *context),
+            (None, Some(old), Some(new)) => diff::run(old, new, *context),
+            _ => unreachable!("clap requires both directories without --against"),
+        },
+        Some(Command::Graph { format }) => graph::print(&cli.out_dir, *format),
+        Some(Command::Bench { runs, pages }) => bench::run(&cli.input_dir, &cli.config, &config_args(&cli), *runs, *pages),
+        Some(Command::Serve { host, port, open, no_drafts, tls_cert, tls_key }) => {
+            let tls = tls_cert.as_deref().zip(tls_key.as_deref());
+            serve::run(&cli, *host, *port, *open, !*no_drafts, tls)
+        }
+        Some(Command::Stats) => stats::print(&cli.input_dir, &cli.out_dir),
+        Some(Command::Doctor) => doctor::run(&cli),
+        Some(Command::Render { input, template }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            render::run(input, template.as_deref(), &config)
+        }
+        Some(Command::Test { templates, snapshot: selected, update, golden }) => {
+            let all = !(*templates || *selected || *update);
+            let fixtures = Path::new("tests/templates");
+            let run_templates = *templates || (all && fixtures.is_dir());
+            let run_snapshot = *selected || *update || (all && golden.is_dir());
+            if !run_templates && !run_snapshot {
+                anyhow::bail!("Nothing to test; add fixtures to {:?} or create a snapshot with `rustic test --snapshot --update`", fixtures);
+            }
+            if run_templates {
+                let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+                template_tests::run(fixtures, &config)?;
+            }
+            if run_snapshot {
+                snapshot::run(&cli, golden, *update)?;
+            }
+            Ok(())
+        }
+        Some(Command::TestTemplates { dir }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            template_tests::run(dir, &config)
+        }
+        Some(Command::Check { orphans, a11y, spelling, seo, assets, strict }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            let checks = check::Checks {
+                orphans: *orphans,
+                a11y: *a11y,
+                spelling: *spelling,
+                seo: *seo,
+                assets: *assets,
+                strict: *strict,
+            };
+            check::run(&cli.input_dir, &cli.out_dir, &config.check, checks)
+        }
+        Some(Command::Export { format: ExportCommand::Epub { section, title, cover, output } }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            let book = epub::Book {
+                title: title.clone().or_else(|| config.title.clone()).unwrap_or_else(|| "Untitled".to_string()),
+                author: config.author.clone(),
+                language: config.language.clone().unwrap_or_else(|| "en".to_string()),
+                cover: cover.clone(),
+            };
+            let output = output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("{}.epub", section.as_deref().unwrap_or("site"))));
+            epub::export(&cli.input_dir, &config, section.as_deref(), &book, &output)
+        }
+        Some(Command::Export { format: ExportCommand::Print { section, title, stylesheet, output } }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            let title = title.clone().or_else(|| config.title.clone()).unwrap_or_else(|| "Untitled".to_string());
+            let output = output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("{}-print.html", section.as_deref().unwrap_or("site"))));
+            print::export(&cli.input_dir, &config, section.as_deref(), &title, stylesheet.as_deref(), &output)
+        }
+        Some(Command::Export { format: ExportCommand::Newsletter { page, output } }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            let output = output.clone().unwrap_or_else(|| {
+                let stem = page.file_stem().unwrap_or_default().to_string_lossy();
+                PathBuf::from(format!("{}-newsletter.html", stem))
+            });
+            newsletter::export(page, &cli.input_dir, &cli.out_dir, &config, &output)
+        }
+        Some(Command::Export { format: ExportCommand::Standalone { page, output } }) => {
+            let output = output.clone().unwrap_or_else(|| {
+                let stem = page.file_stem().unwrap_or_default().to_string_lossy();
+                PathBuf::from(format!("{}-standalone.html", stem))
+            });
+            standalone::export(page, &cli.input_dir, &cli.out_dir, &output)
+        }
+        Some(Command::Debug { action: DebugCommand::Context { page } }) => {
+            debug::context(&cli.input_dir, &cli.out_dir, &cli.config, &config_args(&cli), page)
+        }
+        Some(Command::Webmention { action: WebmentionCommand::Send { dry_run } }) => {
+            let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+            webmention::send(&config.webmention, &config.base_url, &cli.out_dir, *dry_run)
+        }
+        Some(Command::Build { all: true, workspace, only }) => workspace::build_all(&cli, workspace, only),
+        None | Some(Command::Build { .. }) => build(&cli),
+    }
+}
+
+/// Re-creates the `--profile` and `--set` options, for builds run in a child process.
+fn config_args(cli: &Cli) -> Vec<String> {
+    let profile = cli.profile.iter().flat_map(|name| ["--profile".to_string(), name.clone()]);
+    let overrides = cli.overrides.iter().flat_map(|o| ["--set".to_string(), o.to_string()]);
+    profile.chain(overrides).collect()
+}
+
+/// Builds the whole site from `cli.input_dir` into `cli.out_dir`.
+fn build(cli: &Cli) -> Result<()> {
+    if cli.dry_run {
+        return dry_run::run(cli);
+    }
     let input_dir = &cli.input_dir;
     let out_dir = &cli.out_dir;
+    let config = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides)?;
+    let only = match &cli.command {
+        Some(Command::Build { only, .. }) if !only.is_empty() => Some(only_matcher(only)?),
+        _ => None,
+    };
+    let timings = Timings::default();
+    let mut lap = timings.lap();
 
-    // Clean and recreate the output directory
-    if out_dir.exists() {
-        fs::remove_dir_all(out_dir)
-            .with_context(|| format!("Failed to remove existing output directory: {:?}", out_dir))?;
+    // Build metadata handed to hook commands
+    let mut hook_env = vec![
+        ("RUSTIC_VERSION", env!("CARGO_PKG_VERSION").to_string()),
+        ("RUSTIC_INPUT_DIR", input_dir.display().to_string()),
+        ("RUSTIC_OUTPUT_DIR", out_dir.display().to_string()),
+        ("RUSTIC_CONFIG", cli.config.display().to_string()),
+    ];
+    pipeline::run_hooks("pre_build", &config.hooks.pre_build, &hook_env)?;
+    lap.mark("hooks");
+
+    // Clean the output directory, keeping copied assets so unchanged ones aren't copied again.
+    // Partial builds keep the rest of the previous build too.
+    if only.is_none() {
+        clean_output(out_dir)?;
+        if let Some(gemini) = &config.gemini {
+            gemini::clean(gemini)?;
+        }
     }
-    fs::create_dir_all(out_dir)
-        .with_context(|| format!("Failed to create output directory: {:?}", out_dir))?;
+
+    // Run asset pipeline commands first so their outputs land in static/
+    pipeline::run_pipeline(&config.pipeline)?;
+    lap.mark("pipeline");
 
     // Copy static assets
     let static_dir = PathBuf::from("static");
     if static_dir.exists() {
-        let mut options = CopyOptions::new();
-        options.overwrite = true;
-        copy(&static_dir, out_dir, &options)
-            .with_context(|| format!("Failed to copy static assets from {:?}", static_dir))?;
+        // Camera and GPS metadata is stripped from the published copies
+        let synced = assets::sync(
+            &static_dir,
+            &out_dir.join("static"),
+            !config.keep_image_metadata,
+            config.link_assets,
+        )?;
+        println!(
+            "Copied {} asset(s) ({} linked), {} unchanged, {} removed",
+            synced.copied, synced.linked, synced.skipped, synced.removed
+        );
+        if synced.stripped > 0 {
+            println!("Stripped metadata from {} image(s)", synced.stripped);
+        }
+    } else if out_dir.join("static").exists() {
+        fs::remove_dir_all(out_dir.join("static"))
+            .with_context(|| format!("Failed to remove stale assets: {:?}", out_dir.join("static")))?;
+    }
+
+    // Publish files stored alongside the notes of an Obsidian vault
+    if config.obsidian {
+        let copied = obsidian::copy_attachments(input_dir, out_dir, !config.keep_image_metadata)?;
+        println!("Copied {} attachment(s)", copied);
     }
+    lap.mark("asset copy");
+
+    // Bundle JavaScript entry points into content-hashed files
+    let bundles = bundle::build_bundles(&config.bundle, out_dir)?;
+    lap.mark("bundles");
+
+    // Find all markdown files, refusing to let one page overwrite another
+    let sources: Vec<PathBuf> = WalkDir::new(input_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|path| path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect();
+    check_output_collisions(&sources, input_dir, out_dir, only.is_none())?;
+    lap.mark("walk");
+
+    // Read every page's front matter up front so templates can list the whole site
+    let cache = Cache::new(config.cache_dir.clone());
+    let build_time = dates::source_date_epoch()?.unwrap_or_else(Utc::now);
+    let listing = Listing::new(list_pages(&sources, input_dir, &config, &cache, build_time)?, &config.sections);
+    taxonomies::check_spellings(&config.taxonomies, &listing)?;
+    let globals = site_globals(&config, &listing, build_time)?;
+    let localized = i18n::Languages::new(&config)
+        .codes()
+        .skip(1)
+        .map(|code| Ok((code.to_string(), language_globals(&config, &listing, build_time, code)?)))
+        .collect::<Result<_>>()?;
+    lap.mark("listing");
 
     // Initialize Tera templating engine
-    let tera = Tera::new("templates/**/*.html")
-        .with_context(|| "Failed to initialize Tera templating engine")?;
+    let mut tera = load_templates(config.theme_dir.as_deref())?;
+    let data = data::load_with_shared(Path::new("data"), config.shared_data_dir.as_deref())?;
 
-    // Process all markdown files
-    for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
-            process_markdown_file(path, input_dir, out_dir, &tera)?;
+    // Partials included with cached_include() see only site-wide values, so they render once
+    let mut shared = TeraContext::new();
+    shared.insert("bundles", &bundles);
+    shared.insert("data", &data);
+    shared.insert("site", &globals);
+    register_template_functions(&mut tera, &config, shared)?;
+    lap.mark("template load");
+
+    // Load the critical stylesheet once so every page can inline it
+    let critical_css = match &config.critical_css {
+        Some(css_path) => Some(
+            fs::read_to_string(css_path)
+                .with_context(|| format!("Failed to read critical stylesheet: {:?}", css_path))?,
+        ),
+        None => None,
+    };
+    let rewriter = Rewriter::from_config(&config.rewrite, critical_css)?;
+    let social_cards = config.social_cards.as_ref().map(SocialCards::load).transpose()?;
+
+    // Advertise webmention endpoints, and generate the favicon set, on every page
+    let mut head = webmention::head_links(&config.webmention);
+    if let Some(source) = &config.favicon {
+        head.extend(favicon::generate(source, out_dir)?);
+    }
+
+    let wiki_index = published_wiki_index(input_dir, &config, &cache, build_time)?;
+    lap.mark("setup");
+
+    let site = Site {
+        config: &config,
+        input_dir,
+        out_dir,
+        tera,
+        rewriter,
+        bundles,
+        social_cards,
+        head,
+        data,
+        globals,
+        localized,
+        listing,
+        build_time,
+        wiki_index,
+        timings: &timings,
+        cache,
+        dump_context: cli.dump_context.as_deref(),
+        rendered: RefCell::default(),
+        body_tera: RefCell::default(),
+    };
+
+    // Pages are streamed through parse, render and write one at a time; only the
+    // metadata that site-wide listings need is kept once a page is written
+    let keep_summaries = config.podcast.is_some();
+    let keep_bodies = config.feeds.content == feed::FeedContent::Full;
+    let mut pages = Vec::new();
+    let mut bodies = HashMap::new();
+    let mut redirects = Vec::new();
+    // Listed pages by URL, for the JSON copies that carry their metadata
+    let json_pages: HashMap<&str, &PageListing> = if config.json_pages {
+        site.listing.pages.iter().map(|page| (page.url.as_str(), page)).collect()
+    } else {
+        HashMap::new()
+    };
+    let mut page_count = 0;
+    for path in &sources {
+        if let Some(only) = &only {
+            let relative = path.strip_prefix(input_dir)?.to_string_lossy().replace('\\', "/");
+            if !only.is_match(relative) {
+                continue;
+            }
+        }
+        let started = std::time::Instant::now();
+        let Some(summary) = process_markdown_file(path, &site, None)? else {
+            continue;
+        };
+        timings.record_page(path.clone(), started.elapsed());
+        page_count += 1;
+        if let Some(target) = &summary.front_matter.redirect_to {
+            redirects.push(redirects::Redirect { from: summary.url.clone(), to: target.clone() });
+            continue;
+        }
+        if let Some(page) = json_pages.get(summary.url.as_str()) {
+            headless::write_page(page, &summary.front_matter.stinger, &summary.authors, &summary.content, out_dir)?;
+        }
+        if keep_bodies {
+            bodies.insert(summary.url.clone(), summary.content.clone());
+        }
+        if keep_summaries {
+            pages.push(summary);
+        }
+    }
+
+    // Pages not yet translated into languages that fall back to the default one
+    let mut fallbacks = 0;
+    for (lang, page) in i18n::Languages::new(&config).fallbacks(&site.listing) {
+        let path = input_dir.join(page.url.trim_start_matches('/')).with_extension("md");
+        if let Some(only) = &only {
+            let relative = path.strip_prefix(input_dir)?.to_string_lossy().replace('\\', "/");
+            if !only.is_match(relative) {
+                continue;
+            }
+        }
+        process_markdown_file(&path, &site, Some(lang))?;
+        fallbacks += 1;
+    }
+    if fallbacks > 0 {
+        println!("Generated {} page(s) standing in for missing translations", fallbacks);
+    }
+    // Page stages were recorded by process_markdown_file
+    lap = timings.lap();
+
+    // Listings, feeds and other site-wide files need every page rendered, so they wait
+    // for a full build
+    if only.is_some() {
+        println!("✅ Rendered {} of {} page(s)", page_count, sources.len());
+        if cli.timings {
+            timings.print();
         }
+        return Ok(());
+    }
+
+    // Give each series an index page when there is a template for them
+    if has_series_index(&site) {
+        let written = write_series_pages(&site)?;
+        println!("Generated {} series page(s)", written);
+    }
+
+    // Pages and feeds listing the pages filed under each taxonomy term
+    write_taxonomies(&site, &bodies)?;
+
+    // Feeds scoped to a single section, for sections that ask for one
+    write_section_feeds(&site, &bodies)?;
+
+    // A feed of each language's dated pages, at the language's root
+    if config.feeds.languages {
+        write_language_feeds(&site, &bodies)?;
+    }
+
+    // Let the host redirect before the stubs are even served
+    if config.redirects_file {
+        redirects::write_rules(&redirects, out_dir)?;
+    }
+
+    // Index the JSON copies so other frontends can find every page
+    if config.json_pages {
+        headless::write_index(&site.listing.pages, out_dir)?;
+    }
+
+    // Let client-side widgets page through listings without a backend
+    if let Some(api) = &config.api {
+        api::write(api, &config.taxonomies, &site.listing, out_dir)?;
+    }
+
+    // List every page for search engines, which needs absolute URLs
+    if !config.base_url.is_empty() {
+        sitemap::write(&site.listing, &i18n::Languages::new(&config), &config.base_url, out_dir)?;
+    }
+
+    // Generate the podcast feed from pages with audio attached
+    if let Some(podcast) = &config.podcast {
+        feed::write_podcast(podcast, &config.base_url, &pages, out_dir)?;
+    }
+
+    // Let the fediverse follow the site's posts
+    if let Some(activitypub) = &config.activitypub {
+        activitypub::write(activitypub, config.title.as_deref(), &config.base_url, &site.listing, out_dir)?;
+    }
+
+    // Finish the Gemini copy with an index of every page
+    if let Some(gemini) = &config.gemini {
+        let title = config.title.as_deref().unwrap_or("Home");
+        gemini::write_index(gemini, title, &site.listing)?;
+    }
+
+    // Share the recommended feeds as one importable file
+    if let Some(blogs) = blogroll::load(&site.data)? {
+        let title = match &config.title {
+            Some(title) => format!("{} blogroll", title),
+            None => "Blogroll".to_string(),
+        };
+        blogroll::write(&blogs, &title, out_dir)?;
+        println!("Generated blogroll.opml with {} feed(s)", blogs.len());
+    }
+    lap.mark("feeds");
+
+    plugins::notify(
+        &config.plugins,
+        Stage::BuildFinished,
+        &serde_json::json!({ "out_dir": out_dir, "page_count": page_count }),
+    )?;
+
+    hook_env.push(("RUSTIC_PAGE_COUNT", page_count.to_string()));
+    pipeline::run_hooks("post_build", &config.hooks.post_build, &hook_env)?;
+    lap.mark("hooks");
+
+    // Package the finished output, including anything post-build hooks added
+    if let Some(archive) = &cli.archive {
+        archive::write(out_dir, archive)?;
+        lap.mark("archive");
     }
 
     println!("✅ Site generated successfully!");
+    if cli.timings {
+        timings.print();
+    }
+    if cli.unused_templates {
+        template_usage::print_unused(&site.tera, &site.rendered.borrow());
+    }
     Ok(())
 }
 
 /// Processes a single markdown file: parses, converts to HTML, and renders in a template.
-fn process_markdown_file(path: &Path, input_dir: &Path, out_dir: &Path, tera: &Tera) -> Result<()> {
+///
+/// With `fallback`, the page is rendered again under that language's directory, standing in
+/// for a translation that doesn't exist yet.
+fn process_markdown_file(path: &Path, site: &Site, fallback: Option<&str>) -> Result<Option<PageSummary>> {
     println!("Processing: {:?}", path);
+    let mut lap = site.timings.lap();
 
-    // Read file and parse front matter
-    let file_content = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read markdown file: {:?}", path))?;
-    
-    let matter = Matter::<YAML>::new();
-    let parsed_entity = matter.parse(&file_content);
-    
-    let front_matter: FrontMatter = parsed_entity.data
-        .ok_or_else(|| anyhow::anyhow!("Missing front matter in {:?}", path))?
-        .deserialize()
-        .context("Failed to deserialize front matter")?;
-    
-    let markdown_content = parsed_entity.content;
-
-    // Convert markdown body to an HTML string
-    let parser = MarkdownParser::new(&markdown_content);
-    let mut html_body = String::new();
-    html::push_html(&mut html_body, parser);
+    let (front_matter, content) = read_page(path, site.config, &site.cache)?;
+    let published = front_matter.is_published(site.config.drafts, site.config.future, site.build_time)
+        .with_context(|| format!("Failed to read front matter of {:?}", path))?;
+    if !published {
+        println!("Skipping unpublished page: {:?}", path);
+        return Ok(None);
+    }
+
+    // The password isn't serialized, so plugins never see it; resolve it before they run
+    let password = front_matter.password.clone().or_else(|| site.config.protected_password.clone());
+
+    // Give plugins a chance to adjust the page before it is converted
+    let ParsedPage { front_matter, content: markdown_content, .. } = plugins::run_stage(
+        &site.config.plugins,
+        Stage::PageParsed,
+        ParsedPage {
+            path: path.to_path_buf(),
+            front_matter,
+            content,
+        },
+    )?;
+    lap.mark("parse");
 
     // Calculate the relative path prefix for assets
-    let relative_path = path.strip_prefix(input_dir)?;
+    let source_relative = path.strip_prefix(site.input_dir)?;
+    let relative_path = &match fallback {
+        Some(lang) => Path::new(lang).join(source_relative),
+        None => source_relative.to_path_buf(),
+    };
     let depth = relative_path.ancestors().count() - 2; // -1 for self, -1 for root component
     let path_prefix = "..".repeat(depth);
-    
+    let output_relative = if front_matter.unlisted {
+        unlisted_path(relative_path, &site.config.unlisted_key)
+    } else {
+        relative_path.with_extension("html").to_string_lossy().replace('\\', "/")
+    };
+    let url = format!("/{}", output_relative);
+    if front_matter.unlisted {
+        println!("Unlisted page {:?} is at {}", path, site_url(&site.config.base_url, Path::new(&output_relative)));
+        if site.config.unlisted_key.is_empty() {
+            eprintln!("⚠️  Set unlisted_key in the config, or the URL of {:?} can be worked out from its path", path);
+        }
+    }
+
+    // Pages that only point elsewhere get a stub instead of the template
+    if let Some(target) = &front_matter.redirect_to {
+        let output_path = site.out_dir.join(&output_relative);
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create parent directory for {:?}", output_path))?;
+        }
+        fs::write(&output_path, redirects::stub(target, &site.config.base_url))
+            .with_context(|| format!("Failed to write redirect: {:?}", output_path))?;
+        return Ok(Some(PageSummary { url, front_matter, authors: Vec::new(), content: String::new() }));
+    }
+
+    // The front matter plus what the build knows about the page, exposed to templates as `page`
+    let mut page = serde_json::to_value(&front_matter)?;
+    let home_title = site.config.title.as_deref().unwrap_or("Home");
+    let languages = i18n::Languages::new(site.config);
+    let lang = languages.of(&url);
+    page["url"] = serde_json::json!(url);
+    page["lang"] = serde_json::json!(lang);
+    // Drafts and future posts published only because `drafts` or `future` is on, as in `rustic serve`
+    page["preview"] = serde_json::json!(!front_matter.is_published(false, false, site.build_time)?);
+    page["translations"] = serde_json::to_value(languages.translations(&url, &site.listing))?;
+    let mut breadcrumbs = site.listing.breadcrumbs(&url, home_title);
+    // A translated page's trail starts at its language's home page
+    if lang != languages.default {
+        breadcrumbs.remove(0);
+    }
+    // A stand-in isn't in the listing, so its own crumb is titled here
+    if let Some(last) = breadcrumbs.last_mut().filter(|_| fallback.is_some()) {
+        *last = listing::Crumb { title: front_matter.title.clone(), url: Some(url.clone()) };
+    }
+    page["breadcrumbs"] = serde_json::to_value(breadcrumbs)?;
+    if let Some(listed) = site.listing.get(&url) {
+        page["updated"] = serde_json::json!(listed.updated);
+        page["contributors"] = serde_json::to_value(&listed.contributors)?;
+    }
+    let authors = authors::resolve(&front_matter.authors, &site.data, path);
+    page["authors"] = serde_json::to_value(&authors)?;
+    let (previous, next) = site.listing.neighbours(&url);
+    page["previous"] = serde_json::to_value(previous)?;
+    page["next"] = serde_json::to_value(next)?;
+    if let Some(name) = &front_matter.series {
+        let pages = site.listing.series_pages(name);
+        let position = pages.iter().position(|p| p.url == url).map_or(0, |i| i + 1);
+        page["series"] = serde_json::json!({
+            "name": name,
+            "url": has_series_index(site).then(|| series_url(name)),
+            "pages": pages,
+            "position": position,
+            "total": pages.len(),
+        });
+    }
+
+    let markdown_content = expand_content(&markdown_content, path, depth, &page, front_matter.render_templates, site)?;
+    lap.mark("shortcodes");
+
+    // Convert markdown body to an HTML string, unless this exact markdown was converted before
+    let html_body = site.cache.get_or_insert("html", &markdown_content, || {
+        let parser = MarkdownParser::new(&markdown_content);
+        let mut html_body = String::new();
+        html::push_html(&mut html_body, parser);
+        Ok(html_body)
+    })?;
+    // Protected pages publish only the encrypted body, and unlisted ones only their token
+    // URL, so neither gets a gemtext copy; nor do stand-ins for missing translations
+    let html_body = if front_matter.protected {
+        let password = password.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("{:?} is protected but has no password; set `password` or `protected_password`", path)
+        })?;
+        protect::encrypt(&html_body, password, &url)?
+    } else {
+        if let Some(gemini) = site.config.gemini.as_ref().filter(|_| !front_matter.unlisted && fallback.is_none()) {
+            gemini::write_page(gemini, relative_path, &front_matter.title, &markdown_content)?;
+        }
+        html_body
+    };
+    lap.mark("markdown");
+
     // Render the full HTML page using the template
     let mut context = TeraContext::new();
     context.insert("title", &front_matter.title);
     context.insert("stinger", &front_matter.stinger);
+    context.insert("date", &front_matter.date);
+    context.insert("description", &front_matter.description);
+    context.insert("audio", &front_matter.audio);
+    context.insert("duration", &front_matter.duration);
     context.insert("content", &html_body);
     context.insert("path_prefix", &path_prefix); // Pass the new prefix to Tera
+    context.insert("bundles", &site.bundles);
+    context.insert("data", &site.data);
+    context.insert("site", site.globals_for(lang));
+    context.insert("page", &page);
+    context.insert("url", &url);
+    context.insert("is_fallback", &fallback.is_some());
+    context.insert("backlinks", &site.wiki_index.backlinks(&output_relative, depth));
+    if !site.config.base_url.is_empty() {
+        // Stand-ins point search engines at the page they copy
+        let canonical = if fallback.is_some() { source_relative.with_extension("html") } else { PathBuf::from(&output_relative) };
+        let canonical_url = site_url(&site.config.base_url, &canonical);
+        context.insert("canonical_url", &canonical_url);
+    }
+
+    let mut head = site.head.clone();
+    // Unlisted pages also ask search engines to stay away, should their URL get out
+    let robots = front_matter.robots.as_deref().or(front_matter.unlisted.then_some("noindex"));
+    if let Some(robots) = robots {
+        head.push(format!("<meta name=\"robots\" content=\"{}\" />", escape_attr(robots)));
+    }
 
-    let full_html = tera.render("template.html", &context)
-        .with_context(|| "Failed to render template")?;
+    // Generate the Open Graph card and advertise it in the page head
+    if let Some(cards) = &site.social_cards {
+        let card_path = Path::new("cards").join(relative_path).with_extension("png");
+        cards.render(&front_matter.title, &site.out_dir.join(&card_path))?;
+
+        let card_url = site_url(&site.config.base_url, &card_path);
+        let page_url = site_url(&site.config.base_url, Path::new(&output_relative));
+        head.push(format!("<meta property=\"og:title\" content=\"{}\" />", escape_attr(&front_matter.title)));
+        head.push("<meta property=\"og:type\" content=\"article\" />".to_string());
+        head.push(format!("<meta property=\"og:url\" content=\"{}\" />", escape_attr(&page_url)));
+        head.push(format!("<meta property=\"og:image\" content=\"{}\" />", escape_attr(&card_url)));
+        head.push("<meta property=\"og:image:width\" content=\"1200\" />".to_string());
+        head.push("<meta property=\"og:image:height\" content=\"630\" />".to_string());
+        head.push("<meta name=\"twitter:card\" content=\"summary_large_image\" />".to_string());
+        context.insert("og_image", &card_url);
+        lap.mark("social cards");
+    }
+
+    if let Some(dir) = site.dump_context {
+        debug::write_context(dir, relative_path, &context)?;
+    }
+    let full_html = site.render("template.html", &context)
+        .with_context(|| format!("Failed to render template.html for {:?}", path))?;
+    lap.mark("template render");
 
     // Determine the output path, preserving directory structure
-    let mut output_path = out_dir.join(relative_path);
-    output_path.set_extension("html");
+    let output_path = site.out_dir.join(&output_relative);
+
+    // The page's h-entry properties, credited to the site's author when it names none
+    let entry = if site.config.rewrite.microformats {
+        let permalink = site_url(&site.config.base_url, Path::new(&output_relative));
+        let published = front_matter.date.as_deref().and_then(dates::parse_date);
+        let site_author = site.config.author.iter().map(|name| authors::Author { name: name.clone(), ..Default::default() });
+        let authors = if authors.is_empty() { site_author.collect() } else { authors.clone() };
+        microformats::properties(&permalink, published, &authors)
+    } else {
+        Vec::new()
+    };
+
+    // Apply post-processing rules to the rendered page
+    let full_html = site.rewriter.rewrite(
+        &full_html,
+        &PageInfo {
+            out_dir: site.out_dir,
+            output_path: &output_path,
+            head: &head,
+            entry: &entry,
+        },
+    )?;
+
+    let RenderedPage { html: full_html, .. } = plugins::run_stage(
+        &site.config.plugins,
+        Stage::HtmlRendered,
+        RenderedPage {
+            path: path.to_path_buf(),
+            html: full_html,
+        },
+    )?;
+    lap.mark("post-processing");
 
     // Create parent directories if they don't exist
     if let Some(parent) = output_path.parent() {
@@ -122,15 +1184,560 @@ fn process_markdown_file(path: &Path, input_dir: &Path, out_dir: &Path, tera: &T
     // Write the final HTML to the output file
     fs::write(&output_path, full_html)
         .with_context(|| format!("Failed to write HTML file: {:?}", output_path))?;
+    lap.mark("write");
+
+    let url = output_path.strip_prefix(site.out_dir)?.to_string_lossy().replace('\\', "/");
+    Ok(Some(PageSummary {
+        url: format!("/{}", url),
+        front_matter,
+        authors,
+        content: html_body,
+    }))
+}
+
+/// Reads a markdown source into its front matter and body, reusing the cached parse of unchanged files.
+fn read_page(path: &Path, config: &Config, cache: &Cache) -> Result<(FrontMatter, String)> {
+    let file_content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read markdown file: {:?}", path))?;
+    let parts = cache.get_or_insert("front-matter", &file_content, || {
+        let parsed_entity = Matter::<YAML>::new().parse(&file_content);
+        let front_matter = parsed_entity.data.map(|data| data.deserialize()).transpose()?;
+        Ok(serde_json::to_string(&SourceParts { front_matter, content: parsed_entity.content })?)
+    })?;
+    let parts: SourceParts = serde_json::from_str(&parts)?;
+
+    let front_matter: FrontMatter = if config.obsidian {
+        obsidian::front_matter(parts.front_matter, path)?
+    } else {
+        let data = parts.front_matter
+            .ok_or_else(|| anyhow::anyhow!("Missing front matter in {:?}", path))?;
+        serde_json::from_value(data).context("Failed to deserialize front matter")?
+    };
+    Ok((front_matter, parts.content))
+}
+
+/// Lists every published page for `site.pages`, reading only front matter.
+fn list_pages(
+    sources: &[PathBuf],
+    input_dir: &Path,
+    config: &Config,
+    cache: &Cache,
+    build_time: DateTime<Utc>,
+) -> Result<Vec<PageListing>> {
+    let history = (config.git_dates || config.git_contributors).then(|| history::History::load(input_dir));
+    let languages = i18n::Languages::new(config);
+    let mut pages = Vec::new();
+    for path in sources {
+        let (front_matter, _) = read_page(path, config, cache)?;
+        let published = front_matter.is_published(config.drafts, config.future, build_time)
+            .with_context(|| format!("Failed to read front matter of {:?}", path))?;
+        // Redirect stubs aren't pages to list or link to, and unlisted pages are shared by hand
+        if !published || front_matter.redirect_to.is_some() || front_matter.unlisted {
+            continue;
+        }
+        let relative = path.strip_prefix(input_dir)?;
+        let url = format!("/{}", relative.with_extension("html").to_string_lossy().replace('\\', "/"));
+        let lang = languages.of(&url);
+        let mut dirs = relative.parent().into_iter().flat_map(Path::components).map(|dir| dir.as_os_str().to_string_lossy());
+        let section = if lang == languages.default {
+            dirs.next().map(|dir| dir.into_owned())
+        } else {
+            dirs.nth(1).map(|dir| format!("{}/{}", lang, dir))
+        };
+        pages.push(PageListing {
+            title: front_matter.title,
+            date: front_matter.date,
+            description: front_matter.description,
+            lang: lang.to_string(),
+            url,
+            section,
+            weight: front_matter.weight,
+            taxonomies: config
+                .taxonomies
+                .iter()
+                .map(|taxonomy| Ok((taxonomy.name.clone(), terms(&front_matter.extra, &taxonomy.name, path)?)))
+                .collect::<Result<_>>()?,
+            series: front_matter.series,
+            draft: front_matter.draft,
+            updated: history
+                .as_ref()
+                .filter(|_| config.git_dates)
+                .and_then(|h| h.updated(path))
+                .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, false)),
+            contributors: history
+                .as_ref()
+                .filter(|_| config.git_contributors)
+                .map(|h| h.contributors(path))
+                .unwrap_or_default(),
+            menu: front_matter.menu,
+            sitemap: match front_matter.robots.as_deref() {
+                Some(robots) if seo::is_noindex(robots) => Some(sitemap::PageSitemap::Listed(false)),
+                _ => front_matter.sitemap,
+            },
+        });
+    }
+    Ok(pages)
+}
+
+/// Reads a taxonomy's terms from front matter, given as a list or a single string.
+fn terms(extra: &serde_json::Map<String, serde_json::Value>, taxonomy: &str, path: &Path) -> Result<Vec<String>> {
+    match extra.get(taxonomy) {
+        None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+        Some(serde_json::Value::String(term)) => Ok(vec![term.clone()]),
+        Some(value) => serde_json::from_value(value.clone())
+            .with_context(|| format!("`{}` in {:?} must be a list of strings", taxonomy, path)),
+    }
+}
+
+/// The `site` object every template sees: settings from rustic.toml plus every page.
+fn site_globals(config: &Config, listing: &Listing, build_time: DateTime<Utc>) -> Result<serde_json::Value> {
+    language_globals(config, listing, build_time, i18n::Languages::new(config).default)
+}
+
+/// The `site` object as pages in the language `lang` see it, with the title, description
+/// and menus configured for that language, where it has its own.
+fn language_globals(config: &Config, listing: &Listing, build_time: DateTime<Utc>, lang: &str) -> Result<serde_json::Value> {
+    let languages = i18n::Languages::new(config);
+    let language = languages.config(lang);
+    let menu_pages = listing
+        .pages
+        .iter()
+        .filter(|page| page.lang == lang)
+        .filter_map(|page| Some((page.title.as_str(), page.url.as_str(), page.menu.as_ref()?)));
+    let configured = language.map(|language| &language.menu).filter(|menu| !menu.is_empty()).unwrap_or(&config.menu);
+    let menus = menus::build(configured, menu_pages);
+    Ok(serde_json::json!({
+        "title": language.and_then(|language| language.title.as_ref()).or(config.title.as_ref()),
+        "description": language.and_then(|language| language.description.as_ref()).or(config.description.as_ref()),
+        "author": config.author,
+        "language": lang,
+        "languages": languages.list(),
+        "base_url": config.base_url,
+        "build_time": build_time.to_rfc3339(),
+        "extra": config.extra,
+        "pages": listing.pages,
+        "sections": listing.sections(),
+        "menus": menus,
+        "taxonomies": taxonomies::collect(&config.taxonomies, listing),
+    }))
+}
+
+/// Matches content paths against `--only` globs, where `*` stays within a directory and `**` crosses them.
+fn only_matcher(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid --only pattern: {:?}", pattern))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// Loads the `.html` and `.xml` templates under `templates/`, over those of the shared theme.
+fn load_templates(theme: Option<&Path>) -> Result<Tera> {
+    let mut files = BTreeMap::new();
+    for dir in theme.into_iter().chain([Path::new("templates")]) {
+        for entry in WalkDir::new(dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if matches!(path.extension().and_then(|s| s.to_str()), Some("html" | "xml")) {
+                let name = path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+                files.insert(name, path.to_path_buf());
+            }
+        }
+    }
+    let mut tera = Tera::default();
+    tera.add_template_files(files.iter().map(|(name, path)| (path, Some(name))))
+        .with_context(|| "Failed to initialize Tera templating engine")?;
+    Ok(tera)
+}
+
+/// Registers rustic's template functions: `get_env` limited to `template_env`,
+/// `is_active`, `load_data`, `trans`, and `cached_include`, whose partials see only `shared`.
+///
+/// Builds and the commands rendering templates outside one all register them here, so
+/// every template sees the same functions.
+fn register_template_functions(tera: &mut Tera, config: &Config, shared: TeraContext) -> Result<()> {
+    tera.register_function("get_env", env::GetEnv::new(config.template_env.clone()));
+    tera.register_function("is_active", menus::IsActive);
+    tera.register_function("load_data", load_data::LoadData::new(&config.load_data, config.cache_dir.clone()));
+    let language = config.language.as_deref().unwrap_or("en");
+    tera.register_function("trans", i18n::Trans::new(i18n::load(Path::new("i18n"))?, language));
+    let partials = tera.clone();
+    tera.register_function("cached_include", fragments::CachedInclude::new(partials, shared));
+    Ok(())
+}
+
+/// Series index pages are generated only for sites with a `series.html` template.
+fn has_series_index(site: &Site) -> bool {
+    site.tera.get_template_names().any(|name| name == "series.html")
+}
+
+/// Site-relative URL of a series' index page.
+fn series_url(name: &str) -> String {
+    format!("/series/{}.html", slug::slugify(name))
+}
+
+/// Renders `templates/series.html` once per series into `series/<slug>.html`.
+fn write_series_pages(site: &Site) -> Result<usize> {
+    let series: Vec<(&str, Vec<&PageListing>)> = site.listing.series().collect();
+
+    // Names that slugify alike would overwrite each other's index page
+    let clashes = slug::collisions(series.iter().map(|(name, _)| (*name, *name)));
+    if let Some((slug, names)) = clashes.into_iter().next() {
+        let names: Vec<&str> = names.iter().map(|(name, _)| *name).collect();
+        anyhow::bail!("Series {} would share the index page series/{}.html; rename one", names.join(", "), slug);
+    }
+
+    for (name, pages) in &series {
+        let url = series_url(name);
+        let mut context = TeraContext::new();
+        context.insert("series", &serde_json::json!({ "name": name, "url": url, "pages": pages }));
+        context.insert("title", name);
+        context.insert("url", &url);
+        context.insert("path_prefix", "..");
+        context.insert("bundles", &site.bundles);
+        context.insert("data", &site.data);
+        context.insert("site", &site.globals);
+        write_generated_page(site, "series.html", &context, &url)?;
+    }
+    Ok(series.len())
+}
+
+/// Writes each taxonomy's index and term pages when their templates exist, and term feeds.
+///
+/// `taxonomy.html` renders `<name>/index.html` with `taxonomy` and `terms`, and
+/// `taxonomy_term.html` renders `<name>/<term>/index.html` with `taxonomy` and `term`.
+fn write_taxonomies(site: &Site, bodies: &HashMap<String, String>) -> Result<()> {
+    let has_template = |name: &str| site.tera.get_template_names().any(|t| t == name);
+    let language = site.globals["language"].as_str().unwrap_or("en");
+    let site_title = site.config.title.as_deref().unwrap_or(&site.config.base_url);
+
+    for (taxonomy, terms) in taxonomies::collect(&site.config.taxonomies, &site.listing) {
+        let mut context = TeraContext::new();
+        context.insert("taxonomy", &taxonomy);
+        context.insert("bundles", &site.bundles);
+        context.insert("data", &site.data);
+        context.insert("site", &site.globals);
+        if has_template("taxonomy.html") {
+            context.insert("terms", &terms);
+            context.insert("title", &taxonomy);
+            context.insert("url", &format!("/{}/index.html", taxonomy));
+            context.insert("path_prefix", "..");
+            write_generated_page(site, "taxonomy.html", &context, &format!("{}/index.html", taxonomy))?;
+        }
+
+        for term in &terms {
+            if has_template("taxonomy_term.html") {
+                context.insert("term", term);
+                context.insert("title", &term.name);
+                context.insert("url", &term.url);
+                context.insert("path_prefix", "../..");
+                write_generated_page(site, "taxonomy_term.html", &context, &term.url)?;
+            }
+            if let Some(feed_url) = &term.feed_url {
+                let channel = feed::Channel {
+                    title: format!("{}: {}", site_title, term.name),
+                    description: format!("Pages filed under {} in {}", term.name, taxonomy),
+                    link: &term.url,
+                    path: feed_url,
+                    language,
+                };
+                feed::write_rss(&channel, &term.pages, bodies, site)?;
+            }
+        }
+        println!("Generated {} {} term(s)", terms.len(), taxonomy);
+    }
+    Ok(())
+}
+
+/// Writes `<section>/rss.xml`, newest first, for each section configured with `feed = true`,
+/// including its translations, e.g. `fr/blog/rss.xml`.
+fn write_section_feeds(site: &Site, bodies: &HashMap<String, String>) -> Result<()> {
+    let languages = i18n::Languages::new(site.config);
+    let sections = site.listing.sections();
+    let mut names: BTreeSet<&str> = site.config.sections.keys().map(String::as_str).collect();
+    names.extend(sections.keys());
+
+    for name in names.into_iter().filter(|name| listing::section_config(&site.config.sections, name).is_some_and(|section| section.feed)) {
+        let mut pages = sections.get(name).cloned().unwrap_or_default();
+        pages.sort_by_key(|page| std::cmp::Reverse(page.date.as_deref().and_then(dates::parse_date)));
+        let index = format!("/{}/index.html", name);
+        let path = format!("/{}/rss.xml", name);
+        let language = languages.of(&index);
+        let site_title = site.globals_for(language)["title"].as_str().unwrap_or(&site.config.base_url);
+        let section_title = site.listing.get(&index).map_or(name, |page| page.title.as_str());
+        let channel = feed::Channel {
+            title: format!("{}: {}", site_title, section_title),
+            description: format!("Latest pages in {}", section_title),
+            link: &index,
+            path: &path,
+            language,
+        };
+        feed::write_rss(&channel, &pages, bodies, site)?;
+        println!("Generated feed {}", path);
+    }
+    Ok(())
+}
+
+/// Writes `rss.xml` at the root of each language, e.g. `/rss.xml` and `/fr/rss.xml`,
+/// listing the language's dated pages newest first.
+fn write_language_feeds(site: &Site, bodies: &HashMap<String, String>) -> Result<()> {
+    let languages = i18n::Languages::new(site.config);
+    for code in languages.codes() {
+        let mut pages: Vec<&PageListing> =
+            site.listing.pages.iter().filter(|page| page.lang == code && page.date.is_some()).collect();
+        pages.sort_by_key(|page| std::cmp::Reverse(page.date.as_deref().and_then(dates::parse_date)));
+        let home = languages.url_in("/index.html", code);
+        let path = languages.url_in("/rss.xml", code);
+        let globals = site.globals_for(code);
+        let title = globals["title"].as_str().unwrap_or(&site.config.base_url);
+        let channel = feed::Channel {
+            title: title.to_string(),
+            description: globals["description"].as_str().map_or_else(|| format!("Latest pages on {}", title), str::to_string),
+            link: &home,
+            path: &path,
+            language: code,
+        };
+        feed::write_rss(&channel, &pages, bodies, site)?;
+        println!("Generated feed {}", path);
+    }
+    Ok(())
+}
+
+/// Renders a page the build generates itself, refusing to overwrite a content page.
+fn write_generated_page(site: &Site, template: &str, context: &TeraContext, url: &str) -> Result<()> {
+    let html = site.render(template, context)
+        .with_context(|| format!("Failed to render {} for {}", template, url))?;
+    let output_path = site.out_dir.join(url.trim_start_matches('/'));
+    if output_path.exists() {
+        anyhow::bail!("{} would overwrite {:?}", template, output_path);
+    }
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&output_path, html).with_context(|| format!("Failed to write {:?}", output_path))
+}
+
+/// Expands what a page's body holds besides markdown, ready to be converted: includes,
+/// wiki links, shortcodes, template calls when `render_templates` is set, and containers.
+fn expand_content(
+    markdown: &str,
+    path: &Path,
+    depth: usize,
+    page: &serde_json::Value,
+    render_templates: bool,
+    site: &Site,
+) -> Result<String> {
+    // Splice in shared snippets, then expand shortcodes into HTML before the markdown is converted
+    let markdown = include::expand(markdown, path)?;
+    let markdown = wikilinks::expand(&markdown, path, depth, &site.wiki_index, site.config.obsidian);
+    let markdown = shortcodes::expand(&markdown, path, site, render_templates)?;
+    let markdown = if render_templates {
+        render_content_template(&markdown, page, site).with_context(|| format!("Failed to render templates in {:?}", path))?
+    } else {
+        markdown
+    };
+    containers::expand(&markdown, path, site)
+}
+
+/// Indexes the pages under `input_dir` for wiki links; only pages published at
+/// `build_time` can be linked to.
+fn published_wiki_index(input_dir: &Path, config: &Config, cache: &Cache, build_time: DateTime<Utc>) -> Result<wikilinks::Index> {
+    wikilinks::Index::build(input_dir, |path| {
+        read_page(path, config, cache)
+            .and_then(|(front_matter, _)| front_matter.is_published(config.drafts, config.future, build_time))
+            .unwrap_or(true)
+    })
+}
+
+/// Renders a page's markdown body as a Tera template with `page`, `site` and `data` in context.
+fn render_content_template(markdown: &str, page: &serde_json::Value, site: &Site) -> Result<String> {
+    let mut context = TeraContext::new();
+    context.insert("page", page);
+    context.insert("site", site.globals_for(page["lang"].as_str().unwrap_or_default()));
+    context.insert("data", &site.data);
+    let mut body_tera = site.body_tera.borrow_mut();
+    render_body(body_tera.get_or_insert_with(|| site.tera.clone()), markdown, &context)
+}
+
+/// Renders `markdown` alongside the site's templates, so it calls the same functions they
+/// do, with `get_env` limited to `template_env`. `tera` is a scratch copy of the site's
+/// templates; each body replaces the last one added to it.
+fn render_body(tera: &mut Tera, markdown: &str, context: &TeraContext) -> Result<String> {
+    tera.add_raw_template("__content.md", markdown)?;
+    Ok(tera.render("__content.md", context)?)
+}
+
+/// Empties `out_dir` except for `static/`, which `assets::sync` keeps up to date.
+fn clean_output(out_dir: &Path) -> Result<()> {
+    fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create output directory: {:?}", out_dir))?;
+    for entry in fs::read_dir(out_dir)? {
+        let path = entry?.path();
+        if path == out_dir.join("static") {
+            continue;
+        }
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        removed.with_context(|| format!("Failed to remove existing output: {:?}", path))?;
+    }
+    Ok(())
+}
 
+/// Fails if two pages would be written to the same file, or (when the output was just
+/// cleaned, so anything there is a copied asset) a page would replace a copied asset.
+///
+/// Paths are compared case-insensitively, since macOS and Windows file systems (and many
+/// hosts) treat `About.html` and `about.html` as the same file.
+fn check_output_collisions(sources: &[PathBuf], input_dir: &Path, out_dir: &Path, cleaned: bool) -> Result<()> {
+    let mut outputs: HashMap<String, &Path> = HashMap::new();
+    for source in sources {
+        let output = out_dir.join(source.strip_prefix(input_dir)?).with_extension("html");
+        if cleaned && output.exists() {
+            anyhow::bail!(
+                "{:?} would overwrite {:?}, which was already copied from static files or attachments",
+                source,
+                output
+            );
+        }
+        if let Some(other) = outputs.insert(output.to_string_lossy().to_lowercase(), source) {
+            anyhow::bail!("{:?} and {:?} would both be written to {:?}", other, source, output);
+        }
+    }
     Ok(())
 }
 
+/// Where an unlisted page is written: its usual path plus a token that only builds with the
+/// same `unlisted_key` can work out, e.g. `notes/draft-1f3a9c0b2d4e6f80.html`.
+fn unlisted_path(relative_path: &Path, key: &str) -> String {
+    let source = relative_path.to_string_lossy().replace('\\', "/");
+    let token = bundle::short_hash(format!("{}:{}", key, source).as_bytes());
+    let stem = relative_path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = format!("{}-{}.html", stem, token);
+    match source.rfind('/') {
+        Some(i) => format!("{}/{}", &source[..i], file_name),
+        None => file_name,
+    }
+}
+
+/// Builds the public URL of an output file, absolute when `base_url` is configured.
+fn site_url(base_url: &str, output_relative: &Path) -> String {
+    let path = format!("/{}", output_relative.to_string_lossy().replace('\\', "/"));
+    if base_url.is_empty() {
+        path
+    } else {
+        feed::absolute_url(base_url, &path)
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*; // Import things from parent
 
+    #[test]
+    fn test_render_body_calls_site_functions() {
+        // Arrange
+        let mut tera = Tera::default();
+        tera.register_function("get_env", env::GetEnv::new(Vec::new()));
+        tera.register_function("is_active", menus::IsActive);
+        let context = TeraContext::new();
+
+        // Act
+        let active = render_body(&mut tera, r#"{{ is_active(url="/blog/", current="/blog/post.html") }}"#, &context);
+        let secret = render_body(&mut tera, r#"{{ get_env(name="HOME") }}"#, &context);
+
+        // Assert
+        assert_eq!(active.unwrap(), "true");
+        assert!(secret.is_err(), "get_env must honour template_env in content");
+    }
+
+    #[test]
+    fn test_output_collisions_are_detected() {
+        // Arrange
+        let sources = [PathBuf::from("content/About.md"), PathBuf::from("content/about.md")];
+
+        // Act
+        let result = check_output_collisions(&sources, Path::new("content"), Path::new("does-not-exist"), true);
+
+        // Assert
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("About.md") && message.contains("about.md"), "{}", message);
+    }
+
+    #[test]
+    fn test_site_globals_group_pages_by_section() {
+        // Arrange
+        let page = |url: &str, section: Option<&str>| PageListing {
+            title: url.to_string(),
+            url: url.to_string(),
+            date: None,
+            description: None,
+            lang: "en".to_string(),
+            section: section.map(str::to_string),
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: BTreeMap::new(),
+            menu: None,
+            sitemap: None,
+        };
+        let pages = vec![page("/index.html", None), page("/blog/a.html", Some("blog")), page("/blog/b.html", Some("blog"))];
+        let config = Config::default();
+
+        // Act
+        let globals = site_globals(&config, &Listing::new(pages, &config.sections), Utc::now()).unwrap();
+
+        // Assert
+        assert_eq!(globals["language"], "en");
+        assert_eq!(globals["pages"].as_array().unwrap().len(), 3);
+        assert_eq!(globals["sections"]["blog"][1]["url"], "/blog/b.html");
+    }
+
+    #[test]
+    fn test_publish_window() {
+        // Arrange
+        let front_matter: FrontMatter = serde_json::from_value(serde_json::json!({
+            "title": "Sale",
+            "stinger": "",
+            "publish_date": "2024-11-29",
+            "expiry_date": "2024-12-02",
+        }))
+        .unwrap();
+        let at = |date: &str| dates::parse_date(date).unwrap();
+
+        // Act & Assert
+        assert!(!front_matter.is_published(false, false, at("2024-11-28")).unwrap());
+        assert!(front_matter.is_published(false, true, at("2024-11-28")).unwrap());
+        assert!(front_matter.is_published(false, false, at("2024-11-29")).unwrap());
+        assert!(!front_matter.is_published(false, true, at("2024-12-02")).unwrap());
+    }
+
+    #[test]
+    fn test_unlisted_path() {
+        // Act
+        let path = unlisted_path(Path::new("notes/draft.md"), "secret");
+
+        // Assert
+        assert!(path.starts_with("notes/draft-") && path.ends_with(".html"), "{}", path);
+        assert_eq!(path, unlisted_path(Path::new("notes/draft.md"), "secret"));
+        assert_ne!(path, unlisted_path(Path::new("notes/draft.md"), "other"));
+    }
+
+    #[test]
+    fn test_only_matcher() {
+        // Act
+        let only = only_matcher(&["blog/2024/**".to_string(), "/about.md".to_string()]).unwrap();
+
+        // Assert
+        assert!(only.is_match("blog/2024/05/post.md"));
+        assert!(only.is_match("about.md"));
+        assert!(!only.is_match("blog/2023/post.md"));
+    }
+
     #[test]
     fn test_markdown_to_html() {
         // Arrange
@@ -0,0 +1,119 @@
+// Rustic - navigation menus from `[[menu.<name>]]` entries and page front matter
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tera::{Function, Value};
+
+/// A link in a navigation menu, as declared under `[[menu.main]]` and exposed as `site.menus.main`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MenuEntry {
+    pub name: String,
+    pub url: String,
+    /// Entries are sorted by weight, lightest first, then by name
+    #[serde(default)]
+    pub weight: i64,
+}
+
+/// A page's `menu:` front matter: a menu name, a list of them, or per-menu settings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PageMenus {
+    One(String),
+    Many(Vec<String>),
+    Detailed(BTreeMap<String, PageMenu>),
+}
+
+/// Overrides for a page's entry in one menu.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PageMenu {
+    /// Label to use instead of the page title
+    pub name: Option<String>,
+    #[serde(default)]
+    pub weight: i64,
+}
+
+/// Merges configured entries with pages that add themselves to a menu, each menu sorted.
+pub fn build<'a>(
+    configured: &BTreeMap<String, Vec<MenuEntry>>,
+    pages: impl IntoIterator<Item = (&'a str, &'a str, &'a PageMenus)>,
+) -> BTreeMap<String, Vec<MenuEntry>> {
+    let mut menus = configured.clone();
+    for (title, url, page_menus) in pages {
+        let entries: Vec<(String, PageMenu)> = match page_menus {
+            PageMenus::One(menu) => vec![(menu.clone(), PageMenu::default())],
+            PageMenus::Many(names) => names.iter().map(|menu| (menu.clone(), PageMenu::default())).collect(),
+            PageMenus::Detailed(menus) => menus.clone().into_iter().collect(),
+        };
+        for (menu, settings) in entries {
+            menus.entry(menu).or_default().push(MenuEntry {
+                name: settings.name.unwrap_or_else(|| title.to_string()),
+                url: url.to_string(),
+                weight: settings.weight,
+            });
+        }
+    }
+    for entries in menus.values_mut() {
+        entries.sort_by(|a, b| a.weight.cmp(&b.weight).then_with(|| a.name.cmp(&b.name)));
+    }
+    menus
+}
+
+/// Tera function telling whether a menu link points at the current page or its section.
+///
+/// `{% if is_active(url=item.url, current=url) %}`: a link to `/blog/` or
+/// `/blog/index.html` is active on every page under `/blog/`.
+pub struct IsActive;
+
+impl Function for IsActive {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let arg = |name: &str| {
+            args.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| tera::Error::msg(format!("is_active needs a `{}` argument", name)))
+        };
+        let (url, current) = (arg("url")?, arg("current")?);
+        if url.contains("://") {
+            return Ok(Value::Bool(false));
+        }
+
+        let section = url.strip_suffix("index.html").unwrap_or(url);
+        let active = url == current || (section.ends_with('/') && current.starts_with(section));
+        Ok(Value::Bool(active))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menus_merge_config_and_pages() {
+        // Arrange
+        let configured = BTreeMap::from([(
+            "main".to_string(),
+            vec![MenuEntry { name: "Blog".to_string(), url: "/blog/index.html".to_string(), weight: 10 }],
+        )]);
+        let about = PageMenus::Detailed(BTreeMap::from([(
+            "main".to_string(),
+            PageMenu { name: Some("About us".to_string()), weight: 20 },
+        )]));
+        let home = PageMenus::One("main".to_string());
+
+        // Act
+        let menus = build(&configured, [("About", "/about.html", &about), ("Home", "/index.html", &home)]);
+        let active = |url: &str, current: &str| {
+            let args = HashMap::from([("url".to_string(), Value::from(url)), ("current".to_string(), Value::from(current))]);
+            IsActive.call(&args).unwrap()
+        };
+
+        // Assert
+        let names: Vec<&str> = menus["main"].iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["Home", "Blog", "About us"]);
+        assert_eq!(active("/blog/index.html", "/blog/2024/post.html"), Value::Bool(true));
+        assert_eq!(active("/about.html", "/blog/index.html"), Value::Bool(false));
+    }
+}
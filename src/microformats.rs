@@ -0,0 +1,102 @@
+// Rustic - microformats2 h-entry markup added to rendered pages
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use lol_html::html_content::{ContentType, Element};
+
+use crate::authors::Author;
+use crate::postprocess::{PageInfo, Transform};
+use crate::shortcodes::escape_attr;
+
+/// Adds a class to every element matching a selector, keeping any it already has.
+pub struct AddClass {
+    pub selector: &'static str,
+    pub class: &'static str,
+}
+
+impl Transform for AddClass {
+    fn selector(&self) -> &str {
+        self.selector
+    }
+
+    fn element(&self, element: &mut Element, _page: &PageInfo) -> Result<()> {
+        let classes = element.get_attribute("class").unwrap_or_default();
+        if !classes.split_whitespace().any(|class| class == self.class) {
+            element.set_attribute("class", format!("{} {}", classes, self.class).trim())?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks `<main>` as the page's h-entry and appends its hidden properties.
+pub struct Entry;
+
+impl Transform for Entry {
+    fn selector(&self) -> &str {
+        "main"
+    }
+
+    fn element(&self, element: &mut Element, page: &PageInfo) -> Result<()> {
+        AddClass { selector: "main", class: "h-entry" }.element(element, page)?;
+        if !page.entry.is_empty() {
+            element.append(&format!("<div hidden>{}</div>\n", page.entry.concat()), ContentType::Html);
+        }
+        Ok(())
+    }
+}
+
+/// The h-entry properties of a page that its visible markup doesn't carry: its permalink,
+/// publication date and authors as h-cards.
+pub fn properties(url: &str, published: Option<DateTime<Utc>>, authors: &[Author]) -> Vec<String> {
+    let mut properties = vec![format!("<a class=\"u-url\" href=\"{}\"></a>", escape_attr(url))];
+    if let Some(published) = published {
+        let datetime = published.to_rfc3339_opts(SecondsFormat::Secs, true);
+        properties.push(format!("<time class=\"dt-published\" datetime=\"{}\"></time>", datetime));
+    }
+    for author in authors {
+        let mut card = format!("<span class=\"p-author h-card\"><span class=\"p-name\">{}</span>", escape_attr(&author.name));
+        if let Some(url) = &author.url {
+            card.push_str(&format!("<a class=\"u-url\" href=\"{}\"></a>", escape_attr(url)));
+        }
+        if let Some(avatar) = &author.avatar {
+            card.push_str(&format!("<img class=\"u-photo\" src=\"{}\" alt=\"\">", escape_attr(avatar)));
+        }
+        card.push_str("</span>");
+        properties.push(card);
+    }
+    properties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::{RewriteConfig, Rewriter};
+    use std::path::Path;
+
+    #[test]
+    fn test_entry_markup() {
+        // Arrange
+        let config = RewriteConfig { microformats: true, ..RewriteConfig::default() };
+        let rewriter = Rewriter::from_config(&config, None).unwrap();
+        let ada = Author { name: "Ada".to_string(), url: Some("https://ada.example".to_string()), ..Author::default() };
+        let entry = properties("https://example.com/post.html", None, &[ada]);
+        let page = PageInfo {
+            out_dir: Path::new("public"),
+            output_path: Path::new("public/post.html"),
+            head: &[],
+            entry: &entry,
+        };
+        let html = r#"<main class="wide"><h1>Post</h1><article>Hi</article></main>"#;
+
+        // Act
+        let rewritten = rewriter.rewrite(html, &page).unwrap();
+
+        // Assert
+        assert_eq!(
+            rewritten,
+            "<main class=\"wide h-entry\"><h1 class=\"p-name\">Post</h1><article class=\"e-content\">Hi</article>\
+             <div hidden><a class=\"u-url\" href=\"https://example.com/post.html\"></a>\
+             <span class=\"p-author h-card\"><span class=\"p-name\">Ada</span><a class=\"u-url\" href=\"https://ada.example\"></a></span></div>\n</main>"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+// Rustic - email-safe HTML of a page, for pasting into a newsletter service
+
+use anyhow::{bail, Context, Result};
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use pulldown_cmark::{html, Parser as MarkdownParser};
+use std::fs;
+use std::path::Path;
+use tera::Context as TeraContext;
+use url::Url;
+
+use crate::config::Config;
+use crate::shortcodes::escape_attr;
+use crate::timings::Timings;
+use crate::{expand_content, read_page, Site};
+
+/// Inline styles for elements email clients would otherwise render unstyled, since many
+/// strip `<style>` blocks. Styles already on an element take precedence.
+const STYLES: &[(&str, &str)] = &[
+    ("h1", "font-size: 28px; line-height: 1.25; margin: 0 0 16px; color: #2c3e50;"),
+    ("h2", "font-size: 22px; line-height: 1.3; margin: 24px 0 12px; color: #2c3e50;"),
+    ("h3", "font-size: 18px; line-height: 1.3; margin: 20px 0 8px; color: #2c3e50;"),
+    ("p", "margin: 0 0 16px;"),
+    ("a", "color: #1a73e8;"),
+    ("img", "max-width: 100%; height: auto; border: 0;"),
+    ("blockquote", "margin: 0 0 16px; padding-left: 16px; border-left: 4px solid #dddddd; color: #555555;"),
+    ("pre", "background-color: #f4f4f4; padding: 12px; overflow-x: auto; white-space: pre-wrap;"),
+    ("code", "font-family: Menlo, Consolas, monospace; font-size: 14px;"),
+    ("ul, ol", "margin: 0 0 16px; padding-left: 24px;"),
+];
+
+/// A single 600px column built from tables, the layout email clients render consistently.
+const LAYOUT: &str = "<!DOCTYPE html>\n<html lang=\"{language}\">\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n<title>{title}</title>\n</head>\n\
+<body style=\"margin: 0; padding: 0; background-color: #f4f4f4;\">\n\
+<table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" border=\"0\" style=\"background-color: #f4f4f4;\">\n\
+<tr><td align=\"center\" style=\"padding: 24px 12px;\">\n\
+<table role=\"presentation\" width=\"600\" cellpadding=\"0\" cellspacing=\"0\" border=\"0\" style=\"width: 100%; max-width: 600px; background-color: #ffffff;\">\n\
+<tr><td style=\"padding: 32px; font-family: Georgia, serif; font-size: 16px; line-height: 1.6; color: #333333;\">\n\
+<h1>{title}</h1>\n{content}\
+<p style=\"font-size: 14px; color: #777777;\"><a href=\"{url}\">Read this on the web</a></p>\n\
+</td></tr>\n</table>\n</td></tr>\n</table>\n</body>\n</html>\n";
+
+/// Renders the page at `path` for email and writes it to `output`.
+///
+/// The body is expanded as a build would, so shortcodes, wiki links and containers work;
+/// galleries write their images to `out_dir`. The page goes through
+/// `templates/newsletter.html` when the site has one, and the built-in layout otherwise.
+/// Either way, styles are inlined and URLs made absolute.
+pub fn export(path: &Path, input_dir: &Path, out_dir: &Path, config: &Config, output: &Path) -> Result<()> {
+    if config.base_url.is_empty() {
+        bail!("Set base_url in the config; links and images in emails need absolute URLs");
+    }
+    let timings = Timings::default();
+    let site = Site::outside_build(config, input_dir, out_dir, &timings)?;
+    let (front_matter, markdown) = read_page(path, config, &site.cache)?;
+
+    let relative = path.strip_prefix(input_dir).with_context(|| format!("{:?} isn't in {:?}", path, input_dir))?;
+    let relative_url = relative.with_extension("html").to_string_lossy().replace('\\', "/");
+    let page_url = format!("{}/{}", config.base_url.trim_end_matches('/'), relative_url);
+    let page_url = Url::parse(&page_url).with_context(|| format!("Invalid page URL: {}", page_url))?;
+
+    let mut page = serde_json::to_value(&front_matter)?;
+    page["url"] = serde_json::json!(format!("/{}", relative_url));
+    let depth = relative.ancestors().count() - 2; // -1 for self, -1 for root component
+    let markdown = expand_content(&markdown, path, depth, &page, front_matter.render_templates, &site)?;
+    let mut content = String::new();
+    html::push_html(&mut content, MarkdownParser::new(&markdown));
+
+    let html = if site.tera.get_template_names().any(|name| name == "newsletter.html") {
+        let mut context = TeraContext::new();
+        context.insert("title", &front_matter.title);
+        context.insert("stinger", &front_matter.stinger);
+        context.insert("date", &front_matter.date);
+        context.insert("description", &front_matter.description);
+        context.insert("content", &content);
+        context.insert("url", page_url.as_str());
+        context.insert("data", &site.data);
+        context.insert("site", &site.globals);
+        context.insert("page", &page);
+        site.render("newsletter.html", &context).context("Failed to render newsletter.html")?
+    } else {
+        LAYOUT
+            .replace("{language}", &escape_attr(config.language.as_deref().unwrap_or("en")))
+            .replace("{url}", &escape_attr(page_url.as_str()))
+            .replace("{title}", &escape_attr(&front_matter.title))
+            .replace("{content}", &content)
+    };
+
+    fs::write(output, email_safe(&html, &page_url)?).with_context(|| format!("Failed to write newsletter: {:?}", output))?;
+    println!("✅ Wrote {:?}", output);
+    Ok(())
+}
+
+/// Inlines `STYLES` and resolves links and images against the page's public URL.
+fn email_safe(html: &str, page_url: &Url) -> Result<String> {
+    let absolute = |value: String| page_url.join(&value).map(String::from).unwrap_or(value);
+    let mut settings = RewriteStrSettings::new()
+        .append_element_content_handler(element!("a[href]", |el| {
+            el.set_attribute("href", &absolute(el.get_attribute("href").unwrap_or_default()))?;
+            Ok(())
+        }))
+        .append_element_content_handler(element!("img[src]", |el| {
+            el.set_attribute("src", &absolute(el.get_attribute("src").unwrap_or_default()))?;
+            Ok(())
+        }));
+    for (selector, style) in STYLES {
+        settings = settings.append_element_content_handler(element!(selector, move |el| {
+            let existing = el.get_attribute("style").unwrap_or_default();
+            el.set_attribute("style", format!("{} {}", style, existing).trim())?;
+            Ok(())
+        }));
+    }
+    Ok(rewrite_str(html, settings)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_safe() {
+        // Arrange
+        let page_url = Url::parse("https://example.com/posts/hello.html").unwrap();
+        let html = "<p style=\"color: red;\">See <a href=\"../about.html\">us</a> <img src=\"/img/a.png\"></p>";
+
+        // Act
+        let email = email_safe(html, &page_url).unwrap();
+
+        // Assert
+        assert_eq!(
+            email,
+            "<p style=\"margin: 0 0 16px; color: red;\">See <a href=\"https://example.com/about.html\" style=\"color: #1a73e8;\">us</a> \
+             <img src=\"https://example.com/img/a.png\" style=\"max-width: 100%; height: auto; border: 0;\"></p>"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// Rustic - compatibility with Obsidian vault conventions
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::{exif, FrontMatter};
+
+/// Reads a note's front matter, defaulting the title to the file name and the stinger to empty.
+///
+/// Obsidian notes often have no front matter at all, or only `tags` and `aliases`.
+pub fn front_matter(data: Option<Value>, path: &Path) -> Result<FrontMatter> {
+    let mut value = data.unwrap_or_else(|| Value::Object(Default::default()));
+    let Some(fields) = value.as_object_mut() else {
+        anyhow::bail!("Front matter in {:?} is not a mapping", path);
+    };
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    fields.entry("title").or_insert_with(|| Value::from(stem));
+    fields.entry("stinger").or_insert_with(|| Value::from(""));
+    serde_json::from_value(value).context("Failed to deserialize front matter")
+}
+
+/// Copies every non-markdown file in the vault to the same place in `out_dir`.
+///
+/// Hidden folders such as `.obsidian/` and `.trash/` are skipped. Image metadata is
+/// removed from the copies when `strip_metadata` is set.
+pub fn copy_attachments(input_dir: &Path, out_dir: &Path, strip_metadata: bool) -> Result<usize> {
+    let mut copied = 0;
+    let entries = WalkDir::new(input_dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok());
+
+    for entry in entries {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) == Some("md") {
+            continue;
+        }
+        let target = out_dir.join(path.strip_prefix(input_dir)?);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory for {:?}", target))?;
+        }
+        fs::copy(path, &target)
+            .with_context(|| format!("Failed to copy attachment: {:?}", path))?;
+        if strip_metadata {
+            exif::strip_file(&target)?;
+        }
+        copied += 1;
+    }
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_matter_defaults() {
+        // Act
+        let front_matter = front_matter(None, Path::new("vault/Daily Note.md")).unwrap();
+
+        // Assert
+        assert_eq!(front_matter.title, "Daily Note");
+        assert_eq!(front_matter.stinger, "");
+    }
+}
@@ -0,0 +1,82 @@
+// Rustic - external asset pipeline steps (Tailwind, PostCSS, ...)
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A single asset pipeline command declared under `[[pipeline]]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+pub struct PipelineStep {
+    /// Label shown in the build output
+    pub name: String,
+    /// Shell command to run from the project root
+    pub command: String,
+    /// Files or directories the command reads, which `rustic serve` watches
+    #[serde(default)]
+    pub inputs: Vec<PathBuf>,
+    /// Files or directories the command writes, which `rustic serve` ignores so that
+    /// writing them doesn't start another rebuild
+    #[serde(default)]
+    pub outputs: Vec<PathBuf>,
+}
+
+/// Shell commands run around the build, declared under `[hooks]` in rustic.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Commands run before anything is written to the output directory
+    pub pre_build: Vec<String>,
+    /// Commands run once every page has been written
+    pub post_build: Vec<String>,
+}
+
+/// Runs hook commands in order with build metadata exposed as `RUSTIC_*` env vars.
+pub fn run_hooks(stage: &str, commands: &[String], env: &[(&str, String)]) -> Result<()> {
+    for command in commands {
+        println!("Running {} hook: {}", stage, command);
+        let status = shell_command(command)
+            .envs(env.iter().map(|(key, value)| (*key, value)))
+            .env("RUSTIC_HOOK", stage)
+            .status()
+            .with_context(|| format!("Failed to spawn {} hook: {}", stage, command))?;
+        if !status.success() {
+            bail!("{} hook `{}` exited with {}", stage, command, status);
+        }
+    }
+    Ok(())
+}
+
+/// Runs every pipeline step in declaration order, stopping at the first failure.
+pub fn run_pipeline(steps: &[PipelineStep]) -> Result<()> {
+    for step in steps {
+        println!("Running pipeline step: {}", step.name);
+        run_shell(&step.command)
+            .with_context(|| format!("Asset pipeline step '{}' failed", step.name))?;
+    }
+    Ok(())
+}
+
+/// Builds a `Command` that runs the given line through the platform shell.
+pub fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Runs a shell command, inheriting stdio, and fails if it exits unsuccessfully.
+pub fn run_shell(command: &str) -> Result<()> {
+    let status = shell_command(command)
+        .status()
+        .with_context(|| format!("Failed to spawn command: {}", command))?;
+    if !status.success() {
+        bail!("Command `{}` exited with {}", command, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+// Rustic - external-process plugins that exchange JSON over stdio
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::Stdio;
+
+use crate::pipeline::shell_command;
+
+/// Points in the build pipeline where plugins are invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// After front matter and markdown are read, before conversion to HTML
+    PageParsed,
+    /// After the page template has been rendered
+    HtmlRendered,
+    /// Once every page has been written
+    BuildFinished,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::PageParsed => "page_parsed",
+            Stage::HtmlRendered => "html_rendered",
+            Stage::BuildFinished => "build_finished",
+        }
+    }
+}
+
+/// An executable declared under `[[plugins]]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+pub struct Plugin {
+    /// Shell command that starts the plugin
+    pub command: String,
+    /// Stages the plugin wants to be called for
+    pub stages: Vec<Stage>,
+}
+
+/// Passes `payload` through every plugin registered for `stage`, in declaration order.
+///
+/// Each plugin receives the payload as JSON on stdin and must print the (possibly
+/// modified) payload as JSON on stdout.
+pub fn run_stage<T>(plugins: &[Plugin], stage: Stage, mut payload: T) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    for plugin in plugins.iter().filter(|p| p.stages.contains(&stage)) {
+        let output = call(plugin, stage, &payload)?;
+        payload = serde_json::from_slice(&output).with_context(|| {
+            format!("Plugin `{}` returned invalid JSON for {}", plugin.command, stage.name())
+        })?;
+    }
+    Ok(payload)
+}
+
+/// Sends `payload` to every plugin registered for `stage`, ignoring anything they print.
+pub fn notify<T: Serialize>(plugins: &[Plugin], stage: Stage, payload: &T) -> Result<()> {
+    for plugin in plugins.iter().filter(|p| p.stages.contains(&stage)) {
+        call(plugin, stage, payload)?;
+    }
+    Ok(())
+}
+
+/// Spawns a plugin, feeds it the JSON payload and returns its stdout.
+fn call<T: Serialize>(plugin: &Plugin, stage: Stage, payload: &T) -> Result<Vec<u8>> {
+    let input = serde_json::to_vec(payload).context("Failed to serialize plugin payload")?;
+
+    let mut child = shell_command(&plugin.command)
+        .env("RUSTIC_STAGE", stage.name())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to spawn plugin: {}", plugin.command))?;
+
+    // Write from a separate thread so a plugin streaming its output can't deadlock us
+    let mut stdin = child.stdin.take().context("Plugin stdin was not captured")?;
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output();
+        let _ = writer.join();
+        output
+    })
+    .with_context(|| format!("Failed to run plugin: {}", plugin.command))?;
+
+    if !output.status.success() {
+        bail!(
+            "Plugin `{}` exited with {} during {}",
+            plugin.command,
+            output.status,
+            stage.name()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_stage_skips_plugins_for_other_stages() {
+        // Arrange
+        let plugins = vec![Plugin {
+            command: "exit 1".to_string(),
+            stages: vec![Stage::BuildFinished],
+        }];
+
+        // Act
+        let result = run_stage(&plugins, Stage::PageParsed, "unchanged".to_string()).unwrap();
+
+        // Assert
+        assert_eq!(result, "unchanged");
+    }
+}
@@ -0,0 +1,312 @@
+// Rustic - streaming HTML rewriting that runs after templates are rendered
+
+use anyhow::{Context, Result};
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{rewrite_str, ElementContentHandlers, HandlerResult, RewriteStrSettings, Selector};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::microformats::{self, AddClass};
+
+/// Built-in rewrite rules toggled under `[rewrite]` in rustic.toml.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RewriteConfig {
+    /// Open off-site links in a new tab
+    pub external_links_new_tab: bool,
+    /// `rel` value added to off-site links, e.g. "noopener noreferrer"
+    pub external_links_rel: Option<String>,
+    /// Add `loading="lazy"`, `decoding="async"` and intrinsic width/height to local images
+    pub lazy_images: bool,
+    /// Mark up `<main>` as a microformats2 h-entry, with its `h1` as the name and `article` as the content
+    pub microformats: bool,
+}
+
+/// The page currently being rewritten.
+pub struct PageInfo<'a> {
+    /// Root of the generated site
+    pub out_dir: &'a Path,
+    /// Where the page will be written, inside `out_dir`
+    pub output_path: &'a Path,
+    /// Extra tags (meta, link) appended to the page's `<head>`
+    pub head: &'a [String],
+    /// Hidden h-entry properties appended to `<main>`, with microformats on
+    pub entry: &'a [String],
+}
+
+/// A rewrite rule applied to every element matching its selector.
+pub trait Transform {
+    /// CSS selector of the elements this transform visits
+    fn selector(&self) -> &str;
+
+    /// Called for each matching element, in document order
+    fn element(&self, element: &mut Element, page: &PageInfo) -> Result<()>;
+}
+
+/// Runs a list of transforms over rendered HTML in a single streaming pass.
+#[derive(Default)]
+pub struct Rewriter {
+    transforms: Vec<(Selector, Box<dyn Transform>)>,
+}
+
+impl Rewriter {
+    /// Creates a rewriter with the built-in rules enabled by the config.
+    pub fn from_config(config: &RewriteConfig, critical_css: Option<String>) -> Result<Self> {
+        let mut rewriter = Self::default();
+        rewriter.add(HeadTags)?;
+
+        if let Some(css) = critical_css {
+            rewriter.add(CriticalCss { css })?;
+        }
+        if config.external_links_new_tab || config.external_links_rel.is_some() {
+            rewriter.add(ExternalLinks {
+                new_tab: config.external_links_new_tab,
+                rel: config.external_links_rel.clone(),
+            })?;
+        }
+
+        if config.lazy_images {
+            rewriter.add(LazyImages)?;
+        }
+
+        if config.microformats {
+            rewriter.add(microformats::Entry)?;
+            rewriter.add(AddClass { selector: "main h1", class: "p-name" })?;
+            rewriter.add(AddClass { selector: "main article", class: "e-content" })?;
+        }
+
+        Ok(rewriter)
+    }
+
+    /// Registers a transform; transforms run in the order they were added.
+    pub fn add(&mut self, transform: impl Transform + 'static) -> Result<()> {
+        let selector = transform
+            .selector()
+            .parse()
+            .with_context(|| format!("Invalid rewrite selector: {}", transform.selector()))?;
+        self.transforms.push((selector, Box::new(transform)));
+        Ok(())
+    }
+
+    /// Applies every registered transform to the page's HTML.
+    pub fn rewrite(&self, html: &str, page: &PageInfo) -> Result<String> {
+        if self.transforms.len() == 1 && page.head.is_empty() {
+            return Ok(html.to_string());
+        }
+
+        let settings = self.transforms.iter().fold(
+            RewriteStrSettings::new(),
+            |settings, (selector, transform)| {
+                let handler = move |element: &mut Element| -> HandlerResult {
+                    transform.element(element, page).map_err(Into::into)
+                };
+                settings.append_element_content_handler((
+                    Cow::Borrowed(selector),
+                    ElementContentHandlers::default().element(handler),
+                ))
+            },
+        );
+
+        rewrite_str(html, settings)
+            .with_context(|| format!("Failed to post-process HTML for {:?}", page.output_path))
+    }
+}
+
+/// Appends the page's extra head tags, such as Open Graph metadata.
+struct HeadTags;
+
+impl Transform for HeadTags {
+    fn selector(&self) -> &str {
+        "head"
+    }
+
+    fn element(&self, element: &mut Element, page: &PageInfo) -> Result<()> {
+        for tag in page.head {
+            element.append(&format!("{}\n", tag), ContentType::Html);
+        }
+        Ok(())
+    }
+}
+
+/// Inlines a stylesheet at the end of `<head>` so first paint doesn't wait on a request.
+struct CriticalCss {
+    css: String,
+}
+
+impl Transform for CriticalCss {
+    fn selector(&self) -> &str {
+        "head"
+    }
+
+    fn element(&self, element: &mut Element, _page: &PageInfo) -> Result<()> {
+        element.append(&format!("<style>{}</style>\n", self.css.trim()), ContentType::Html);
+        Ok(())
+    }
+}
+
+/// Adds `target` and `rel` attributes to links pointing off-site. Configured `rel`
+/// tokens are added to any the link already has, such as `rel="me"`.
+struct ExternalLinks {
+    new_tab: bool,
+    rel: Option<String>,
+}
+
+impl Transform for ExternalLinks {
+    fn selector(&self) -> &str {
+        "a[href]"
+    }
+
+    fn element(&self, element: &mut Element, _page: &PageInfo) -> Result<()> {
+        let href = element.get_attribute("href").unwrap_or_default();
+        if !(href.starts_with("http://") || href.starts_with("https://")) {
+            return Ok(());
+        }
+
+        if self.new_tab {
+            element.set_attribute("target", "_blank")?;
+        }
+        if let Some(rel) = &self.rel {
+            let mut tokens: Vec<String> = element
+                .get_attribute("rel")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+            for token in rel.split_whitespace() {
+                if !tokens.iter().any(|t| t.eq_ignore_ascii_case(token)) {
+                    tokens.push(token.to_string());
+                }
+            }
+            element.set_attribute("rel", &tokens.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes images lazy and reserves their space to avoid layout shift.
+struct LazyImages;
+
+impl Transform for LazyImages {
+    fn selector(&self) -> &str {
+        "img[src]"
+    }
+
+    fn element(&self, element: &mut Element, page: &PageInfo) -> Result<()> {
+        if !element.has_attribute("loading") {
+            element.set_attribute("loading", "lazy")?;
+        }
+        if !element.has_attribute("decoding") {
+            element.set_attribute("decoding", "async")?;
+        }
+        if element.has_attribute("width") || element.has_attribute("height") {
+            return Ok(());
+        }
+
+        let src = element.get_attribute("src").unwrap_or_default();
+        let Some(image_path) = resolve_local_url(&src, page) else {
+            return Ok(());
+        };
+        match imagesize::size(&image_path) {
+            Ok(size) => {
+                element.set_attribute("width", &size.width.to_string())?;
+                element.set_attribute("height", &size.height.to_string())?;
+            }
+            Err(err) => eprintln!(
+                "⚠️  Could not read dimensions of {:?} referenced from {:?}: {}",
+                image_path, page.output_path, err
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Maps a URL found in a page to the file it refers to inside the output directory.
+///
+/// Returns `None` for remote and inline URLs.
+pub fn resolve_local_url(url: &str, page: &PageInfo) -> Option<PathBuf> {
+    if url.is_empty() || url.starts_with("//") || url.starts_with('#') || url.contains(':') {
+        return None;
+    }
+
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    match url.strip_prefix('/') {
+        Some(site_relative) => Some(page.out_dir.join(site_relative)),
+        None => Some(page.output_path.parent()?.join(url)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> PageInfo<'static> {
+        PageInfo {
+            out_dir: Path::new("public"),
+            output_path: Path::new("public/index.html"),
+            head: &[],
+            entry: &[],
+        }
+    }
+
+    #[test]
+    fn test_critical_css_is_inlined_into_head() {
+        // Arrange
+        let css = Some("body { margin: 0; }\n".to_string());
+        let rewriter = Rewriter::from_config(&RewriteConfig::default(), css).unwrap();
+        let html = "<html><head><title>Hi</title></head><body></body></html>";
+
+        // Act
+        let rewritten = rewriter.rewrite(html, &page()).unwrap();
+
+        // Assert
+        assert_eq!(
+            rewritten,
+            "<html><head><title>Hi</title><style>body { margin: 0; }</style>\n</head><body></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_external_links_get_rel() {
+        // Arrange
+        let config = RewriteConfig {
+            external_links_new_tab: false,
+            external_links_rel: Some("noopener noreferrer".into()),
+            ..RewriteConfig::default()
+        };
+        let rewriter = Rewriter::from_config(&config, None).unwrap();
+        let html = concat!(
+            r#"<a href="https://example.com">x</a><a href="/about.html">y</a>"#,
+            r#"<a href="https://social.example/@me" rel="me noopener">z</a>"#
+        );
+
+        // Act
+        let rewritten = rewriter.rewrite(html, &page()).unwrap();
+
+        // Assert
+        assert_eq!(
+            rewritten,
+            concat!(
+                r#"<a href="https://example.com" rel="noopener noreferrer">x</a><a href="/about.html">y</a>"#,
+                r#"<a href="https://social.example/@me" rel="me noopener noreferrer">z</a>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_resolve_local_url() {
+        // Arrange
+        let page = PageInfo {
+            out_dir: Path::new("public"),
+            output_path: Path::new("public/blog/post.html"),
+            head: &[],
+            entry: &[],
+        };
+
+        // Act & Assert
+        assert_eq!(resolve_local_url("/static/a.png", &page), Some(PathBuf::from("public/static/a.png")));
+        assert_eq!(resolve_local_url("img/b.png?v=2", &page), Some(PathBuf::from("public/blog/img/b.png")));
+        assert_eq!(resolve_local_url("https://example.com/c.png", &page), None);
+        assert_eq!(resolve_local_url("data:image/png;base64,AAAA", &page), None);
+    }
+}
@@ -0,0 +1,159 @@
+// Rustic - single-document HTML of a section, for printing or saving as PDF
+
+use anyhow::{Context, Result};
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::Config;
+use crate::export::{self, Chapter};
+use crate::shortcodes::escape_attr;
+
+/// Page breaks before each chapter, running page numbers and page numbers in the
+/// table of contents, for browsers and PDF tools that support CSS paged media.
+const STYLESHEET: &str = "\
+@page { margin: 2cm; @bottom-center { content: counter(page); } }
+body { font-family: Georgia, serif; line-height: 1.5; max-width: 42em; margin: 0 auto; }
+.title-page { text-align: center; padding-top: 30vh; }
+.toc, .chapter { break-before: page; }
+.toc a { color: inherit; text-decoration: none; }
+.toc a::after { content: leader('.') target-counter(attr(href), page); }
+h1, h2, h3, h4 { break-after: avoid; }
+pre, img, table, figure, blockquote { break-inside: avoid; }
+img { max-width: 100%; }
+pre { white-space: pre-wrap; }
+";
+
+/// Writes the published pages of `section` (or the whole site) to one HTML file,
+/// with a title page and table of contents, each page starting on a new sheet.
+pub fn export(
+    input_dir: &Path,
+    config: &Config,
+    section: Option<&str>,
+    title: &str,
+    stylesheet: Option<&Path>,
+    output: &Path,
+) -> Result<()> {
+    let chapters = export::chapters(input_dir, config, section)?;
+    let css = match stylesheet {
+        Some(path) => fs::read_to_string(path).with_context(|| format!("Failed to read stylesheet: {:?}", path))?,
+        None => STYLESHEET.to_string(),
+    };
+    let base = output.parent().unwrap_or(Path::new(""));
+    let html = document(&chapters, title, config.language.as_deref().unwrap_or("en"), &css, |chapter, src| {
+        let file = export::local_file(src, &chapter.dir, input_dir)?;
+        relative_path(&file, base)
+    })?;
+    fs::write(output, html).with_context(|| format!("Failed to write document: {:?}", output))?;
+    println!("✅ Wrote {} pages to {:?}", chapters.len(), output);
+    Ok(())
+}
+
+/// Anchor of a chapter within the document, e.g. `docs-install` for `docs/install.html`.
+fn chapter_id(url: &str) -> String {
+    url.trim_end_matches(".html").replace('/', "-")
+}
+
+/// Joins the chapters into one document; `image` gives the `src` a local image should
+/// have from where the document is written.
+fn document(
+    chapters: &[Chapter],
+    title: &str,
+    language: &str,
+    css: &str,
+    image: impl Fn(&Chapter, &str) -> Option<String>,
+) -> Result<String> {
+    let ids: BTreeMap<&str, String> = chapters.iter().map(|c| (c.url.as_str(), chapter_id(&c.url))).collect();
+    let title = escape_attr(title);
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+        escape_attr(language),
+        title,
+        css
+    );
+    html.push_str(&format!("<header class=\"title-page\">\n<h1>{}</h1>\n</header>\n", title));
+    html.push_str("<nav class=\"toc\">\n<h2>Contents</h2>\n<ol>\n");
+    for chapter in chapters {
+        html.push_str(&format!("<li><a href=\"#{}\">{}</a></li>\n", ids[chapter.url.as_str()], escape_attr(&chapter.title)));
+    }
+    html.push_str("</ol>\n</nav>\n");
+
+    for chapter in chapters {
+        let body = rewrite_str(
+            &chapter.html,
+            RewriteStrSettings::new()
+                .append_element_content_handler(element!("img[src]", |el| {
+                    let src = el.get_attribute("src").unwrap_or_default();
+                    if let Some(src) = image(chapter, &src) {
+                        el.set_attribute("src", &src)?;
+                    }
+                    Ok(())
+                }))
+                .append_element_content_handler(element!("a[href]", |el| {
+                    // Links between exported pages jump within the document instead
+                    let href = el.get_attribute("href").unwrap_or_default();
+                    if let Some(id) = export::resolve_link(&href, &chapter.url).and_then(|url| ids.get(url.as_str())) {
+                        let fragment = href.find('#').map(|i| href[i..].to_string());
+                        el.set_attribute("href", &fragment.unwrap_or_else(|| format!("#{}", id)))?;
+                    }
+                    Ok(())
+                })),
+        )?;
+        html.push_str(&format!(
+            "<section class=\"chapter\" id=\"{}\">\n<h1>{}</h1>\n{}</section>\n",
+            ids[chapter.url.as_str()],
+            escape_attr(&chapter.title),
+            body
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+/// The path to `file` from the directory `base`, both taken from the working directory.
+fn relative_path(file: &Path, base: &Path) -> Option<String> {
+    let file = file.canonicalize().ok()?;
+    let base = if base.as_os_str().is_empty() { Path::new(".") } else { base };
+    let base = base.canonicalize().ok()?;
+    let common = file.components().zip(base.components()).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for component in base.components().skip(common) {
+        if let Component::Normal(_) = component {
+            relative.push("..");
+        }
+    }
+    relative.extend(file.components().skip(common));
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_document() {
+        // Arrange
+        let chapter = |title: &str, url: &str, html: &str| Chapter {
+            title: title.to_string(),
+            url: url.to_string(),
+            dir: PathBuf::from("content/docs"),
+            html: html.to_string(),
+        };
+        let chapters = vec![
+            chapter("Intro", "docs/index.html", "<p><a href=\"setup.html\">Setup</a> <img src=\"a.png\" alt=\"\"></p>\n"),
+            chapter("Setup", "docs/setup.html", ""),
+        ];
+
+        // Act
+        let html = document(&chapters, "Docs", "en", "", |_, src| Some(format!("content/docs/{}", src))).unwrap();
+
+        // Assert
+        assert!(html.contains("<li><a href=\"#docs-index\">Intro</a></li>\n<li><a href=\"#docs-setup\">Setup</a></li>"));
+        assert!(html.contains(
+            "<section class=\"chapter\" id=\"docs-index\">\n<h1>Intro</h1>\n\
+             <p><a href=\"#docs-setup\">Setup</a> <img src=\"content/docs/a.png\" alt=\"\"></p>\n</section>"
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+// Rustic - stub pages for `redirect_to` front matter, and the host rules that go with them
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::feed::{absolute_url, escape_xml};
+
+/// A page that sends visitors on to another URL.
+#[derive(Debug)]
+pub struct Redirect {
+    /// Site-relative URL of the stub, e.g. `/go/docs.html`
+    pub from: String,
+    pub to: String,
+}
+
+/// An HTML page that redirects to `target` at once and tells crawlers where the content lives.
+pub fn stub(target: &str, base_url: &str) -> String {
+    let target = if base_url.is_empty() { target.to_string() } else { absolute_url(base_url, target) };
+    let target = escape_xml(&target);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Redirecting…</title>\n\
+         <link rel=\"canonical\" href=\"{0}\">\n<meta name=\"robots\" content=\"noindex\">\n\
+         <meta http-equiv=\"refresh\" content=\"0; url={0}\">\n</head>\n\
+         <body><p>Redirecting to <a href=\"{0}\">{0}</a>.</p></body>\n</html>\n",
+        target
+    )
+}
+
+/// Writes a `_redirects` file, as read by Netlify and Cloudflare Pages, so the host
+/// redirects before the stub is even served.
+pub fn write_rules(redirects: &[Redirect], out_dir: &Path) -> Result<()> {
+    let rules: String = redirects.iter().map(|r| format!("{} {} 301\n", r.from, r.to)).collect();
+    let path = out_dir.join("_redirects");
+    fs::write(&path, rules).with_context(|| format!("Failed to write redirect rules: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stub_points_at_target() {
+        // Act
+        let absolute = stub("https://elsewhere.example/?a=1&b=2", "https://example.com");
+        let relative = stub("/new.html", "https://example.com");
+
+        // Assert
+        assert!(absolute.contains("<meta http-equiv=\"refresh\" content=\"0; url=https://elsewhere.example/?a=1&amp;b=2\">"));
+        assert!(relative.contains("<link rel=\"canonical\" href=\"https://example.com/new.html\">"));
+    }
+}
@@ -0,0 +1,89 @@
+// Rustic - `rustic render`, converting one markdown document for shell pipelines and editor previews
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use gray_matter::{engine::YAML, Matter};
+use pulldown_cmark::{html, Parser as MarkdownParser};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tera::{Context as TeraContext, Tera};
+
+use crate::config::Config;
+use crate::listing::Listing;
+use crate::{data, include, load_templates, register_template_functions, site_globals, template_error};
+
+/// Converts the markdown at `input` (`-` for stdin) and writes the HTML to stdout: the
+/// bare body, or a whole page when `template` names one of the site's templates.
+///
+/// Front matter is optional. Includes resolve from the working directory; shortcodes and
+/// wiki links need the whole site, so they are left as written.
+pub fn run(input: &Path, template: Option<&str>, config: &Config) -> Result<()> {
+    let source = if input == Path::new("-") {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).context("Failed to read markdown from stdin")?;
+        source
+    } else {
+        fs::read_to_string(input).with_context(|| format!("Failed to read markdown file: {:?}", input))?
+    };
+    let html = render(&source, input, template, config)?;
+    io::stdout().write_all(html.as_bytes()).context("Failed to write HTML to stdout")
+}
+
+fn render(source: &str, input: &Path, template: Option<&str>, config: &Config) -> Result<String> {
+    let parsed = Matter::<YAML>::new().parse(source);
+    let front_matter: Value = match parsed.data {
+        Some(data) => data.deserialize().context("Failed to read front matter")?,
+        None => Value::Object(Default::default()),
+    };
+    let markdown = include::expand(&parsed.content, input)?;
+    let mut body = String::new();
+    html::push_html(&mut body, MarkdownParser::new(&markdown));
+
+    let Some(template) = template else {
+        return Ok(body);
+    };
+    let (tera, mut context) = templates_outside_build(config)?;
+    for field in ["title", "stinger", "date", "description", "audio", "duration"] {
+        context.insert(field, &front_matter.get(field));
+    }
+    context.insert("content", &body);
+    context.insert("page", &front_matter);
+    tera.render(template, &context)
+        .map_err(|e| template_error::explain(e, &tera, template, &context))
+        .with_context(|| format!("Failed to render template {:?}", template))
+}
+
+/// Loads the site's templates with rustic's template functions, and the context every
+/// page shares, for rendering outside a build.
+///
+/// No pages are read, so `site.pages` and the menus are empty and there are no bundles.
+pub fn templates_outside_build(config: &Config) -> Result<(Tera, TeraContext)> {
+    let mut tera = load_templates(config.theme_dir.as_deref())?;
+    let data = data::load_with_shared(Path::new("data"), config.shared_data_dir.as_deref())?;
+    let globals = site_globals(config, &Listing::new(Vec::new(), &config.sections), Utc::now())?;
+    let bundles: BTreeMap<String, String> = BTreeMap::new();
+    let mut shared = TeraContext::new();
+    shared.insert("bundles", &bundles);
+    shared.insert("data", &data);
+    shared.insert("site", &globals);
+    register_template_functions(&mut tera, config, shared.clone())?;
+    shared.insert("path_prefix", ".");
+    Ok((tera, shared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_body() {
+        // Act
+        let html = render("---\ntitle: Hi\n---\n# Hello\n", Path::new("-"), None, &Config::default()).unwrap();
+
+        // Assert
+        assert_eq!(html, "<h1>Hello</h1>\n");
+    }
+}
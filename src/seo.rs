@@ -0,0 +1,169 @@
+// Rustic - search engine metadata audit for rendered pages
+
+use anyhow::Result;
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+/// Titles longer than this are usually truncated in search results.
+const MAX_TITLE_LENGTH: usize = 60;
+
+/// Metadata search engines read from a page.
+#[derive(Debug, Default)]
+pub struct PageMeta {
+    pub title: String,
+    pub description: Option<String>,
+    pub canonical: bool,
+    pub noindex: bool,
+    /// Images without both `width` and `height`, which shift the layout as they load
+    pub unsized_images: Vec<String>,
+}
+
+/// Reads a page's title, description, canonical link and image dimensions.
+pub fn scan(html: &str) -> Result<PageMeta> {
+    let meta = RefCell::new(PageMeta::default());
+    rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(text!("head > title", |chunk| {
+                meta.borrow_mut().title.push_str(chunk.as_str());
+                Ok(())
+            }))
+            .append_element_content_handler(element!("meta[name]", |el| {
+                let name = el.get_attribute("name").unwrap_or_default().to_ascii_lowercase();
+                let content = el.get_attribute("content").unwrap_or_default();
+                match name.as_str() {
+                    "description" => meta.borrow_mut().description = Some(content),
+                    "robots" => meta.borrow_mut().noindex |= is_noindex(&content),
+                    _ => {}
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("link[rel=canonical][href]", |_| {
+                meta.borrow_mut().canonical = true;
+                Ok(())
+            }))
+            .append_element_content_handler(element!("img", |el| {
+                if !el.has_attribute("width") || !el.has_attribute("height") {
+                    let src = el.get_attribute("src").unwrap_or_default();
+                    meta.borrow_mut().unsized_images.push(src);
+                }
+                Ok(())
+            })),
+    )?;
+
+    let mut meta = meta.into_inner();
+    meta.title = meta.title.trim().to_string();
+    Ok(meta)
+}
+
+/// Whether robots directives such as "noindex, nofollow" ask crawlers not to index a page.
+pub fn is_noindex(directives: &str) -> bool {
+    directives
+        .split(',')
+        .any(|directive| matches!(directive.trim().to_ascii_lowercase().as_str(), "noindex" | "none"))
+}
+
+/// Lists problems per page. `sitemap` holds the pages listed in `sitemap.xml`, when there is one.
+pub fn audit(
+    pages: &BTreeMap<String, PageMeta>,
+    sitemap: Option<&[String]>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut titles: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut descriptions: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (page, meta) in pages {
+        titles.entry(meta.title.as_str()).or_default().push(page);
+        if let Some(description) = &meta.description {
+            descriptions.entry(description.as_str()).or_default().push(page);
+        }
+    }
+
+    let mut findings: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (page, meta) in pages {
+        let problems = findings.entry(page.clone()).or_default();
+        let others = |shared: &[&str]| {
+            shared.iter().filter(|p| **p != page).copied().collect::<Vec<_>>().join(", ")
+        };
+
+        if meta.title.is_empty() {
+            problems.push("Missing <title>".to_string());
+        } else {
+            if meta.title.chars().count() > MAX_TITLE_LENGTH {
+                problems.push(format!(
+                    "Title is {} characters; search results show about {}",
+                    meta.title.chars().count(),
+                    MAX_TITLE_LENGTH
+                ));
+            }
+            if titles[meta.title.as_str()].len() > 1 {
+                problems.push(format!("Title also used by {}", others(&titles[meta.title.as_str()])));
+            }
+        }
+        match meta.description.as_deref() {
+            None | Some("") => problems.push("Missing meta description".to_string()),
+            Some(description) if descriptions[description].len() > 1 => {
+                problems.push(format!("Description also used by {}", others(&descriptions[description])));
+            }
+            Some(_) => {}
+        }
+        if !meta.canonical {
+            problems.push("Missing canonical URL".to_string());
+        }
+        let unlisted = sitemap.is_some_and(|listed| !listed.iter().any(|p| p == page));
+        if unlisted && !meta.noindex {
+            problems.push("Not listed in sitemap.xml".to_string());
+        }
+        for src in &meta.unsized_images {
+            problems.push(format!("Image without width and height: {}", src));
+        }
+    }
+    findings
+}
+
+/// Reads the pages listed in a sitemap, as paths relative to the site root.
+pub fn sitemap_pages(xml: &str) -> Vec<String> {
+    xml.split("<loc>")
+        .skip(1)
+        .filter_map(|rest| rest.split("</loc>").next())
+        .map(|url| {
+            // Drop the scheme and host, keeping the path
+            let path = url.trim().splitn(4, '/').nth(3).unwrap_or_default();
+            let path = path.split(['?', '#']).next().unwrap_or_default();
+            if path.is_empty() || path.ends_with('/') {
+                format!("{}index.html", path)
+            } else {
+                path.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit() {
+        // Arrange
+        let html = r#"<html><head><title>Same</title><meta name="description" content="A page"></head>
+            <body><img src="a.png" width="10"></body></html>"#;
+        let mut pages = BTreeMap::new();
+        pages.insert("a.html".to_string(), scan(html).unwrap());
+        pages.insert("b.html".to_string(), scan("<html><head><title>Same</title></head></html>").unwrap());
+        let sitemap = sitemap_pages("<url><loc>https://example.com/a.html</loc></url><url><loc>https://example.com/</loc></url>");
+
+        // Act
+        let findings = audit(&pages, Some(&sitemap));
+
+        // Assert
+        assert_eq!(sitemap, ["a.html", "index.html"]);
+        assert_eq!(
+            findings["a.html"],
+            ["Title also used by b.html", "Missing canonical URL", "Image without width and height: a.png"]
+        );
+        assert_eq!(
+            findings["b.html"],
+            ["Title also used by a.html", "Missing meta description", "Missing canonical URL", "Not listed in sitemap.xml"]
+        );
+    }
+}
@@ -0,0 +1,636 @@
+// Rustic - `rustic serve`, a local preview server that rebuilds and reloads on changes
+
+use anyhow::{bail, Context, Result};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, UdpSocket};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use walkdir::WalkDir;
+
+use crate::config::{Config, Override};
+use crate::{build, include, standalone, Cli};
+
+/// How many ports after `--port` are tried when it is busy.
+const PORT_ATTEMPTS: u16 = 10;
+
+/// How often the sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The path pages poll for the build number, which changes after every rebuild.
+const RELOAD_PATH: &str = "/__rustic/reload";
+
+/// Source extensions whose changes only restyle pages.
+const STYLESHEETS: [&str; 4] = ["css", "less", "sass", "scss"];
+
+/// Added to every HTML page served, so open pages reload after a rebuild. After a rebuild
+/// that only changed stylesheets, the page's `<link>` and `<style>` elements are swapped
+/// for the rebuilt page's instead, keeping scroll position and other state.
+const RELOAD_SCRIPT: &str = "<script>(function () {\n\
+  var build = null;\n\
+  function swapStyles(current) {\n\
+    fetch(location.href).then(function (r) { return r.text(); }).then(function (html) {\n\
+      var selector = 'link[rel=stylesheet], style';\n\
+      var fresh = new DOMParser().parseFromString(html, 'text/html').querySelectorAll(selector);\n\
+      var old = document.querySelectorAll(selector);\n\
+      if (fresh.length !== old.length) return location.reload();\n\
+      fresh.forEach(function (element, i) {\n\
+        var node = document.importNode(element, true);\n\
+        if (node.href) node.href += (node.href.indexOf('?') < 0 ? '?' : '&') + '__rustic=' + current;\n\
+        old[i].replaceWith(node);\n\
+      });\n\
+    }).catch(function () { location.reload(); });\n\
+  }\n\
+  setInterval(function () {\n\
+    fetch('/__rustic/reload').then(function (r) { return r.text(); }).then(function (status) {\n\
+      var current = status.split(' ');\n\
+      if (build !== null && current[0] !== build) {\n\
+        if (current[1] === 'css' && Number(current[0]) === Number(build) + 1) swapStyles(current[0]);\n\
+        else location.reload();\n\
+      }\n\
+      build = current[0];\n\
+    }).catch(function () {});\n\
+  }, 1000);\n\
+})();</script>\n";
+
+/// Builds the site, serves the output directory on `host` and rebuilds whenever a file
+/// the build reads changes.
+///
+/// The first free port from `port` on is used. Pages poll the server and reload after
+/// each successful rebuild; a failed rebuild is reported and the last good build stays up.
+/// With `preview`, drafts and future-dated pages are built too. With `tls`, a certificate
+/// chain and key in PEM files, the site is served over HTTPS instead of HTTP.
+pub fn run(cli: &Cli, host: IpAddr, port: u16, open: bool, preview: bool, tls: Option<(&Path, &Path)>) -> Result<()> {
+    let tls = tls.map(|(cert, key)| tls_config(cert, key)).transpose()?;
+    let cli = &preview_cli(cli, preview)?;
+    build(cli)?;
+    let listener = bind(host, port)?;
+    let port = listener.local_addr()?.port();
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let url = format!("{}://{}:{}/", scheme, if host.is_unspecified() { "localhost".to_string() } else { host.to_string() }, port);
+    println!("✅ Serving {:?} at {}", cli.out_dir, url);
+    if host.is_unspecified() {
+        if let Some(ip) = lan_address() {
+            println!("   On your network: {}://{}:{}/", scheme, ip, port);
+        }
+    } else if host.is_loopback() {
+        println!("   Use --host 0.0.0.0 to reach it from other devices on your network");
+    }
+    if open {
+        open_browser(&url);
+    }
+
+    let builds = Builds::default();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for stream in listener.incoming().filter_map(|s| s.ok()) {
+                let (out_dir, builds, tls) = (&cli.out_dir, &builds, &tls);
+                scope.spawn(move || {
+                    let build = builds.status();
+                    let answered = match tls {
+                        Some(config) => ServerConnection::new(Arc::clone(config)).map_err(anyhow::Error::from).and_then(|conn| {
+                            let mut stream = StreamOwned::new(conn, stream);
+                            respond(&mut stream, out_dir, &build)?;
+                            stream.conn.send_close_notify();
+                            Ok(stream.flush()?)
+                        }),
+                        None => respond(&mut &stream, out_dir, &build),
+                    };
+                    if let Err(err) = answered {
+                        eprintln!("⚠️  Failed to answer a request: {}", err);
+                    }
+                });
+            }
+        });
+        watch(cli, &builds)
+    })
+}
+
+/// Loads the certificate chain and private key for serving over HTTPS.
+fn tls_config(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read the TLS certificate {:?}", cert))?;
+    if certs.is_empty() {
+        bail!("No certificates found in {:?}", cert);
+    }
+    let key = PrivateKeyDer::from_pem_file(key).with_context(|| format!("Failed to read the TLS key {:?}", key))?;
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("The TLS certificate and key don't make a usable pair")?;
+    Ok(Arc::new(config))
+}
+
+/// The build `rustic serve` runs: `cli` with `drafts` and `future` switched on for a
+/// preview. They go before the `--set` overrides, so those still win.
+fn preview_cli(cli: &Cli, preview: bool) -> Result<Cli> {
+    let mut overrides = Vec::new();
+    if preview {
+        for setting in ["drafts=true", "future=true"] {
+            overrides.push(setting.parse::<Override>().map_err(anyhow::Error::msg)?);
+        }
+    }
+    overrides.extend(cli.overrides.iter().cloned());
+    Ok(Cli {
+        command: None,
+        input_dir: cli.input_dir.clone(),
+        out_dir: cli.out_dir.clone(),
+        config: cli.config.clone(),
+        profile: cli.profile.clone(),
+        overrides,
+        timings: cli.timings,
+        unused_templates: cli.unused_templates,
+        archive: cli.archive.clone(),
+        dump_context: cli.dump_context.clone(),
+        dry_run: false,
+    })
+}
+
+/// Binds the first free port from `port` on.
+fn bind(host: IpAddr, port: u16) -> Result<TcpListener> {
+    for candidate in port..port.saturating_add(PORT_ATTEMPTS) {
+        match TcpListener::bind((host, candidate)) {
+            Ok(listener) => return Ok(listener),
+            Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+                println!("Port {} is in use, trying {}", candidate, candidate + 1);
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to listen on {}:{}", host, candidate)),
+        }
+    }
+    bail!("Ports {} to {} are all in use; pick another with --port", port, port.saturating_add(PORT_ATTEMPTS - 1))
+}
+
+/// The address other devices on the network reach this machine at. Connecting a UDP
+/// socket picks the outgoing interface without sending anything.
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 168, 0, 1), 80)).ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+}
+
+fn open_browser(url: &str) {
+    let opened = if cfg!(target_os = "macos") {
+        Command::new("open").arg(url).spawn()
+    } else if cfg!(windows) {
+        Command::new("cmd").args(["/C", "start", "", url]).spawn()
+    } else {
+        Command::new("xdg-open").arg(url).spawn()
+    };
+    if let Err(err) = opened {
+        eprintln!("⚠️  Couldn't open a browser at {}: {}", url, err);
+    }
+}
+
+/// The good builds so far, as the reload script sees them.
+#[derive(Default)]
+struct Builds {
+    count: AtomicUsize,
+    /// Whether the latest build only changed stylesheets, so pages can swap them in place
+    styles_only: AtomicBool,
+}
+
+impl Builds {
+    fn record(&self, styles_only: bool) {
+        self.styles_only.store(styles_only, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// What `RELOAD_PATH` answers: the build number, followed by ` css` when that build
+    /// only changed stylesheets.
+    fn status(&self) -> String {
+        let count = self.count.load(Ordering::Relaxed);
+        if count > 0 && self.styles_only.load(Ordering::Relaxed) {
+            format!("{} css", count)
+        } else {
+            count.to_string()
+        }
+    }
+}
+
+/// Polls the sources and rebuilds when their contents change, counting good builds in `builds`.
+///
+/// File sizes and modification times are checked on every poll; only when those move are
+/// the contents hashed, so saving a file without editing it doesn't rebuild. A burst of
+/// saves, as from a formatter or a branch switch, is waited out and rebuilt once. The watched
+/// files are worked out again after each rebuild, since the config or includes may have changed.
+fn watch(cli: &Cli, builds: &Builds) -> Result<()> {
+    let mut watched = Watched::new(cli);
+    let mut last_stamp = stamp(&watched.files());
+    let mut last_contents = contents(&watched.files())?;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut files = watched.files();
+        let mut current = stamp(&files);
+        if current == last_stamp {
+            continue;
+        }
+        // Debounce: wait until a whole poll interval passes without further changes
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            files = watched.files();
+            let settled = stamp(&files);
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+        last_stamp = current;
+        let current = contents(&files)?;
+        let changed = changed_files(&last_contents, &current);
+        if changed.is_empty() {
+            continue;
+        }
+        last_contents = current;
+        println!("\nChange detected, rebuilding");
+        match build(cli) {
+            Ok(()) => builds.record(changed.iter().all(|path| is_stylesheet(path))),
+            Err(err) => eprintln!("⚠️  Rebuild failed, still serving the last build: {:#}", err),
+        }
+        let rewatched = Watched::new(cli);
+        if rewatched != watched {
+            watched = rewatched;
+            last_stamp = stamp(&watched.files());
+            last_contents = contents(&watched.files())?;
+        }
+    }
+}
+
+/// The files and directories a build reads, and the pipeline outputs among them.
+#[derive(Debug, PartialEq)]
+struct Watched {
+    roots: Vec<PathBuf>,
+    /// Written by pipeline commands during the build, so never watched
+    outputs: Vec<PathBuf>,
+}
+
+impl Watched {
+    /// The content directory and the site's conventional directories, plus what the
+    /// config and the pages' include directives name.
+    fn new(cli: &Cli) -> Self {
+        let mut roots: Vec<PathBuf> = vec![
+            cli.input_dir.clone(),
+            "templates".into(),
+            "static".into(),
+            "data".into(),
+            "i18n".into(),
+            cli.config.clone(),
+        ];
+        let mut outputs = Vec::new();
+        // A config that doesn't load is reported by the rebuild; its own file stays watched
+        if let Ok(config) = Config::load(&cli.config, cli.profile.as_deref(), &cli.overrides) {
+            roots.extend(config.theme_dir.iter().chain(&config.shared_data_dir).cloned());
+            roots.extend(config.critical_css.iter().chain(&config.favicon).cloned());
+            if let Some(cards) = &config.social_cards {
+                roots.extend(std::iter::once(&cards.font).chain(&cards.background).cloned());
+            }
+            // Bundlers follow imports, which usually sit next to the entry point
+            for bundle in &config.bundle {
+                let dir = bundle.entry.parent().filter(|dir| !dir.as_os_str().is_empty());
+                roots.push(dir.unwrap_or(&bundle.entry).to_path_buf());
+            }
+            for step in &config.pipeline {
+                roots.extend(step.inputs.iter().cloned());
+                outputs.extend(step.outputs.iter().cloned());
+            }
+        }
+        for entry in WalkDir::new(&cli.input_dir).into_iter().filter_map(|e| e.ok()) {
+            if entry.path().extension().and_then(|s| s.to_str()) == Some("md")
+                && let Ok(markdown) = fs::read_to_string(entry.path())
+            {
+                roots.extend(include::files(&markdown));
+            }
+        }
+        roots.sort();
+        roots.dedup();
+        Watched { roots, outputs }
+    }
+
+    /// Every file under the roots, in a stable order, leaving out pipeline outputs.
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .roots
+            .iter()
+            .flat_map(|root| WalkDir::new(root).sort_by_file_name().into_iter().filter_map(|e| e.ok()))
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| !self.outputs.iter().any(|output| path.starts_with(output)))
+            .collect();
+        // Roots may overlap, e.g. `critical_css` under `static/`
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// A cheap fingerprint of the files' names, sizes and modification times.
+fn stamp(files: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for path in files {
+        path.hash(&mut hasher);
+        if let Ok(metadata) = fs::metadata(path) {
+            metadata.len().hash(&mut hasher);
+            metadata.modified().ok().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// The SHA-256 of each file's contents.
+fn contents(files: &[PathBuf]) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut hashes = BTreeMap::new();
+    for path in files {
+        let mut hasher = Sha256::new();
+        // A file removed since it was listed counts as empty
+        if let Ok(mut file) = fs::File::open(path) {
+            io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {:?}", path))?;
+        }
+        hashes.insert(path.clone(), hasher.finalize().to_vec());
+    }
+    Ok(hashes)
+}
+
+/// The files added, removed or edited between two `contents` snapshots.
+fn changed_files(before: &BTreeMap<PathBuf, Vec<u8>>, after: &BTreeMap<PathBuf, Vec<u8>>) -> Vec<PathBuf> {
+    let edited = after.iter().filter(|(path, hash)| before.get(*path) != Some(*hash)).map(|(path, _)| path);
+    let removed = before.keys().filter(|path| !after.contains_key(*path));
+    edited.chain(removed).cloned().collect()
+}
+
+fn is_stylesheet(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| STYLESHEETS.contains(&ext))
+}
+
+/// Answers one request on `stream`, plain or TLS, with a file from `out_dir`, or the build
+/// status at `RELOAD_PATH`.
+fn respond(stream: &mut (impl Read + Write), out_dir: &Path, build: &str) -> Result<()> {
+    let mut request_line = String::new();
+    {
+        let mut reader = BufReader::new(&mut *stream);
+        reader.read_line(&mut request_line)?;
+        // The headers aren't needed, but are read so the client sees a clean close
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or("/"));
+    if method != "GET" && method != "HEAD" {
+        return send(stream, "405 Method Not Allowed", "text/plain; charset=utf-8", b"Method not allowed\n", method);
+    }
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    if path == RELOAD_PATH {
+        return send(stream, "200 OK", "text/plain; charset=utf-8", build.as_bytes(), method);
+    }
+    let (status, file) = match resolve(out_dir, path) {
+        Resolved::File(file) => ("200 OK", file),
+        Resolved::Directory => return redirect(stream, &format!("{}/", path)),
+        // The site's own 404 page, as static hosts serve it
+        Resolved::NotFound if out_dir.join("404.html").is_file() => ("404 Not Found", out_dir.join("404.html")),
+        Resolved::NotFound => {
+            return send(stream, "404 Not Found", "text/plain; charset=utf-8", b"Not found\n", method);
+        }
+    };
+    let body = fs::read(&file).with_context(|| format!("Failed to read {:?}", file))?;
+    let content_type = content_type(&file);
+    if content_type.starts_with("text/html") {
+        return send(stream, status, content_type, &with_reload_script(&body), method);
+    }
+    send(stream, status, content_type, &body, method)
+}
+
+fn redirect(stream: &mut impl Write, location: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        location
+    )?;
+    Ok(stream.flush()?)
+}
+
+fn send(stream: &mut impl Write, status: &str, content_type: &str, body: &[u8], method: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(body)?;
+    }
+    Ok(stream.flush()?)
+}
+
+/// What a URL path names in the output directory.
+#[derive(Debug, PartialEq)]
+enum Resolved {
+    File(PathBuf),
+    /// A directory asked for without its trailing slash
+    Directory,
+    NotFound,
+}
+
+/// Resolves a URL path the way common static hosts do: `/a/b.html` is the file itself,
+/// `/a/` is `a/index.html`, `/a` is `a.html`, or redirects to `/a/` when only `a/index.html`
+/// exists. Paths that would leave `out_dir` are not found.
+fn resolve(out_dir: &Path, path: &str) -> Resolved {
+    let relative = PathBuf::from(percent_decode(path.trim_start_matches('/')));
+    if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Resolved::NotFound;
+    }
+    let file = out_dir.join(&relative);
+    if file.is_file() {
+        return Resolved::File(file);
+    }
+    if path.ends_with('/') {
+        let index = file.join("index.html");
+        return if index.is_file() { Resolved::File(index) } else { Resolved::NotFound };
+    }
+    let page = file.with_extension("html");
+    if relative.extension().is_none() && page.is_file() {
+        Resolved::File(page)
+    } else if file.join("index.html").is_file() {
+        Resolved::Directory
+    } else {
+        Resolved::NotFound
+    }
+}
+
+/// Decodes `%20`-style escapes in a URL path.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Adds `RELOAD_SCRIPT` before `</body>`, or at the end of pages without one.
+fn with_reload_script(html: &[u8]) -> Vec<u8> {
+    let html = String::from_utf8_lossy(html);
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{}{}", &html[..end], RELOAD_SCRIPT, &html[end..]).into_bytes(),
+        None => format!("{}{}", html, RELOAD_SCRIPT).into_bytes(),
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain; charset=utf-8",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => standalone::media_type(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        // Arrange
+        let out_dir = std::env::temp_dir().join(format!("rustic-serve-{}", std::process::id()));
+        fs::create_dir_all(out_dir.join("blog")).unwrap();
+        fs::write(out_dir.join("index.html"), "home").unwrap();
+        fs::write(out_dir.join("blog/index.html"), "blog").unwrap();
+        fs::write(out_dir.join("blog/first post.html"), "post").unwrap();
+        fs::write(out_dir.join("about.html"), "about").unwrap();
+
+        // Act & Assert
+        assert_eq!(resolve(&out_dir, "/"), Resolved::File(out_dir.join("index.html")));
+        assert_eq!(resolve(&out_dir, "/blog/"), Resolved::File(out_dir.join("blog/index.html")));
+        assert_eq!(resolve(&out_dir, "/blog"), Resolved::Directory);
+        assert_eq!(resolve(&out_dir, "/blog/first%20post.html"), Resolved::File(out_dir.join("blog/first post.html")));
+        assert_eq!(resolve(&out_dir, "/about"), Resolved::File(out_dir.join("about.html")));
+        assert_eq!(resolve(&out_dir, "/about/"), Resolved::NotFound);
+        assert_eq!(resolve(&out_dir, "/missing.html"), Resolved::NotFound);
+        assert_eq!(resolve(&out_dir, "/../secret"), Resolved::NotFound);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_watched_files_leave_out_pipeline_outputs() {
+        // Arrange
+        let root = std::env::temp_dir().join(format!("rustic-serve-watch-{}", std::process::id()));
+        fs::create_dir_all(root.join("static")).unwrap();
+        fs::create_dir_all(root.join("styles")).unwrap();
+        fs::write(root.join("static/app.css"), "built").unwrap();
+        fs::write(root.join("static/logo.svg"), "<svg/>").unwrap();
+        fs::write(root.join("styles/app.css"), "source").unwrap();
+        let watched = Watched {
+            roots: vec![root.join("static"), root.join("styles"), root.join("static/logo.svg")],
+            outputs: vec![root.join("static/app.css")],
+        };
+
+        // Act
+        let files = watched.files();
+
+        // Assert
+        assert_eq!(files, [root.join("static/logo.svg"), root.join("styles/app.css")]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_preview_cli_publishes_drafts_unless_set_otherwise() {
+        // Arrange
+        use clap::Parser;
+        let path = std::env::temp_dir().join(format!("rustic-serve-preview-{}.toml", std::process::id()));
+        fs::write(&path, "title = \"Preview\"\n").unwrap();
+        let config = path.to_str().unwrap();
+        let load = |cli: &Cli| Config::load(&cli.config, None, &cli.overrides).unwrap();
+
+        // Act
+        let preview = load(&preview_cli(&Cli::parse_from(["rustic", "--config", config]), true).unwrap());
+        let production = load(&preview_cli(&Cli::parse_from(["rustic", "--config", config]), false).unwrap());
+        let pinned = load(&preview_cli(&Cli::parse_from(["rustic", "--config", config, "--set", "drafts=false"]), true).unwrap());
+
+        // Assert
+        assert!(preview.drafts && preview.future);
+        assert!(!production.drafts && !production.future);
+        assert!(!pinned.drafts && pinned.future);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stylesheet_only_rebuilds() {
+        // Arrange
+        let snapshot = |files: &[(&str, &str)]| -> BTreeMap<PathBuf, Vec<u8>> {
+            files.iter().map(|(path, hash)| (PathBuf::from(path), hash.as_bytes().to_vec())).collect()
+        };
+        let before = snapshot(&[("static/site.css", "a"), ("styles/app.scss", "b"), ("content/post.md", "c")]);
+        let restyled = snapshot(&[("static/site.css", "A"), ("styles/app.scss", "B"), ("content/post.md", "c")]);
+        let rewritten = snapshot(&[("static/site.css", "A"), ("styles/app.scss", "b")]);
+        let builds = Builds::default();
+
+        // Act
+        let styles = changed_files(&before, &restyled);
+        let content = changed_files(&before, &rewritten);
+        builds.record(styles.iter().all(|path| is_stylesheet(path)));
+        let after_styles = builds.status();
+        builds.record(content.iter().all(|path| is_stylesheet(path)));
+
+        // Assert
+        assert_eq!(styles, [PathBuf::from("static/site.css"), PathBuf::from("styles/app.scss")]);
+        assert_eq!(content, [PathBuf::from("static/site.css"), PathBuf::from("content/post.md")]);
+        assert_eq!(after_styles, "1 css");
+        assert_eq!(builds.status(), "2");
+    }
+
+    #[test]
+    fn test_tls_config_needs_a_certificate() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("rustic-serve-tls-{}.pem", std::process::id()));
+        fs::write(&path, "not a certificate\n").unwrap();
+
+        // Act
+        let err = tls_config(&path, &path).unwrap_err();
+
+        // Assert
+        assert!(err.to_string().starts_with("No certificates found in"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_with_reload_script() {
+        // Act
+        let html = with_reload_script(b"<html><body><p>Hi</p></body></html>");
+
+        // Assert
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.starts_with("<html><body><p>Hi</p><script>"));
+        assert!(html.ends_with("</script>\n</body></html>"));
+    }
+}
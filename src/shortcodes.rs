@@ -0,0 +1,232 @@
+// Rustic - `{{ name(arg="value") }}` shortcodes expanded in markdown before conversion
+
+use anyhow::{anyhow, Context, Result};
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::sync::LazyLock;
+use tera::Context as TeraContext;
+
+use crate::{chart, embeds, gallery, table, wikilinks, Site};
+
+/// Matches a shortcode call such as `{{ gallery(path="trip-2024", columns=3) }}`.
+static SHORTCODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\(((?:[^()"]|"(?:[^"\\]|\\.)*")*)\)\s*\}\}"#)
+        .unwrap()
+});
+
+/// Matches an escaped call such as `{{/* gallery(path="trip-2024") */}}`, published as written.
+static ESCAPED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{/\*(.*?)\*/\}\}").unwrap());
+
+/// Matches one `key=value` argument inside a shortcode call.
+static ARGUMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*([A-Za-z_][A-Za-z0-9_]*)\s*=\s*("(?:[^"\\]|\\.)*"|[^,\s]+)\s*(?:,|$)"#)
+        .unwrap()
+});
+
+/// Arguments passed to a shortcode, keyed by name.
+pub type Args = Map<String, Value>;
+
+/// Replaces every shortcode call in `markdown` with its rendered HTML.
+///
+/// Built-in shortcodes are implemented in Rust; any other name is rendered from
+/// `templates/shortcodes/<name>.html` with the call's arguments as context. With
+/// `templated`, the body goes through Tera next, so calls to names that are neither are
+/// left for it as function calls, e.g. `{{ get_env(name="CI_COMMIT_SHA") }}`.
+pub fn expand(markdown: &str, page: &Path, site: &Site, templated: bool) -> Result<String> {
+    replace_calls(markdown, templated, |name, raw_args| {
+        if templated && !is_built_in(name) && !has_template(name, site) {
+            return Ok(None);
+        }
+        render(name, raw_args, site).map(Some)
+    })
+    .with_context(|| format!("Failed to expand shortcodes in {:?}", page))
+}
+
+/// Replaces shortcode calls outside fenced code with what `render` returns for each name
+/// and argument list, leaving calls it returns `None` for as written. Escaped calls then
+/// lose their `/* */`, so documentation can show a shortcode without running it.
+///
+/// With `templated`, the result goes through Tera next, so escaped calls and fenced code
+/// that looks like Tera are wrapped in `{% raw %}` to reach the page as written.
+fn replace_calls(
+    markdown: &str,
+    templated: bool,
+    mut render: impl FnMut(&str, &str) -> Result<Option<String>>,
+) -> Result<String> {
+    let unescaped = if templated { "{% raw %}{{${1}}}{% endraw %}" } else { "{{${1}}}" };
+    let mut error = None;
+    let mut out = String::with_capacity(markdown.len());
+    for (prose, run) in wikilinks::prose_runs(markdown) {
+        if !prose {
+            if templated && ["{{", "{%", "{#"].iter().any(|tag| run.contains(tag)) {
+                out.push_str(&format!("{{% raw %}}{}{{% endraw %}}", run));
+            } else {
+                out.push_str(run);
+            }
+            continue;
+        }
+        let expanded = SHORTCODE.replace_all(run, |caps: &Captures| match render(&caps[1], &caps[2]) {
+            // Blank lines would end the surrounding HTML block in markdown
+            Ok(Some(html)) => html.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>().join("\n"),
+            Ok(None) => caps[0].to_string(),
+            Err(err) => {
+                error.get_or_insert(err);
+                String::new()
+            }
+        });
+        out.push_str(&ESCAPED.replace_all(&expanded, unescaped));
+    }
+
+    match error {
+        Some(err) => Err(err),
+        None => Ok(out),
+    }
+}
+
+/// Names of the shortcodes `markdown` calls outside fenced code, in order, repeats included.
+pub fn calls(markdown: &str) -> impl Iterator<Item = &str> {
+    wikilinks::prose_runs(markdown)
+        .into_iter()
+        .filter(|(prose, _)| *prose)
+        .flat_map(|(_, run)| SHORTCODE.captures_iter(run).map(|caps| caps.get(1).unwrap().as_str()))
+}
+
+/// Shortcodes implemented in Rust, which need no template.
+pub fn is_built_in(name: &str) -> bool {
+    matches!(name, "gallery" | "youtube" | "vimeo" | "mastodon" | "table" | "chart")
+}
+
+/// Renders a single shortcode call.
+fn render(name: &str, raw_args: &str, site: &Site) -> Result<String> {
+    let args = parse_args(raw_args).with_context(|| format!("Invalid arguments to `{}`", name))?;
+
+    match name {
+        "gallery" => gallery::render(&args, site),
+        "youtube" | "vimeo" | "mastodon" => embeds::render(name, &args, site),
+        "chart" => chart::render(&args, site),
+        "table" => table::render(&args, site),
+        _ => {
+            let context = TeraContext::from_serialize(&args)?;
+            render_template(name, &context, site)?
+                .ok_or_else(|| anyhow!("Unknown shortcode `{}`", name))
+        }
+    }
+}
+
+fn has_template(name: &str, site: &Site) -> bool {
+    let template = format!("shortcodes/{}.html", name);
+    site.tera.get_template_names().any(|t| t == template)
+}
+
+/// Renders `templates/shortcodes/<name>.html` if the site provides one.
+///
+/// Built-in shortcodes use this so themes can replace their default markup.
+pub fn render_template(name: &str, context: &TeraContext, site: &Site) -> Result<Option<String>> {
+    if !has_template(name, site) {
+        return Ok(None);
+    }
+    let template = format!("shortcodes/{}.html", name);
+
+    let mut context = context.clone();
+    context.insert("site", &site.globals);
+    site.render(&template, &context)
+        .map(Some)
+        .with_context(|| format!("Failed to render shortcode template {}", template))
+}
+
+/// Parses `key="string", n=3, flag=true` into a map of JSON values.
+pub fn parse_args(raw: &str) -> Result<Args> {
+    let mut args = Map::new();
+    let mut rest = raw.trim();
+
+    while !rest.is_empty() {
+        let caps = ARGUMENT
+            .captures(rest)
+            .ok_or_else(|| anyhow!("could not parse `{}`", rest))?;
+        let raw_value = &caps[2];
+        let value = if raw_value.starts_with('"') {
+            serde_json::from_str(raw_value)?
+        } else {
+            match raw_value {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                number => serde_json::from_str(number)
+                    .map_err(|_| anyhow!("`{}` is not a string, number or boolean", number))?,
+            }
+        };
+        args.insert(caps[1].to_string(), value);
+        rest = &rest[caps[0].len()..];
+    }
+
+    Ok(args)
+}
+
+/// Reads a required string argument.
+pub fn string_arg<'a>(args: &'a Args, key: &str) -> Result<&'a str> {
+    args.get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("missing string argument `{}`", key))
+}
+
+/// Escapes a value for use inside a double-quoted HTML attribute.
+pub fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        // Act
+        let args = parse_args(r#"path="trip \"2024\"", columns=3, lightbox=true"#).unwrap();
+
+        // Assert
+        assert_eq!(args["path"], "trip \"2024\"");
+        assert_eq!(args["columns"], 3);
+        assert_eq!(args["lightbox"], true);
+    }
+
+    #[test]
+    fn test_fenced_and_escaped_shortcodes_are_left_alone() {
+        // Arrange
+        let markdown = "{{ gallery(path=\"trip-2024\") }}\n\n```md\n{{ gallery(path=\"missing\") }}\n```\n\nWrite {{/* gallery(path=\"x\") */}} to show one.\n";
+
+        // Act
+        let expanded = replace_calls(markdown, false, |name, args| match args {
+            r#"path="trip-2024""# => Ok(Some(format!("<div class=\"{}\"></div>", name))),
+            _ => anyhow::bail!("Gallery folder not found"),
+        });
+
+        // Assert
+        assert_eq!(
+            expanded.unwrap(),
+            "<div class=\"gallery\"></div>\n\n```md\n{{ gallery(path=\"missing\") }}\n```\n\nWrite {{ gallery(path=\"x\") }} to show one.\n"
+        );
+        assert_eq!(calls(markdown).collect::<Vec<_>>(), ["gallery"]);
+    }
+
+    #[test]
+    fn test_escaped_shortcodes_survive_render_templates() {
+        // Arrange
+        let markdown = "Built at {{ now }}. Write {{/* gallery(path=\"x\") */}} to show one.\n\n```jinja\n{% if page %}{{ page.title }}{% endif %}\n```\n";
+        let mut context = TeraContext::new();
+        context.insert("now", "noon");
+
+        // Act
+        let expanded = replace_calls(markdown, true, |_, _| Ok(None)).unwrap();
+        let rendered = tera::Tera::one_off(&expanded, &context, false).unwrap();
+
+        // Assert
+        assert_eq!(
+            rendered,
+            "Built at noon. Write {{ gallery(path=\"x\") }} to show one.\n\n```jinja\n{% if page %}{{ page.title }}{% endif %}\n```\n"
+        );
+    }
+}
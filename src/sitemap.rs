@@ -0,0 +1,195 @@
+// Rustic - sitemap.xml listing every published page for search engines, one per language
+// behind a sitemap index on multilingual sites
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::dates::parse_date;
+use crate::feed::{absolute_url, escape_xml};
+use crate::i18n::Languages;
+use crate::listing::{Listing, PageListing};
+
+/// A page's `sitemap:` front matter: `false` to leave it out, or hints for crawlers.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PageSitemap {
+    Listed(bool),
+    Hints(SitemapHints),
+}
+
+/// How important a page is relative to the rest of the site, and how often it changes.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SitemapHints {
+    /// From 0.0 to 1.0; crawlers assume 0.5 when it is missing
+    pub priority: Option<f32>,
+    pub changefreq: Option<ChangeFreq>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeFreq {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl ChangeFreq {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+            Self::Yearly => "yearly",
+            Self::Never => "never",
+        }
+    }
+}
+
+/// Renders the sitemap of `pages`, skipping those that opt out with `sitemap: false`.
+///
+/// Pages are dated by `updated` when git dates are on, and otherwise by `date`.
+/// `alternates` gives each page's translations as language codes and URLs, listed as
+/// `hreflang` links when there are any.
+pub fn render<'a>(
+    pages: impl IntoIterator<Item = &'a PageListing>,
+    base_url: &str,
+    alternates: impl Fn(&PageListing) -> Vec<(String, String)>,
+) -> Result<String> {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n");
+    for page in pages {
+        let hints = match &page.sitemap {
+            Some(PageSitemap::Listed(false)) => continue,
+            Some(PageSitemap::Hints(hints)) => hints.clone(),
+            Some(PageSitemap::Listed(true)) | None => SitemapHints::default(),
+        };
+
+        xml.push_str("<url>\n");
+        xml.push_str(&format!("<loc>{}</loc>\n", escape_xml(&absolute_url(base_url, &page.url))));
+        let lastmod = page.updated.clone().or_else(|| {
+            let date = page.date.as_deref().and_then(parse_date)?;
+            Some(date.format("%Y-%m-%d").to_string())
+        });
+        if let Some(lastmod) = lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>\n", lastmod));
+        }
+        if let Some(changefreq) = hints.changefreq {
+            xml.push_str(&format!("<changefreq>{}</changefreq>\n", changefreq.as_str()));
+        }
+        if let Some(priority) = hints.priority {
+            if !(0.0..=1.0).contains(&priority) {
+                bail!("Sitemap priority of {} must be between 0.0 and 1.0, not {}", page.url, priority);
+            }
+            xml.push_str(&format!("<priority>{}</priority>\n", priority));
+        }
+        for (lang, url) in alternates(page) {
+            xml.push_str(&format!(
+                "<xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\"/>\n",
+                escape_xml(&lang),
+                escape_xml(&absolute_url(base_url, &url))
+            ));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    Ok(xml)
+}
+
+/// Renders a sitemap index pointing at each of the site-relative `sitemaps`.
+pub fn render_index(sitemaps: &[String], base_url: &str) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for sitemap in sitemaps {
+        xml.push_str(&format!("<sitemap>\n<loc>{}</loc>\n</sitemap>\n", escape_xml(&absolute_url(base_url, sitemap))));
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+/// Writes `sitemap.xml` to the root of the output directory.
+///
+/// A site in several languages gets a `sitemap-<code>.xml` for each, whose pages link to
+/// their translations, and `sitemap.xml` indexes them. They all sit at the root, as a
+/// sitemap may only list pages below its own directory.
+pub fn write(listing: &Listing, languages: &Languages, base_url: &str, out_dir: &Path) -> Result<()> {
+    let write = |name: &str, xml: String| {
+        let path = out_dir.join(name);
+        fs::write(&path, xml).with_context(|| format!("Failed to write sitemap: {:?}", path))
+    };
+    if languages.codes().nth(1).is_none() {
+        return write("sitemap.xml", render(&listing.pages, base_url, |_| Vec::new())?);
+    }
+
+    let alternates = |page: &PageListing| {
+        let translations = languages.translations(&page.url, listing);
+        // Stand-ins for missing translations aren't listed, and a page alone needs no links
+        let listed: Vec<(String, String)> = translations
+            .into_iter()
+            .filter(|translation| !translation.fallback)
+            .map(|translation| (translation.lang.to_string(), translation.url))
+            .collect();
+        if listed.len() > 1 { listed } else { Vec::new() }
+    };
+    let mut sitemaps = Vec::new();
+    for code in languages.codes() {
+        let name = format!("sitemap-{}.xml", code);
+        write(&name, render(listing.pages.iter().filter(|page| page.lang == code), base_url, alternates)?)?;
+        sitemaps.push(format!("/{}", name));
+    }
+    write("sitemap.xml", render_index(&sitemaps, base_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_honours_page_hints() {
+        // Arrange
+        let page = |url: &str, sitemap: Option<PageSitemap>| PageListing {
+            title: url.to_string(),
+            url: url.to_string(),
+            date: Some("2024-03-01".to_string()),
+            description: None,
+            lang: "en".to_string(),
+            section: None,
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: Default::default(),
+            menu: None,
+            sitemap,
+        };
+        let hints = SitemapHints { priority: Some(0.3), changefreq: Some(ChangeFreq::Weekly) };
+        let pages = [
+            page("/index.html", None),
+            page("/archive.html", Some(PageSitemap::Hints(hints))),
+            page("/hidden.html", Some(PageSitemap::Listed(false))),
+        ];
+
+        // Act
+        let xml = render(&pages, "https://example.com", |page| match page.url.as_str() {
+            "/index.html" => vec![("en".to_string(), "/index.html".to_string()), ("fr".to_string(), "/fr/index.html".to_string())],
+            _ => Vec::new(),
+        })
+        .unwrap();
+
+        // Assert
+        assert!(xml.contains("<loc>https://example.com/index.html</loc>\n<lastmod>2024-03-01</lastmod>\n<xhtml:link"));
+        assert!(xml.contains("<xhtml:link rel=\"alternate\" hreflang=\"fr\" href=\"https://example.com/fr/index.html\"/>\n</url>"));
+        assert!(xml.contains("<changefreq>weekly</changefreq>\n<priority>0.3</priority>"));
+        assert!(!xml.contains("hidden"));
+        assert!(render_index(&["/sitemap-fr.xml".to_string()], "https://example.com").contains("<loc>https://example.com/sitemap-fr.xml</loc>"));
+    }
+}
@@ -0,0 +1,50 @@
+// Rustic - slugs for titles and terms, and detection of names that share one
+
+use std::collections::BTreeMap;
+
+/// Lowercases text and joins its words with hyphens, e.g. "Hello, World" -> "hello-world".
+///
+/// Symbols that commonly distinguish names are spelled out, so "C++" and "C#" don't
+/// both collapse to "c".
+pub fn slugify(text: &str) -> String {
+    let spelled = text.replace('+', " plus ").replace('#', " sharp ").replace('&', " and ");
+    spelled
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Groups `(name, owner)` pairs by the slug of the name, keeping only slugs claimed
+/// by more than one owner, such as two pages titled "C" and "C!".
+pub fn collisions<'a, T: PartialEq>(
+    names: impl IntoIterator<Item = (&'a str, T)>,
+) -> BTreeMap<String, Vec<(&'a str, T)>> {
+    let mut by_slug: BTreeMap<String, Vec<(&str, T)>> = BTreeMap::new();
+    for (name, owner) in names {
+        let claimed = by_slug.entry(slugify(name)).or_default();
+        if !claimed.iter().any(|(_, o)| *o == owner) {
+            claimed.push((name, owner));
+        }
+    }
+    by_slug.retain(|_, claimed| claimed.len() > 1);
+    by_slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify_and_collisions() {
+        // Act & Assert
+        assert_eq!(slugify("Hello, World"), "hello-world");
+        assert_eq!(slugify("C++"), "c-plus-plus");
+        assert_eq!(slugify("C#"), "c-sharp");
+
+        let found = collisions([("Rust Notes", 1), ("rust-notes", 2), ("Rust Notes", 1), ("C", 3), ("C++", 4)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found["rust-notes"], [("Rust Notes", 1), ("rust-notes", 2)]);
+    }
+}
@@ -0,0 +1,202 @@
+// Rustic - `rustic test --snapshot`, comparing a fresh build with committed golden output
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use walkdir::WalkDir;
+
+use crate::config::{Override, SKIP_COMMANDS};
+use crate::{build, Cli};
+
+/// Content hashes, as in bundle names and unlisted page URLs.
+static HASH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[0-9a-f]{16,64}\b").unwrap());
+
+/// ISO 8601 and RFC 2822 timestamps with a time of day, as in feeds and sitemaps.
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?|[A-Z][a-z]{2}, \d{2} [A-Z][a-z]{2} \d{4} \d{2}:\d{2}:\d{2} (?:[+-]\d{4}|GMT|UTC)",
+    )
+    .unwrap()
+});
+
+/// A difference between the golden output and a build.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed { path: String, detail: String },
+}
+
+/// Builds the site into a temporary directory and compares it with `golden`, or with
+/// `update`, replaces `golden` with the new output.
+///
+/// Hooks and pipeline commands are skipped, as in a dry run, so a post-build deploy
+/// doesn't run against the test build.
+pub fn run(cli: &Cli, golden: &Path, update: bool) -> Result<()> {
+    let out_dir = std::env::temp_dir().join(format!("rustic-snapshot-{}", std::process::id()));
+    let mut overrides = cli.overrides.clone();
+    for skipped in SKIP_COMMANDS {
+        overrides.push(skipped.parse::<Override>().map_err(anyhow::Error::msg)?);
+    }
+    let snapshot_cli = Cli {
+        command: None,
+        input_dir: cli.input_dir.clone(),
+        out_dir: out_dir.clone(),
+        config: cli.config.clone(),
+        profile: cli.profile.clone(),
+        overrides,
+        timings: false,
+        unused_templates: false,
+        archive: None,
+        dump_context: None,
+        dry_run: false,
+    };
+    let built = build(&snapshot_cli).context("Failed to build the site for the snapshot");
+    let result = built.and_then(|_| if update { replace(golden, &out_dir) } else { report(golden, &out_dir) });
+    let _ = fs::remove_dir_all(&out_dir);
+    result
+}
+
+fn replace(golden: &Path, out_dir: &Path) -> Result<()> {
+    if golden.exists() {
+        fs::remove_dir_all(golden).with_context(|| format!("Failed to remove old snapshot: {:?}", golden))?;
+    }
+    let files = files(out_dir)?;
+    for (relative, source) in &files {
+        let target = golden.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", target))?;
+        }
+        fs::copy(source, &target).with_context(|| format!("Failed to copy {:?} to {:?}", source, target))?;
+    }
+    println!("✅ Updated the snapshot in {:?} ({} files)", golden, files.len());
+    Ok(())
+}
+
+fn report(golden: &Path, out_dir: &Path) -> Result<()> {
+    if !golden.is_dir() {
+        bail!("There is no snapshot in {:?}; create one with `rustic test --snapshot --update`", golden);
+    }
+    let changes = compare(golden, out_dir)?;
+    if changes.is_empty() {
+        println!("✅ Output matches the snapshot in {:?}", golden);
+        return Ok(());
+    }
+    for change in &changes {
+        match change {
+            Change::Added(path) => eprintln!("  + {}", path),
+            Change::Removed(path) => eprintln!("  - {}", path),
+            Change::Changed { path, detail } => eprintln!("  ~ {}: {}", path, detail),
+        }
+    }
+    bail!(
+        "Output differs from the snapshot in {} file(s); rerun with --update if the changes are expected",
+        changes.len()
+    )
+}
+
+/// Compares two output trees file by file, ignoring content hashes and timestamps.
+pub fn compare(golden: &Path, out_dir: &Path) -> Result<Vec<Change>> {
+    let normalize_path = |path: &String| HASH.replace_all(path, "<hash>").into_owned();
+    let expected: BTreeMap<String, PathBuf> = files(golden)?.into_iter().map(|(p, f)| (normalize_path(&p), f)).collect();
+    let actual: BTreeMap<String, PathBuf> = files(out_dir)?.into_iter().map(|(p, f)| (normalize_path(&p), f)).collect();
+
+    let mut changes = Vec::new();
+    for (path, expected_file) in &expected {
+        let Some(actual_file) = actual.get(path) else {
+            changes.push(Change::Removed(path.clone()));
+            continue;
+        };
+        let read = |file: &PathBuf| fs::read(file).with_context(|| format!("Failed to read {:?}", file));
+        let (expected_bytes, actual_bytes) = (read(expected_file)?, read(actual_file)?);
+        if expected_bytes == actual_bytes {
+            continue;
+        }
+        let detail = match (String::from_utf8(expected_bytes), String::from_utf8(actual_bytes)) {
+            (Ok(expected), Ok(actual)) => first_difference(&normalize(&expected), &normalize(&actual)),
+            _ => Some("binary content differs".to_string()),
+        };
+        if let Some(detail) = detail {
+            changes.push(Change::Changed { path: path.clone(), detail });
+        }
+    }
+    changes.extend(actual.keys().filter(|path| !expected.contains_key(*path)).map(|path| Change::Added(path.clone())));
+    Ok(changes)
+}
+
+/// Masks what legitimately changes between builds of the same content.
+fn normalize(text: &str) -> String {
+    let text = TIMESTAMP.replace_all(text, "<timestamp>");
+    HASH.replace_all(&text, "<hash>").into_owned()
+}
+
+/// The first line that differs, with both versions; `None` when the texts are equal.
+fn first_difference(expected: &str, actual: &str) -> Option<String> {
+    let (expected, actual): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+    if expected == actual {
+        return None;
+    }
+    let line = expected.iter().zip(&actual).position(|(e, a)| e != a).unwrap_or(expected.len().min(actual.len()));
+    Some(format!(
+        "line {}\n      expected: {}\n      actual:   {}",
+        line + 1,
+        expected.get(line).unwrap_or(&"(end of file)").trim(),
+        actual.get(line).unwrap_or(&"(end of file)").trim()
+    ))
+}
+
+/// Every file under `dir`, keyed by its forward-slashed relative path.
+pub fn files(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut files = BTreeMap::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("Failed to read {:?}", dir))?;
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+            files.insert(relative, entry.path().to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-snapshot-test-{}", std::process::id()));
+        let (golden, out_dir) = (dir.join("golden"), dir.join("public"));
+        for (root, bundle, updated, body) in [
+            (&golden, "app.0123456789abcdef.js", "2024-01-01T10:00:00Z", "<p>Old</p>"),
+            (&out_dir, "app.fedcba9876543210.js", "2024-06-01T08:30:00Z", "<p>New</p>"),
+        ] {
+            fs::create_dir_all(root.join("static")).unwrap();
+            fs::write(root.join("static").join(bundle), "").unwrap();
+            fs::write(root.join("sitemap.xml"), format!("<lastmod>{}</lastmod>", updated)).unwrap();
+            fs::write(root.join("index.html"), format!("<h1>Home</h1>\n{}\n", body)).unwrap();
+        }
+        fs::write(golden.join("old.html"), "").unwrap();
+        fs::write(out_dir.join("new.html"), "").unwrap();
+
+        // Act
+        let changes = compare(&golden, &out_dir).unwrap();
+
+        // Assert
+        assert_eq!(
+            changes,
+            [
+                Change::Changed {
+                    path: "index.html".to_string(),
+                    detail: "line 2\n      expected: <p>Old</p>\n      actual:   <p>New</p>".to_string()
+                },
+                Change::Removed("old.html".to_string()),
+                Change::Added("new.html".to_string()),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+// Rustic - per-page Open Graph card images
+
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont};
+use anyhow::{anyhow, Context, Result};
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Card size recommended by Facebook, LinkedIn and Mastodon.
+const CARD_WIDTH: u32 = 1200;
+const CARD_HEIGHT: u32 = 630;
+const PADDING: f32 = 80.0;
+const TITLE_SIZE: f32 = 72.0;
+const BYLINE_SIZE: f32 = 36.0;
+const MAX_TITLE_LINES: usize = 4;
+
+/// Settings for generated card images, declared under `[social_cards]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+pub struct SocialCardConfig {
+    /// TrueType/OpenType font used for all text on the card
+    pub font: PathBuf,
+    /// Optional 1200x630 image drawn behind the text
+    pub background: Option<PathBuf>,
+    #[serde(default = "default_background_color")]
+    pub background_color: String,
+    #[serde(default = "default_text_color")]
+    pub text_color: String,
+    /// Line shown under the title, e.g. the author or site name
+    pub byline: Option<String>,
+}
+
+fn default_background_color() -> String {
+    "#2c3e50".to_string()
+}
+
+fn default_text_color() -> String {
+    "#ffffff".to_string()
+}
+
+/// Renders card images with a font and background loaded once per build.
+pub struct SocialCards {
+    font: FontVec,
+    background: RgbaImage,
+    text_color: Rgba<u8>,
+    byline: Option<String>,
+}
+
+impl SocialCards {
+    pub fn load(config: &SocialCardConfig) -> Result<Self> {
+        let font_bytes = fs::read(&config.font)
+            .with_context(|| format!("Failed to read social card font: {:?}", config.font))?;
+        let font = FontVec::try_from_vec(font_bytes)
+            .map_err(|_| anyhow!("Invalid social card font: {:?}", config.font))?;
+
+        let background = match &config.background {
+            Some(path) => image::open(path)
+                .with_context(|| format!("Failed to read social card background: {:?}", path))?
+                .resize_to_fill(CARD_WIDTH, CARD_HEIGHT, image::imageops::FilterType::Triangle)
+                .to_rgba8(),
+            None => RgbaImage::from_pixel(CARD_WIDTH, CARD_HEIGHT, parse_color(&config.background_color)?),
+        };
+
+        Ok(Self {
+            font,
+            background,
+            text_color: parse_color(&config.text_color)?,
+            byline: config.byline.clone(),
+        })
+    }
+
+    /// Draws the card for a page title and writes it as a PNG.
+    pub fn render(&self, title: &str, output: &Path) -> Result<()> {
+        let mut card = self.background.clone();
+        let max_width = CARD_WIDTH as f32 - 2.0 * PADDING;
+
+        let title_scale = PxScale::from(TITLE_SIZE);
+        let line_height = self.font.as_scaled(title_scale).height() * 1.1;
+        let mut baseline = PADDING + self.font.as_scaled(title_scale).ascent();
+        for line in wrap(&self.font, title_scale, title, max_width, MAX_TITLE_LINES) {
+            self.draw_line(&mut card, title_scale, PADDING, baseline, &line);
+            baseline += line_height;
+        }
+
+        if let Some(byline) = &self.byline {
+            let byline_scale = PxScale::from(BYLINE_SIZE);
+            let baseline = CARD_HEIGHT as f32 - PADDING;
+            self.draw_line(&mut card, byline_scale, PADDING, baseline, byline);
+        }
+
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory for {:?}", output))?;
+        }
+        card.save(output)
+            .with_context(|| format!("Failed to write social card: {:?}", output))
+    }
+
+    /// Draws one line of text with its baseline at `y`, alpha-blending onto the card.
+    fn draw_line(&self, card: &mut RgbaImage, scale: PxScale, x: f32, y: f32, text: &str) {
+        let scaled = self.font.as_scaled(scale);
+        let mut caret = x;
+        let mut previous: Option<GlyphId> = None;
+
+        for ch in text.chars() {
+            let id = scaled.glyph_id(ch);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(scale, point(caret, y));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+
+            let Some(outline) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i64 + gx as i64;
+                let py = bounds.min.y as i64 + gy as i64;
+                if px < 0 || py < 0 || px >= CARD_WIDTH as i64 || py >= CARD_HEIGHT as i64 {
+                    return;
+                }
+                let pixel = card.get_pixel_mut(px as u32, py as u32);
+                for channel in 0..3 {
+                    let under = pixel[channel] as f32;
+                    let over = self.text_color[channel] as f32;
+                    pixel[channel] = (under + (over - under) * coverage).round() as u8;
+                }
+            });
+        }
+    }
+}
+
+/// Breaks text into lines no wider than `max_width`, ending with "…" if it doesn't fit.
+fn wrap(font: &FontVec, scale: PxScale, text: &str, max_width: f32, max_lines: usize) -> Vec<String> {
+    let scaled = font.as_scaled(scale);
+    let width = |s: &str| s.chars().map(|c| scaled.h_advance(scaled.glyph_id(c))).sum::<f32>();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if width(&candidate) <= max_width || current.is_empty() {
+            current = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+    lines
+}
+
+/// Parses a `#rrggbb` color.
+fn parse_color(hex: &str) -> Result<Rgba<u8>> {
+    let digits = hex.trim_start_matches('#');
+    let channel = |i: usize| {
+        digits
+            .get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| anyhow!("Invalid color `{}`, expected #rrggbb", hex))
+    };
+    if digits.len() != 6 {
+        return Err(anyhow!("Invalid color `{}`, expected #rrggbb", hex));
+    }
+    Ok(Rgba([channel(0)?, channel(2)?, channel(4)?, 255]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        // Act & Assert
+        assert_eq!(parse_color("#2c3e50").unwrap(), Rgba([0x2c, 0x3e, 0x50, 255]));
+        assert!(parse_color("blue").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+// Rustic - spellchecking of markdown prose
+
+use anyhow::{Context, Result};
+use gray_matter::{engine::YAML, Matter};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Matches markup whose text isn't prose: inline code, shortcodes, HTML tags, URLs, link
+/// targets and callout or container names.
+static NOT_PROSE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"`[^`]*`|\{\{.*?\}\}|\{%.*?%\}|<[^>]+>|\]\([^)]*\)|https?://\S+|\[\[[^\]]*\]\]|\[![\w-]+\]|^\s*:::\S*").unwrap()
+});
+
+/// Matches a word, including apostrophes inside it.
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[\p{L}][\p{L}'’]*").unwrap());
+
+/// Known words, from a stock dictionary plus the project's own list.
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Loads one-word-per-line dictionaries; the project list is optional.
+    pub fn load(dictionary: &Path, project_words: &Path) -> Result<Self> {
+        let stock = fs::read_to_string(dictionary).with_context(|| {
+            format!(
+                "Failed to read dictionary {:?}; install a word list (e.g. the `wamerican` package) or set `dictionary` under [check]",
+                dictionary
+            )
+        })?;
+        let mut words: HashSet<String> = stock.lines().map(|w| w.trim().to_lowercase()).collect();
+        if project_words.exists() {
+            let extra = fs::read_to_string(project_words)
+                .with_context(|| format!("Failed to read word list: {:?}", project_words))?;
+            words.extend(extra.lines().map(|w| w.trim().to_lowercase()));
+        }
+        Ok(Self { words })
+    }
+
+    fn knows(&self, word: &str) -> bool {
+        let word = word.to_lowercase().replace('’', "'");
+        let word = word.trim_end_matches('\'');
+        self.words.contains(word) || word.strip_suffix("'s").is_some_and(|w| self.words.contains(w))
+    }
+}
+
+/// Lists unknown words in a markdown file as `(line number, word)`, skipping front matter and code.
+pub fn check_file(path: &Path, dictionary: &Dictionary) -> Result<Vec<(usize, String)>> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read markdown file: {:?}", path))?;
+    let body = Matter::<YAML>::new().parse(&raw).content;
+    // Line numbers refer to the file, so account for the front matter that was removed
+    let offset = raw.lines().count().saturating_sub(body.lines().count());
+    Ok(check_prose(&body, dictionary)
+        .into_iter()
+        .map(|(line, word)| (line + offset, word))
+        .collect())
+}
+
+/// Lists unknown words in markdown as `(line number, word)`.
+fn check_prose(markdown: &str, dictionary: &Dictionary) -> Vec<(usize, String)> {
+    prose_words(markdown)
+        .into_iter()
+        // Acronyms such as HTML are left alone
+        .filter(|(_, word)| !word.chars().filter(|c| c.is_alphabetic()).all(char::is_uppercase))
+        .filter(|(_, word)| !dictionary.knows(word))
+        .collect()
+}
+
+/// Lists the words of markdown prose with their line numbers, skipping code and markup.
+pub fn prose_words(markdown: &str) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for (number, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        }
+
+        let prose = NOT_PROSE.replace_all(line, " ");
+        words.extend(WORD.find_iter(&prose).map(|m| (number + 1, m.as_str().to_string())));
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_prose() {
+        // Arrange
+        let dictionary = Dictionary {
+            words: ["the", "site", "is", "fast", "see"].into_iter().map(String::from).collect(),
+        };
+        let markdown = "The site's HTML is fsat.\n\n```\nnot checked\n```\nSee `code` and [site](https://example.com/teh).\n:::aside\n> [!tip] The site";
+
+        // Act
+        let misspelled = check_prose(markdown, &dictionary);
+
+        // Assert
+        assert_eq!(misspelled, [(1, "fsat".to_string()), (6, "and".to_string())]);
+    }
+}
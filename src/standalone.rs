@@ -0,0 +1,166 @@
+// Rustic - single-file copy of a built page, with its stylesheets, images and fonts inlined
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use lol_html::html_content::ContentType;
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use regex::{Captures, Regex};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Matches `url(...)` references in CSS, quoted or not.
+static CSS_URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"url\(\s*['"]?([^'")]+?)['"]?\s*\)"#).unwrap());
+
+/// Writes a built page to `output` with everything it loads from the site inlined,
+/// so it can be emailed or archived as one file.
+///
+/// `page` is the page's markdown source under `input_dir` or its HTML under `out_dir`.
+pub fn export(page: &Path, input_dir: &Path, out_dir: &Path, output: &Path) -> Result<()> {
+    let html_path = match page.strip_prefix(input_dir) {
+        Ok(relative) => out_dir.join(relative).with_extension("html"),
+        Err(_) if page.starts_with(out_dir) => page.to_path_buf(),
+        Err(_) => out_dir.join(page).with_extension("html"),
+    };
+    if !html_path.is_file() {
+        bail!("There is no built page at {:?}; build the site first", html_path);
+    }
+    let html = fs::read_to_string(&html_path).with_context(|| format!("Failed to read page: {:?}", html_path))?;
+    let inlined = inline(&html, html_path.parent().unwrap_or(out_dir), out_dir)?;
+    fs::write(output, &inlined).with_context(|| format!("Failed to write page: {:?}", output))?;
+    println!("✅ Wrote {:?} ({} KB)", output, inlined.len() / 1024);
+    Ok(())
+}
+
+/// Inlines local stylesheets as `<style>` and local images, icons and the fonts and
+/// images stylesheets refer to as `data:` URLs. Remote URLs are left as they are.
+pub fn inline(html: &str, page_dir: &Path, out_dir: &Path) -> Result<String> {
+    let warnings = RefCell::new(Vec::new());
+    let data_url = |url: &str, base: &Path| match local_asset(url, base, out_dir) {
+        Some(path) => match fs::read(&path) {
+            Ok(bytes) => Some(format!("data:{};base64,{}", media_type(&path), STANDARD.encode(bytes))),
+            Err(err) => {
+                warnings.borrow_mut().push(format!("Failed to read {:?}: {}", path, err));
+                None
+            }
+        },
+        None => None,
+    };
+    let css = |css: &str, base: &Path| {
+        CSS_URL
+            .replace_all(css, |caps: &Captures| match data_url(&caps[1], base) {
+                Some(url) => format!("url(\"{}\")", url),
+                None => caps[0].to_string(),
+            })
+            .into_owned()
+    };
+    let style = RefCell::new(String::new());
+
+    let inlined = rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("link[rel~=stylesheet][href]", |el| {
+                let href = el.get_attribute("href").unwrap_or_default();
+                if let Some(path) = local_asset(&href, page_dir, out_dir) {
+                    let text = fs::read_to_string(&path).with_context(|| format!("Failed to read stylesheet: {:?}", path))?;
+                    let base = path.parent().unwrap_or(out_dir);
+                    el.replace(&format!("<style>\n{}</style>", css(&text, base)), ContentType::Html);
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("link[rel~=icon][href]", |el| {
+                if let Some(url) = data_url(&el.get_attribute("href").unwrap_or_default(), page_dir) {
+                    el.set_attribute("href", &url)?;
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("img[src]", |el| {
+                if let Some(url) = data_url(&el.get_attribute("src").unwrap_or_default(), page_dir) {
+                    el.set_attribute("src", &url)?;
+                    // Other sizes would still point at the site
+                    el.remove_attribute("srcset");
+                    el.remove_attribute("sizes");
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("picture source[srcset]", |el| {
+                el.remove();
+                Ok(())
+            }))
+            .append_element_content_handler(text!("style", |chunk| {
+                let mut style = style.borrow_mut();
+                style.push_str(chunk.as_str());
+                if chunk.last_in_text_node() {
+                    chunk.replace(&css(&std::mem::take(&mut *style), page_dir), ContentType::Html);
+                } else {
+                    chunk.remove();
+                }
+                Ok(())
+            })),
+    )?;
+    for warning in warnings.into_inner() {
+        eprintln!("⚠️  {}", warning);
+    }
+    Ok(inlined)
+}
+
+/// Finds the built file a URL on the page refers to; `None` for remote and `data:` URLs.
+fn local_asset(url: &str, base: &Path, out_dir: &Path) -> Option<PathBuf> {
+    if url.is_empty() || url.starts_with("//") || url.starts_with('#') || url.contains(':') {
+        return None;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = match path.strip_prefix('/') {
+        Some(site_relative) => out_dir.join(site_relative),
+        None => base.join(path),
+    };
+    path.is_file().then_some(path)
+}
+
+pub fn media_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "css" => "text/css",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_assets() {
+        // Arrange
+        let out_dir = std::env::temp_dir().join(format!("rustic-standalone-{}", std::process::id()));
+        fs::create_dir_all(out_dir.join("css/fonts")).unwrap();
+        fs::write(out_dir.join("css/site.css"), "@font-face { src: url('fonts/a.woff2'); }\n").unwrap();
+        fs::write(out_dir.join("css/fonts/a.woff2"), b"font").unwrap();
+        fs::write(out_dir.join("a.png"), b"png").unwrap();
+        let html = "<head><link rel=\"stylesheet\" href=\"/css/site.css\"></head>\
+                    <body><img src=\"a.png\" srcset=\"a-2x.png 2x\" alt=\"\"><img src=\"https://example.com/b.png\"></body>";
+
+        // Act
+        let inlined = inline(html, &out_dir, &out_dir).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        // Assert
+        assert_eq!(
+            inlined,
+            "<head><style>\n@font-face { src: url(\"data:font/woff2;base64,Zm9udA==\"); }\n</style></head>\
+             <body><img src=\"data:image/png;base64,cG5n\" alt=\"\"><img src=\"https://example.com/b.png\"></body>"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// Rustic - `rustic stats` content inventory
+
+use anyhow::Result;
+use gray_matter::{engine::YAML, Matter};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::spelling::prose_words;
+
+/// Average adult silent reading speed, in words per minute.
+const WORDS_PER_MINUTE: usize = 200;
+/// How many of the longest pages and largest files are listed.
+const TOP: usize = 10;
+
+/// Word count of one source page.
+struct PageStats {
+    path: PathBuf,
+    section: String,
+    words: usize,
+}
+
+/// Prints word counts, pages per section, reading times and the largest output files.
+pub fn print(input_dir: &Path, out_dir: &Path) -> Result<()> {
+    let matter = Matter::<YAML>::new();
+    let mut pages = Vec::new();
+    for entry in WalkDir::new(input_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+        let body = matter.parse(&fs::read_to_string(path)?).content;
+        let relative = path.strip_prefix(input_dir)?;
+        // Pages directly in the content directory belong to the root section
+        let section = match relative.parent().and_then(|p| p.iter().next()) {
+            Some(dir) => dir.to_string_lossy().into_owned(),
+            None => "(root)".to_string(),
+        };
+        pages.push(PageStats {
+            path: relative.to_path_buf(),
+            section,
+            words: prose_words(&body).len(),
+        });
+    }
+
+    let total_words: usize = pages.iter().map(|p| p.words).sum();
+    let average = total_words.checked_div(pages.len()).unwrap_or(0);
+    println!("Pages: {}", pages.len());
+    println!("Words: {}", total_words);
+    println!("Average reading time: {} min", reading_minutes(average));
+
+    let mut sections: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for page in &pages {
+        let section = sections.entry(&page.section).or_default();
+        section.0 += 1;
+        section.1 += page.words;
+    }
+    println!("\nSections:");
+    for (name, (count, words)) in &sections {
+        println!("  {:<24} {:>5} page(s) {:>8} words", name, count, words);
+    }
+
+    pages.sort_by_key(|p| std::cmp::Reverse(p.words));
+    println!("\nLongest pages:");
+    for page in pages.iter().take(TOP) {
+        println!(
+            "  {:<40} {:>8} words {:>4} min",
+            page.path.display(),
+            page.words,
+            reading_minutes(page.words)
+        );
+    }
+
+    if out_dir.is_dir() {
+        let mut files: Vec<(u64, PathBuf)> = WalkDir::new(out_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| Some((e.metadata().ok()?.len(), e.path().strip_prefix(out_dir).ok()?.to_path_buf())))
+            .collect();
+        files.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+        let total: u64 = files.iter().map(|(size, _)| size).sum();
+        println!("\nOutput: {} file(s), {}", files.len(), format_size(total));
+        println!("Largest files:");
+        for (size, path) in files.iter().take(TOP) {
+            println!("  {:<40} {:>10}", path.display(), format_size(*size));
+        }
+    }
+
+    Ok(())
+}
+
+/// Minutes needed to read `words`, rounded up so short pages show at least a minute.
+fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(WORDS_PER_MINUTE).max(1)
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB"] {
+        if size < 1024.0 {
+            return if unit == "B" { format!("{} B", bytes) } else { format!("{:.1} {}", size, unit) };
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} GiB", size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reading_time_and_sizes() {
+        // Act & Assert
+        assert_eq!(reading_minutes(0), 1);
+        assert_eq!(reading_minutes(401), 3);
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
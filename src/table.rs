@@ -0,0 +1,183 @@
+// Rustic - table shortcode rendering a CSV file as an HTML table
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fs;
+use tera::Context as TeraContext;
+
+use crate::shortcodes::{self, escape_attr, string_arg, Args};
+use crate::{data, Site};
+
+/// Sorts the rows by a column when its header is clicked, numerically when both cells are numbers.
+const SORT_SCRIPT: &str = r#"<script>
+(() => {
+  const table = document.currentScript.previousElementSibling;
+  const headers = [...table.tHead.rows[0].cells];
+  headers.forEach((th, column) => th.querySelector("button").addEventListener("click", () => {
+    const ascending = th.getAttribute("aria-sort") !== "ascending";
+    headers.forEach((other) => other.removeAttribute("aria-sort"));
+    th.setAttribute("aria-sort", ascending ? "ascending" : "descending");
+    const key = (row) => row.cells[column]?.dataset.value ?? row.cells[column]?.textContent ?? "";
+    const rows = [...table.tBodies[0].rows].sort((a, b) => {
+      const [x, y] = [key(a), key(b)];
+      const order = x !== "" && y !== "" && !isNaN(x) && !isNaN(y) ? x - y : x.localeCompare(y, undefined, { numeric: true });
+      return ascending ? order : -order;
+    });
+    table.tBodies[0].append(...rows);
+  }));
+})();
+</script>"#;
+
+/// How a column's cells are shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Code,
+    /// Thousands separators, with a fixed number of decimals if given
+    Number(Option<usize>),
+    /// Fractions shown as percentages, e.g. `0.25` as `25%`
+    Percent(Option<usize>),
+}
+
+/// Renders `{{ table(path="data/results.csv", sortable=true) }}`: the CSV file, relative
+/// to the site root, as a table whose first row is the header unless it looks like data.
+pub fn render(args: &Args, site: &Site) -> Result<String> {
+    let path = string_arg(args, "path")?;
+    let raw = fs::read_to_string(path).with_context(|| format!("Failed to read table data: {:?}", path))?;
+    let mut rows = data::parse_csv(&raw).with_context(|| format!("Failed to parse table data: {:?}", path))?;
+    let has_header = match args.get("header").and_then(Value::as_bool) {
+        Some(header) => header,
+        None => rows.first().is_some_and(|row| looks_like_header(row)),
+    };
+    let header = if has_header && !rows.is_empty() { Some(rows.remove(0)) } else { None };
+    let formats = match args.get("format").and_then(Value::as_str) {
+        Some(formats) => formats.split(',').map(parse_format).collect::<Result<_>>()?,
+        None => Vec::new(),
+    };
+    let sortable = args.get("sortable").and_then(Value::as_bool).unwrap_or(false);
+
+    let mut context = TeraContext::new();
+    context.insert("path", path);
+    context.insert("header", &header);
+    context.insert("rows", &rows);
+    context.insert("sortable", &sortable);
+    if let Some(html) = shortcodes::render_template("table", &context, site)? {
+        return Ok(html);
+    }
+    Ok(table_html(header.as_deref(), &rows, &formats, sortable))
+}
+
+/// A first row with a blank or numeric cell is data; headers are words.
+fn looks_like_header(row: &[String]) -> bool {
+    row.iter().all(|cell| !cell.trim().is_empty() && cell.trim().parse::<f64>().is_err())
+}
+
+/// Parses one entry of the `format` argument: `text`, `code`, `number`, `number:2`,
+/// `percent` or `percent:1`; blank means text.
+fn parse_format(raw: &str) -> Result<Format> {
+    let (name, decimals) = match raw.trim().split_once(':') {
+        Some((name, decimals)) => {
+            let decimals = decimals.parse().with_context(|| format!("Invalid decimals in table format {:?}", raw))?;
+            (name, Some(decimals))
+        }
+        None => (raw.trim(), None),
+    };
+    Ok(match (name, decimals) {
+        ("" | "text", None) => Format::Text,
+        ("code", None) => Format::Code,
+        ("number", decimals) => Format::Number(decimals),
+        ("percent", decimals) => Format::Percent(decimals),
+        _ => bail!("Unknown table format {:?}; expected text, code, number or percent", raw),
+    })
+}
+
+fn table_html(header: Option<&[String]>, rows: &[Vec<String>], formats: &[Format], sortable: bool) -> String {
+    // Sorting is driven from the header, so a table without one can't be sorted
+    let sortable = sortable && header.is_some();
+    let mut html = format!("<table class=\"data-table{}\">\n", if sortable { " sortable" } else { "" });
+    if let Some(header) = header {
+        html.push_str("<thead>\n<tr>");
+        for name in header {
+            let name = escape_attr(name);
+            if sortable {
+                html.push_str(&format!("<th scope=\"col\"><button type=\"button\">{}</button></th>", name));
+            } else {
+                html.push_str(&format!("<th scope=\"col\">{}</th>", name));
+            }
+        }
+        html.push_str("</tr>\n</thead>\n");
+    }
+    html.push_str("<tbody>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for (column, cell) in row.iter().enumerate() {
+            html.push_str(&cell_html(cell, formats.get(column).copied().unwrap_or(Format::Text)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>");
+    if sortable {
+        html.push('\n');
+        html.push_str(SORT_SCRIPT);
+    }
+    html
+}
+
+/// A `<td>` for `cell`; formatted numbers keep their raw value in `data-value` for sorting.
+fn cell_html(cell: &str, format: Format) -> String {
+    let number = cell.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+    match (format, number) {
+        (Format::Code, _) => format!("<td><code>{}</code></td>", escape_attr(cell)),
+        (Format::Number(decimals), Some(n)) => {
+            let shown = match decimals {
+                Some(decimals) => format!("{:.*}", decimals, n),
+                None => cell.trim().trim_start_matches('+').to_string(),
+            };
+            format!("<td class=\"numeric\" data-value=\"{}\">{}</td>", n, group_thousands(&shown))
+        }
+        (Format::Percent(decimals), Some(n)) => {
+            let shown = format!("{:.*}", decimals.unwrap_or(0), n * 100.0);
+            format!("<td class=\"numeric\" data-value=\"{}\">{}%</td>", n, group_thousands(&shown))
+        }
+        _ => format!("<td>{}</td>", escape_attr(cell)),
+    }
+}
+
+/// Inserts commas between groups of three digits before the decimal point.
+fn group_thousands(number: &str) -> String {
+    let (sign, unsigned) = number.strip_prefix('-').map_or(("", number), |rest| ("-", rest));
+    let (integer, fraction) = unsigned.split_at(unsigned.find(['.', 'e', 'E']).unwrap_or(unsigned.len()));
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{}{}{}", sign, grouped, fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_html() {
+        // Arrange
+        let mut rows = data::parse_csv("Crate,Downloads,Share\nserde,1234567,0.127\n<tera>,980.5,-\n").unwrap();
+        let header = looks_like_header(&rows[0]).then(|| rows.remove(0));
+        let formats: Vec<Format> = "code,number:1,percent".split(',').map(|f| parse_format(f).unwrap()).collect();
+
+        // Act
+        let html = table_html(header.as_deref(), &rows, &formats, true);
+
+        // Assert
+        assert!(html.starts_with("<table class=\"data-table sortable\">\n<thead>\n<tr><th scope=\"col\"><button type=\"button\">Crate</button></th>"));
+        assert!(html.contains(
+            "<tr><td><code>serde</code></td><td class=\"numeric\" data-value=\"1234567\">1,234,567.0</td><td class=\"numeric\" data-value=\"0.127\">13%</td></tr>"
+        ));
+        assert!(html.contains("<tr><td><code>&lt;tera&gt;</code></td><td class=\"numeric\" data-value=\"980.5\">980.5</td><td>-</td></tr>"));
+        assert!(html.ends_with("</script>"));
+        assert!(!looks_like_header(&["2024".to_string(), "Launch".to_string()]));
+    }
+}
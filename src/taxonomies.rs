@@ -0,0 +1,140 @@
+// Rustic - taxonomies such as tags and categories, with a page and feed per term
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::dates::parse_date;
+use crate::listing::{Listing, PageListing};
+use crate::slug::{collisions, slugify};
+
+/// A taxonomy declared under `[[taxonomies]]` in rustic.toml; pages list terms under its name.
+#[derive(Debug, Deserialize)]
+pub struct TaxonomyConfig {
+    /// Front matter field and URL segment, e.g. `tags`
+    pub name: String,
+    /// Also write an RSS feed per term, at `<name>/<term>/rss.xml`
+    #[serde(default)]
+    pub feed: bool,
+}
+
+/// One term of a taxonomy and the pages using it, newest first.
+#[derive(Debug, Serialize)]
+pub struct Term<'a> {
+    pub name: String,
+    pub slug: String,
+    /// The term page, e.g. `/tags/rust/index.html`
+    pub url: String,
+    pub feed_url: Option<String>,
+    pub pages: Vec<&'a PageListing>,
+}
+
+/// Every (term, page) pair of a taxonomy, in page order.
+fn uses<'a>(taxonomy: &str, listing: &'a Listing) -> Vec<(&'a str, &'a PageListing)> {
+    listing
+        .pages
+        .iter()
+        .flat_map(|page| {
+            let terms = page.taxonomies.get(taxonomy).into_iter().flatten();
+            terms.map(move |term| (term.as_str(), page))
+        })
+        .collect()
+}
+
+/// Fails on terms written differently that slugify alike, such as "Rust" and "rust",
+/// naming each spelling and the pages that use it.
+pub fn check_spellings(config: &[TaxonomyConfig], listing: &Listing) -> Result<()> {
+    let mut conflicts = Vec::new();
+    for taxonomy in config {
+        let uses = uses(&taxonomy.name, listing);
+        for (slug, names) in collisions(uses.iter().map(|(term, _)| (*term, *term))) {
+            conflicts.push(format!("{} terms would share the page {}/{}:", taxonomy.name, taxonomy.name, slug));
+            for (name, _) in names {
+                let mut pages: Vec<&str> = uses.iter().filter(|(term, _)| *term == name).map(|(_, page)| page.url.as_str()).collect();
+                pages.dedup();
+                conflicts.push(format!("  \"{}\" on {}", name, pages.join(", ")));
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        bail!("{}\nPick one spelling for each term", conflicts.join("\n"));
+    }
+    Ok(())
+}
+
+/// Groups the listed pages by term for each configured taxonomy, terms sorted by slug.
+///
+/// Terms that slugify alike share a page under the first spelling seen; builds reject
+/// them first with `check_spellings`.
+pub fn collect<'a>(config: &[TaxonomyConfig], listing: &'a Listing) -> BTreeMap<String, Vec<Term<'a>>> {
+    let mut taxonomies = BTreeMap::new();
+    for taxonomy in config {
+        let uses = uses(&taxonomy.name, listing);
+        let mut terms: BTreeMap<String, Term> = BTreeMap::new();
+        for (name, page) in uses {
+            let slug = slugify(name);
+            let term = terms.entry(slug.clone()).or_insert_with(|| Term {
+                name: name.to_string(),
+                url: format!("/{}/{}/index.html", taxonomy.name, slug),
+                feed_url: taxonomy.feed.then(|| format!("/{}/{}/rss.xml", taxonomy.name, slug)),
+                slug,
+                pages: Vec::new(),
+            });
+            if !term.pages.iter().any(|p| p.url == page.url) {
+                term.pages.push(page);
+            }
+        }
+        for term in terms.values_mut() {
+            term.pages.sort_by_key(|page| std::cmp::Reverse(page.date.as_deref().and_then(parse_date)));
+        }
+        taxonomies.insert(taxonomy.name.clone(), terms.into_values().collect());
+    }
+    taxonomies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_terms() {
+        // Arrange
+        let page = |url: &str, date: &str, tags: &[&str]| PageListing {
+            title: url.to_string(),
+            url: url.to_string(),
+            date: Some(date.to_string()),
+            description: None,
+            lang: "en".to_string(),
+            section: None,
+            weight: None,
+            series: None,
+            draft: false,
+            updated: None,
+            contributors: Vec::new(),
+            taxonomies: BTreeMap::from([("tags".to_string(), tags.iter().map(|t| t.to_string()).collect())]),
+            menu: None,
+            sitemap: None,
+        };
+        let listing = Listing::new(
+            vec![page("/a.html", "2024-01-01", &["Rust", "CLI"]), page("/b.html", "2024-02-01", &["rust"])],
+            &BTreeMap::new(),
+        );
+        let config = [TaxonomyConfig { name: "tags".to_string(), feed: true }];
+
+        // Act
+        let taxonomies = collect(&config, &listing);
+
+        // Assert
+        let tags = &taxonomies["tags"];
+        assert_eq!(tags.iter().map(|t| t.slug.as_str()).collect::<Vec<_>>(), ["cli", "rust"]);
+        assert_eq!(tags[1].name, "Rust");
+        assert_eq!(tags[1].feed_url.as_deref(), Some("/tags/rust/rss.xml"));
+        assert_eq!(tags[1].pages.iter().map(|p| p.url.as_str()).collect::<Vec<_>>(), ["/b.html", "/a.html"]);
+        let err = check_spellings(&config, &listing).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "tags terms would share the page tags/rust:\n  \"Rust\" on /a.html\n  \"rust\" on /b.html\nPick one spelling for each term"
+        );
+        assert!(check_spellings(&config, &Listing::new(vec![page("/c.html", "2024-03-01", &["Rust"])], &BTreeMap::new())).is_ok());
+    }
+}
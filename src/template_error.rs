@@ -0,0 +1,172 @@
+// Rustic - template render errors that point at the failing line and suggest variables
+
+use regex::Regex;
+use serde_json::Value;
+use std::error::Error as StdError;
+use std::fs;
+use std::sync::LazyLock;
+use tera::{Context as TeraContext, Tera};
+
+/// Matches the template an error happened in, when it isn't the one being rendered.
+static HAPPENED_IN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"error happened in '([^']+)'").unwrap());
+
+/// Matches what a Tera message complains about, capturing its kind and name.
+static SUBJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(Filter|Function|Test)(?: call)? '([^']+)'|`([^`]+)`").unwrap());
+
+/// Wraps a failed render with where it failed: the template file, line and column, the
+/// line itself, and for a missing variable the closest ones the context does define.
+pub fn explain(error: tera::Error, tera: &Tera, template: &str, context: &TeraContext) -> anyhow::Error {
+    let messages: Vec<String> =
+        std::iter::successors(Some(&error as &dyn StdError), |&e| e.source()).map(|e| e.to_string()).collect();
+    let happened_in = messages.iter().find_map(|m| HAPPENED_IN.captures(m)).map(|c| c[1].to_string());
+    let subject = messages
+        .iter()
+        .filter(|m| !m.starts_with("Failed to render"))
+        .find_map(|m| SUBJECT.captures(m))
+        .map(|c| match c.get(3) {
+            Some(variable) => (None, variable.as_str().to_string()),
+            None => (Some(c[1].to_string()), c[2].to_string()),
+        });
+    let Some((kind, name)) = subject else {
+        return error.into();
+    };
+
+    let pattern = match kind.as_deref() {
+        Some("Filter") => format!(r"\|\s*{}\b", regex::escape(&name)),
+        Some("Function") => format!(r"\b{}\s*\(", regex::escape(&name)),
+        Some(_) => format!(r"\bis\s+(?:not\s+)?{}\b", regex::escape(&name)),
+        None => format!(r"\b{}\b", regex::escape(&name)),
+    };
+    let pattern = Regex::new(&pattern).expect("escaped pattern");
+
+    let mut report = Vec::new();
+    let mut candidates: Vec<&str> = happened_in.iter().map(String::as_str).chain([template]).collect();
+    if let Ok(tpl) = tera.get_template(template) {
+        candidates.extend(tpl.parents.iter().map(String::as_str));
+    }
+    let mut others: Vec<&str> = tera.get_template_names().filter(|t| !candidates.contains(t)).collect();
+    others.sort_unstable();
+    candidates.extend(others);
+    let located = candidates.into_iter().find_map(|name| {
+        let path = tera.get_template(name).ok()?.path.clone()?;
+        let source = fs::read_to_string(&path).ok()?;
+        locate(&source, &pattern).map(|(start, end)| snippet(&path, &source, start, end))
+    });
+    report.extend(located);
+    let what = kind.as_deref().map_or("expression".to_string(), str::to_lowercase);
+    report.push(format!("   = failing {}: `{}`", what, name));
+    if kind.is_none() && messages.iter().any(|m| m.contains("not found in context")) {
+        let nearest = nearest_variables(&name, &context.clone().into_json());
+        if !nearest.is_empty() {
+            report.push(format!("   = closest defined variables: {}", nearest.join(", ")));
+        }
+    }
+    anyhow::Error::new(error).context(report.join("\n"))
+}
+
+/// Finds the first match of `pattern` that sits inside a `{{ }}` or `{% %}` tag.
+fn locate(source: &str, pattern: &Regex) -> Option<(usize, usize)> {
+    pattern.find_iter(source).map(|m| (m.start(), m.end())).find(|&(start, _)| {
+        let before = &source[..start];
+        let open = before.rfind("{{").max(before.rfind("{%"));
+        let close = before.rfind("}}").max(before.rfind("%}"));
+        open.is_some() && open > close
+    })
+}
+
+/// Formats `path:line:col` and the source line, underlining `start..end`.
+fn snippet(path: &str, source: &str, start: usize, end: usize) -> String {
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line = source[..start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count() + 1;
+    // Keep tabs so the underline lines up with the source
+    let indent: String = source[line_start..start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let width = source[start..end.min(line_end)].chars().count().max(1);
+    let gutter = line.to_string().len();
+    format!(
+        "  --> {}:{}:{}\n{:gutter$} |\n{} | {}\n{:gutter$} | {}{}",
+        path,
+        line,
+        column,
+        "",
+        line,
+        &source[line_start..line_end],
+        "",
+        indent,
+        "^".repeat(width),
+    )
+}
+
+/// Up to three variables next to the missing part of a dotted path, closest spelling first.
+fn nearest_variables(name: &str, context: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut value = context;
+    let mut missing = None;
+    for segment in name.split('.') {
+        match value.get(segment) {
+            Some(next) => {
+                found.push(segment);
+                value = next;
+            }
+            None => {
+                missing = Some(segment);
+                break;
+            }
+        }
+    }
+    let (Some(missing), Value::Object(keys)) = (missing, value) else {
+        return Vec::new();
+    };
+    let mut keys: Vec<&String> = keys.keys().collect();
+    keys.sort_by_key(|key| (edit_distance(key, missing), key.as_str()));
+    keys.into_iter()
+        .take(3)
+        .map(|key| found.iter().copied().chain([key.as_str()]).collect::<Vec<_>>().join("."))
+        .collect()
+}
+
+/// Levenshtein distance between two words.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_missing_variable() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-template-error-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("page.html");
+        fs::write(&path, "<html>\n  <h1>{{ page.titel }}</h1>\n</html>\n").unwrap();
+        let mut tera = Tera::default();
+        tera.add_template_file(&path, Some("page.html")).unwrap();
+        let mut context = TeraContext::new();
+        context.insert("page", &serde_json::json!({ "title": "Hi", "date": "2024-01-01", "tags": [] }));
+        let error = tera.render("page.html", &context).unwrap_err();
+
+        // Act
+        let report = explain(error, &tera, "page.html", &context).to_string();
+
+        // Assert
+        assert!(report.contains(&format!("{}:2:10", path.display())), "{}", report);
+        assert!(report.contains("2 |   <h1>{{ page.titel }}</h1>\n  |          ^^^^^^^^^^"), "{}", report);
+        assert!(report.contains("closest defined variables: page.title, page.date, page.tags"), "{}", report);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+// Rustic - `rustic test-templates`, rendering templates against fixture contexts
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tera::{Context as TeraContext, Tera};
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::{render, template_error};
+
+/// A file of template test cases under `tests/templates/`, each a `[[case]]` table.
+#[derive(Debug, Deserialize)]
+pub struct Fixture {
+    #[serde(rename = "case")]
+    pub cases: Vec<Case>,
+}
+
+/// One render of a template, and what its output must and mustn't contain.
+#[derive(Debug, Deserialize)]
+pub struct Case {
+    pub name: String,
+    pub template: String,
+    /// Variables added to the ones every page shares: `site`, `data`, `bundles` and `path_prefix`
+    #[serde(default)]
+    pub context: toml::value::Table,
+    #[serde(default)]
+    pub contains: Vec<String>,
+    #[serde(default)]
+    pub not_contains: Vec<String>,
+    /// A file holding the whole expected output, relative to the fixture file
+    pub expected: Option<PathBuf>,
+}
+
+/// Renders `case` on top of the `shared` context, and describes each way the output
+/// falls short; an empty list means the case passed.
+pub fn check(tera: &Tera, shared: &TeraContext, case: &Case, fixture_dir: &Path) -> Result<Vec<String>> {
+    let mut context = shared.clone();
+    context.extend(TeraContext::from_serialize(&case.context)?);
+    let output = match tera.render(&case.template, &context) {
+        Ok(output) => output,
+        Err(e) => return Ok(vec![format!("{:#}", template_error::explain(e, tera, &case.template, &context))]),
+    };
+
+    let mut failures = Vec::new();
+    for text in &case.contains {
+        if !output.contains(text.as_str()) {
+            failures.push(format!("output doesn't contain {:?}", text));
+        }
+    }
+    for text in &case.not_contains {
+        if output.contains(text.as_str()) {
+            failures.push(format!("output contains {:?}", text));
+        }
+    }
+    if let Some(expected) = &case.expected {
+        let path = fixture_dir.join(expected);
+        let expected = fs::read_to_string(&path).with_context(|| format!("Failed to read expected output: {:?}", path))?;
+        // Trailing whitespace and line endings don't count, so editors can't break a test
+        let lines = |text: &str| {
+            let mut lines: Vec<String> = text.lines().map(|line| line.trim_end().to_string()).collect();
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+            lines
+        };
+        let (expected, actual) = (lines(&expected), lines(&output));
+        if expected != actual {
+            let line = expected.iter().zip(&actual).position(|(e, a)| e != a).unwrap_or(expected.len().min(actual.len()));
+            failures.push(format!(
+                "output differs from {:?} at line {}:\n      expected: {}\n      actual:   {}",
+                path,
+                line + 1,
+                expected.get(line).map_or("(end of file)", String::as_str),
+                actual.get(line).map_or("(end of output)", String::as_str),
+            ));
+        }
+    }
+    Ok(failures)
+}
+
+/// Runs every case of every `.toml` fixture under `dir`, failing if any case fails.
+pub fn run(dir: &Path, config: &Config) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("There are no template tests in {:?}", dir);
+    }
+    let (tera, shared) = render::templates_outside_build(config)?;
+
+    let (mut total, mut failed) = (0, 0);
+    for entry in WalkDir::new(dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("toml") {
+            continue;
+        }
+        let raw = fs::read_to_string(path).with_context(|| format!("Failed to read template test: {:?}", path))?;
+        let fixture: Fixture = toml::from_str(&raw).with_context(|| format!("Failed to parse template test: {:?}", path))?;
+        let name = path.strip_prefix(dir).unwrap_or(path).display();
+        for case in &fixture.cases {
+            total += 1;
+            let failures = check(&tera, &shared, case, path.parent().unwrap_or(dir))?;
+            if failures.is_empty() {
+                println!("✅ {}: {}", name, case.name);
+                continue;
+            }
+            failed += 1;
+            eprintln!("⚠️  {}: {}", name, case.name);
+            for failure in failures {
+                eprintln!("    {}", failure);
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} template test(s) failed", failed, total);
+    }
+    println!("✅ {} template test(s) passed", total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        // Arrange
+        let mut tera = Tera::default();
+        tera.add_raw_template("title.html", "<h1>{{ title }}</h1>\n<p>{{ site.title }}</p>\n").unwrap();
+        let mut shared = TeraContext::new();
+        shared.insert("site", &serde_json::json!({ "title": "Notes" }));
+        let fixture: Fixture = toml::from_str(
+            "[[case]]\nname = \"shows the title\"\ntemplate = \"title.html\"\ncontext = { title = \"Hello\" }\n\
+             contains = [\"<h1>Hello</h1>\"]\nnot_contains = [\"Notes\"]\n",
+        )
+        .unwrap();
+
+        // Act
+        let failures = check(&tera, &shared, &fixture.cases[0], Path::new(".")).unwrap();
+
+        // Assert
+        assert_eq!(failures, ["output contains \"Notes\""]);
+    }
+}
@@ -0,0 +1,201 @@
+// Rustic - `--unused-templates`, finding templates, partials and macros no page reaches
+
+use std::collections::BTreeSet;
+use tera::ast::{Expr, ExprVal, FunctionCall, MacroCall, Node};
+use tera::{Tera, Template};
+
+/// Templates rendered by commands other than the build, which a build never reaches.
+const OTHER_ENTRY_POINTS: &[&str] = &["newsletter.html"];
+
+/// Templates and macros reachable from the ones the build rendered.
+struct Reach<'a> {
+    tera: &'a Tera,
+    templates: BTreeSet<&'a str>,
+    /// `(file, macro)` pairs that were called
+    macros: BTreeSet<(&'a str, &'a str)>,
+}
+
+impl<'a> Reach<'a> {
+    fn template(&mut self, name: &str) {
+        let Ok(tpl) = self.tera.get_template(name) else {
+            return;
+        };
+        if !self.templates.insert(&tpl.name) {
+            return;
+        }
+        for parent in &tpl.parents {
+            self.template(parent);
+        }
+        self.nodes(tpl, &tpl.ast);
+    }
+
+    /// Walks the markup of `tpl`; macro definitions are only walked once they are called.
+    fn nodes(&mut self, tpl: &'a Template, nodes: &'a [Node]) {
+        for node in nodes {
+            match node {
+                Node::VariableBlock(_, expr) => self.expr(tpl, expr),
+                // Tera renders the first of the listed templates that exists
+                Node::Include(_, names, _) => {
+                    if let Some(name) = names.iter().find(|name| self.tera.get_template(name).is_ok()) {
+                        self.template(name);
+                    }
+                }
+                Node::ImportMacro(_, file, _) => self.template(file),
+                Node::Set(_, set) => self.expr(tpl, &set.value),
+                Node::FilterSection(_, section, _) => {
+                    self.call(tpl, &section.filter);
+                    self.nodes(tpl, &section.body);
+                }
+                Node::Block(_, block, _) => self.nodes(tpl, &block.body),
+                Node::Forloop(_, forloop, _) => {
+                    self.expr(tpl, &forloop.container);
+                    self.nodes(tpl, &forloop.body);
+                    if let Some(body) = &forloop.empty_body {
+                        self.nodes(tpl, body);
+                    }
+                }
+                Node::If(branches, _) => {
+                    for (_, condition, body) in &branches.conditions {
+                        self.expr(tpl, condition);
+                        self.nodes(tpl, body);
+                    }
+                    if let Some((_, body)) = &branches.otherwise {
+                        self.nodes(tpl, body);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn expr(&mut self, tpl: &'a Template, expr: &'a Expr) {
+        match &expr.val {
+            ExprVal::Math(math) => {
+                self.expr(tpl, &math.lhs);
+                self.expr(tpl, &math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.expr(tpl, &logic.lhs);
+                self.expr(tpl, &logic.rhs);
+            }
+            ExprVal::In(test) => {
+                self.expr(tpl, &test.lhs);
+                self.expr(tpl, &test.rhs);
+            }
+            ExprVal::Test(test) => test.args.iter().for_each(|arg| self.expr(tpl, arg)),
+            ExprVal::Array(items) => items.iter().for_each(|item| self.expr(tpl, item)),
+            ExprVal::MacroCall(call) => self.macro_call(tpl, call),
+            ExprVal::FunctionCall(call) => self.call(tpl, call),
+            _ => {}
+        }
+        for filter in &expr.filters {
+            self.call(tpl, filter);
+        }
+    }
+
+    /// Follows `cached_include(template="...")` calls, whose partial is named by a string literal.
+    fn call(&mut self, tpl: &'a Template, call: &'a FunctionCall) {
+        for arg in call.args.values() {
+            self.expr(tpl, arg);
+        }
+        if call.name == "cached_include"
+            && let Some(Expr { val: ExprVal::String(name), .. }) = call.args.get("template")
+        {
+            self.template(name);
+        }
+    }
+
+    fn macro_call(&mut self, tpl: &'a Template, call: &'a MacroCall) {
+        for arg in call.args.values() {
+            self.expr(tpl, arg);
+        }
+        let file = if call.namespace == "self" {
+            Some(tpl.name.as_str())
+        } else {
+            tpl.imported_macro_files.iter().find(|(_, namespace)| *namespace == call.namespace).map(|(file, _)| file.as_str())
+        };
+        let Some(file) = file.and_then(|file| self.tera.get_template(file).ok()) else {
+            return;
+        };
+        self.template(&file.name);
+        if self.macros.insert((&file.name, &call.name))
+            && let Some(definition) = file.macros.get(&call.name)
+        {
+            self.nodes(file, &definition.body);
+        }
+    }
+}
+
+/// Templates, and `file::macro` names, that rendering `rendered` never reaches.
+pub fn unused(tera: &Tera, rendered: &BTreeSet<String>) -> (Vec<String>, Vec<String>) {
+    let mut reach = Reach { tera, templates: BTreeSet::new(), macros: BTreeSet::new() };
+    for name in rendered.iter().map(String::as_str).chain(OTHER_ENTRY_POINTS.iter().copied()) {
+        reach.template(name);
+    }
+
+    let mut names: Vec<&str> = tera.get_template_names().collect();
+    names.sort_unstable();
+    let templates = names.iter().filter(|name| !reach.templates.contains(*name)).map(|name| name.to_string()).collect();
+    let mut macros = Vec::new();
+    for name in names.iter().filter(|name| reach.templates.contains(*name)) {
+        let tpl = tera.get_template(name).expect("listed template");
+        let mut defined: Vec<&String> = tpl.macros.keys().collect();
+        defined.sort_unstable();
+        for macro_name in defined {
+            if !reach.macros.contains(&(tpl.name.as_str(), macro_name.as_str())) {
+                macros.push(format!("{}::{}", tpl.name, macro_name));
+            }
+        }
+    }
+    (templates, macros)
+}
+
+/// Lists what `unused` finds, so theme maintainers can prune it.
+pub fn print_unused(tera: &Tera, rendered: &BTreeSet<String>) {
+    let (templates, macros) = unused(tera, rendered);
+    if templates.is_empty() && macros.is_empty() {
+        println!("✅ Every template and macro is used");
+        return;
+    }
+    if !templates.is_empty() {
+        println!("\nUnused templates:");
+        for template in &templates {
+            println!("  {}", template);
+        }
+    }
+    if !macros.is_empty() {
+        println!("\nUnused macros:");
+        for name in &macros {
+            println!("  {}", name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused() {
+        // Arrange
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base.html", "<body>{% block main %}{% endblock %}{% include \"partials/footer.html\" %}</body>"),
+            ("partials/footer.html", "{{ cached_include(template=\"partials/nav.html\") }}"),
+            ("partials/nav.html", "<nav></nav>"),
+            ("partials/old.html", "<aside></aside>"),
+            ("macros.html", "{% macro card() %}{{ self::badge() }}{% endmacro %}{% macro badge() %}{% endmacro %}{% macro unused() %}{% endmacro %}"),
+            ("template.html", "{% extends \"base.html\" %}{% import \"macros.html\" as m %}{% block main %}{{ m::card() }}{% endblock %}"),
+            ("taxonomy.html", "{% extends \"base.html\" %}"),
+        ])
+        .unwrap();
+        let rendered = BTreeSet::from(["template.html".to_string()]);
+
+        // Act
+        let (templates, macros) = unused(&tera, &rendered);
+
+        // Assert
+        assert_eq!(templates, ["partials/old.html", "taxonomy.html"]);
+        assert_eq!(macros, ["macros.html::unused"]);
+    }
+}
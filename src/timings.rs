@@ -0,0 +1,101 @@
+// Rustic - per-stage build timings for `--timings`
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How many of the slowest pages are listed.
+const SLOWEST_PAGES: usize = 10;
+
+/// Time spent in each build stage, summed across pages, plus the time taken by each page.
+#[derive(Debug, Default)]
+pub struct Timings {
+    stages: RefCell<Vec<(&'static str, Duration)>>,
+    /// The slowest pages so far, slowest first
+    pages: RefCell<Vec<(PathBuf, Duration)>>,
+}
+
+/// Attributes the time since the previous mark to the stage named at each mark.
+pub struct Lap<'a> {
+    timings: &'a Timings,
+    last: Instant,
+}
+
+impl Timings {
+    /// Starts measuring from now.
+    pub fn lap(&self) -> Lap<'_> {
+        Lap { timings: self, last: Instant::now() }
+    }
+
+    /// Keeps the page if it is among the slowest so far; memory stays bounded on huge sites.
+    pub fn record_page(&self, path: PathBuf, elapsed: Duration) {
+        let mut pages = self.pages.borrow_mut();
+        let position = pages.partition_point(|(_, slower)| *slower >= elapsed);
+        if position < SLOWEST_PAGES {
+            pages.insert(position, (path, elapsed));
+            pages.truncate(SLOWEST_PAGES);
+        }
+    }
+
+    fn record(&self, stage: &'static str, elapsed: Duration) {
+        let mut stages = self.stages.borrow_mut();
+        match stages.iter_mut().find(|(name, _)| *name == stage) {
+            Some((_, total)) => *total += elapsed,
+            None => stages.push((stage, elapsed)),
+        }
+    }
+
+    /// Prints stage totals in the order they first ran, then the slowest pages.
+    pub fn print(&self) {
+        let stages = self.stages.borrow();
+        let total: Duration = stages.iter().map(|(_, elapsed)| *elapsed).sum();
+        println!("\nTimings:");
+        for (stage, elapsed) in stages.iter() {
+            let share = 100.0 * elapsed.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON);
+            println!("  {:<16} {:>10.1} ms {:>5.1}%", stage, elapsed.as_secs_f64() * 1000.0, share);
+        }
+        println!("  {:<16} {:>10.1} ms", "total", total.as_secs_f64() * 1000.0);
+
+        println!("\nSlowest pages:");
+        for (path, elapsed) in self.pages.borrow().iter() {
+            println!("  {:<40} {:>8.1} ms", path.display(), elapsed.as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+impl Lap<'_> {
+    /// Adds the time since the last mark to `stage`.
+    pub fn mark(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.timings.record(stage, now - self.last);
+        self.last = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages_accumulate() {
+        // Arrange
+        let timings = Timings::default();
+
+        // Act
+        let mut lap = timings.lap();
+        lap.mark("parse");
+        lap.mark("render");
+        lap.mark("parse");
+
+        for ms in 0..20 {
+            timings.record_page(PathBuf::from(format!("{}.md", ms)), Duration::from_millis(ms));
+        }
+
+        // Assert
+        let stages: Vec<&str> = timings.stages.borrow().iter().map(|(name, _)| *name).collect();
+        assert_eq!(stages, ["parse", "render"]);
+        let pages = timings.pages.borrow();
+        assert_eq!(pages.len(), SLOWEST_PAGES);
+        assert_eq!(pages[0].1, Duration::from_millis(19));
+    }
+}
@@ -0,0 +1,160 @@
+// Rustic - static assets of the built site that nothing refers to
+
+use anyhow::Result;
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use walkdir::WalkDir;
+
+use crate::graph::{normalize, percent_decode, unescape_html};
+use crate::postprocess::{resolve_local_url, PageInfo};
+
+/// Matches `url(...)` and `@import "..."` references in CSS.
+static CSS_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"url\(\s*['"]?([^'")]+?)['"]?\s*\)|@import\s+['"]([^'"]+)['"]"#).unwrap());
+
+/// Matches quoted strings in scripts and JSON, any of which may name an asset.
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"["']([^"'\s<>]+)["']"#).unwrap());
+
+/// Files that are read for references to other files.
+const TEXT_EXTENSIONS: &[&str] = &["html", "htm", "css", "js", "mjs", "json", "webmanifest", "xml", "svg"];
+
+/// Files under `static/` that no page refers to, directly or through a stylesheet,
+/// script or manifest that is itself referred to.
+pub fn unused(out_dir: &Path) -> Result<Vec<String>> {
+    let static_dir = normalize(&out_dir.join("static"));
+    let mut assets = BTreeSet::new();
+    let mut queue = Vec::new();
+    for entry in WalkDir::new(out_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = normalize(entry.path());
+        if path.starts_with(&static_dir) {
+            assets.insert(path);
+        } else if is_text(&path) {
+            // Pages, feeds and other generated files are where references start
+            queue.push(path);
+        }
+    }
+
+    let mut reached = HashSet::new();
+    while let Some(file) = queue.pop() {
+        for target in references(&file, out_dir)? {
+            let target = normalize(&target);
+            if reached.insert(target.clone()) && assets.contains(&target) && is_text(&target) {
+                queue.push(target);
+            }
+        }
+    }
+
+    Ok(assets
+        .iter()
+        .filter(|asset| !reached.contains(*asset))
+        .map(|asset| asset.strip_prefix(normalize(out_dir)).unwrap_or(asset).to_string_lossy().replace('\\', "/"))
+        .collect())
+}
+
+fn is_text(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()).is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext))
+}
+
+/// The files `file` refers to, whether they exist or not.
+fn references(file: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+    let Ok(text) = fs::read_to_string(file) else {
+        return Ok(Vec::new());
+    };
+    let urls = match file.extension().and_then(|s| s.to_str()) {
+        Some("html" | "htm") => html_urls(&text)?,
+        Some("css") => css_urls(&text),
+        _ => QUOTED.captures_iter(&text).map(|caps| caps[1].to_string()).collect(),
+    };
+    let page = PageInfo { out_dir, output_path: file, head: &[], entry: &[] };
+    Ok(urls.iter().filter_map(|url| resolve(url, &page)).collect())
+}
+
+/// Resolves a reference to a path in the output; absolute URLs count by their path,
+/// since the site's own base URL isn't known here.
+fn resolve(url: &str, page: &PageInfo) -> Option<PathBuf> {
+    let url = percent_decode(&unescape_html(url.trim()));
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').and_then(|slash| resolve_local_url(&rest[slash..], page)),
+        None => resolve_local_url(&url, page),
+    }
+}
+
+fn css_urls(css: &str) -> Vec<String> {
+    CSS_URL.captures_iter(css).filter_map(|caps| caps.get(1).or(caps.get(2))).map(|m| m.as_str().to_string()).collect()
+}
+
+/// Every attribute value of a page that could be a URL, plus the references in its
+/// inline styles and scripts.
+fn html_urls(html: &str) -> Result<Vec<String>> {
+    let urls = RefCell::new(Vec::new());
+    let styles = RefCell::new(String::new());
+    let scripts = RefCell::new(String::new());
+    rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("*", |el| {
+                let mut urls = urls.borrow_mut();
+                for attribute in el.attributes() {
+                    let value = attribute.value();
+                    match attribute.name().as_str() {
+                        "srcset" | "imagesrcset" => {
+                            urls.extend(value.split(',').filter_map(|candidate| candidate.split_whitespace().next()).map(String::from))
+                        }
+                        "style" => urls.extend(css_urls(&value)),
+                        _ => urls.push(value),
+                    }
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(text!("style", |chunk| {
+                styles.borrow_mut().push_str(chunk.as_str());
+                Ok(())
+            }))
+            .append_element_content_handler(text!("script", |chunk| {
+                scripts.borrow_mut().push_str(chunk.as_str());
+                Ok(())
+            })),
+    )?;
+    let mut urls = urls.into_inner();
+    urls.extend(css_urls(&styles.into_inner()));
+    urls.extend(QUOTED.captures_iter(&scripts.into_inner()).map(|caps| caps[1].to_string()));
+    Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused() {
+        // Arrange
+        let out_dir = std::env::temp_dir().join(format!("rustic-unused-assets-{}", std::process::id()));
+        fs::create_dir_all(out_dir.join("static/fonts")).unwrap();
+        fs::create_dir_all(out_dir.join("notes")).unwrap();
+        fs::write(
+            out_dir.join("notes/rust.html"),
+            "<link rel=\"stylesheet\" href=\"../static/site.css\"><img srcset=\"/static/a%20b.png 1x, /static/big.png 2x\">",
+        )
+        .unwrap();
+        fs::write(out_dir.join("static/site.css"), "@font-face { src: url('fonts/inter.woff2'); }").unwrap();
+        fs::write(out_dir.join("static/old.css"), "@font-face { src: url('fonts/legacy.woff2'); }").unwrap();
+        for file in ["a b.png", "big.png", "fonts/inter.woff2", "fonts/legacy.woff2", "logo.svg"] {
+            fs::write(out_dir.join("static").join(file), "").unwrap();
+        }
+
+        // Act
+        let unused = unused(&out_dir).unwrap();
+
+        // Assert
+        assert_eq!(unused, ["static/fonts/legacy.woff2", "static/logo.svg", "static/old.css"]);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+// Rustic - webmention endpoints in page heads, and `rustic webmention send` for outbound links
+
+use anyhow::{bail, Context, Result};
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use ureq::ResponseExt;
+use url::Url;
+use walkdir::WalkDir;
+
+use crate::feed::absolute_url;
+use crate::shortcodes::escape_attr;
+
+/// Settings for receiving and sending webmentions, declared under `[webmention]` in rustic.toml.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct WebmentionConfig {
+    /// Endpoint that receives mentions of the site's pages, e.g. one from webmention.io
+    pub endpoint: Option<String>,
+    /// XML-RPC endpoint for older blogs that only send pingbacks
+    pub pingback: Option<String>,
+    /// Only links inside elements matching this selector are mentioned
+    pub content_selector: String,
+    /// Where `rustic webmention send` records what it has sent, so reruns only send changes
+    pub sent_file: PathBuf,
+}
+
+impl Default for WebmentionConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            pingback: None,
+            content_selector: "article".to_string(),
+            sent_file: PathBuf::from("webmentions.json"),
+        }
+    }
+}
+
+/// Tags advertising the configured endpoints, for every page's `<head>`.
+pub fn head_links(config: &WebmentionConfig) -> Vec<String> {
+    let mut head = Vec::new();
+    if let Some(endpoint) = &config.endpoint {
+        head.push(format!("<link rel=\"webmention\" href=\"{}\" />", escape_attr(endpoint)));
+    }
+    if let Some(pingback) = &config.pingback {
+        head.push(format!("<link rel=\"pingback\" href=\"{}\" />", escape_attr(pingback)));
+    }
+    head
+}
+
+/// Targets already mentioned, by source page URL.
+type Sent = BTreeMap<String, BTreeSet<String>>;
+
+/// Sends a webmention for each outbound link that was added to or removed from a page
+/// of the built site since the last run, as recorded in `config.sent_file`.
+///
+/// With `dry_run`, endpoints are discovered but nothing is sent or recorded.
+pub fn send(config: &WebmentionConfig, base_url: &str, out_dir: &Path, dry_run: bool) -> Result<()> {
+    if base_url.is_empty() {
+        bail!("Sending webmentions needs `base_url` to be set in rustic.toml");
+    }
+    if !out_dir.is_dir() {
+        bail!("{:?} doesn't exist; build the site first", out_dir);
+    }
+    let mut sent: Sent = match fs::read_to_string(&config.sent_file) {
+        Ok(json) => serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse sent webmentions: {:?}", config.sent_file))?,
+        Err(_) => Sent::new(),
+    };
+    let agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(15)))
+        .http_status_as_error(false)
+        .build()
+        .new_agent();
+    let own_host = Url::parse(base_url).ok().and_then(|url| url.host_str().map(str::to_string));
+
+    let (mut attempted, mut delivered) = (0, 0);
+    for entry in WalkDir::new(out_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("html") {
+            continue;
+        }
+        let html = fs::read_to_string(path).with_context(|| format!("Failed to read page: {:?}", path))?;
+        let relative = path.strip_prefix(out_dir)?.to_string_lossy().replace('\\', "/");
+        let source = absolute_url(base_url, &relative);
+        let links: BTreeSet<String> = outbound_links(&html, &config.content_selector)?
+            .into_iter()
+            .filter(|link| Url::parse(link).ok().and_then(|url| url.host_str().map(str::to_string)) != own_host)
+            .collect();
+
+        // Removed links are mentioned too, so the target can drop the mention
+        let previous = sent.get(&source).cloned().unwrap_or_default();
+        let mut delivered_all = true;
+        for target in links.symmetric_difference(&previous) {
+            attempted += 1;
+            match mention(&agent, &source, target, dry_run) {
+                Ok(Some(endpoint)) => {
+                    delivered += 1;
+                    println!("Mentioned {} at {}", target, endpoint);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    delivered_all = false;
+                    eprintln!("⚠️  Failed to mention {} from {}: {:#}", target, source, e);
+                }
+            }
+        }
+        // Pages with failures are retried in full next time
+        if delivered_all {
+            sent.insert(source, links);
+        }
+    }
+
+    if dry_run {
+        println!("Found {} link(s) to mention; nothing was sent", attempted);
+        return Ok(());
+    }
+    sent.retain(|_, targets| !targets.is_empty());
+    fs::write(&config.sent_file, serde_json::to_string_pretty(&sent)?)
+        .with_context(|| format!("Failed to write sent webmentions: {:?}", config.sent_file))?;
+    println!("✅ Sent {} of {} webmention(s)", delivered, attempted);
+    Ok(())
+}
+
+/// Discovers `target`'s endpoint and notifies it of `source`, returning the endpoint
+/// used, or `None` when the target doesn't accept webmentions.
+fn mention(agent: &ureq::Agent, source: &str, target: &str, dry_run: bool) -> Result<Option<String>> {
+    let Some(endpoint) = discover(agent, target)? else {
+        return Ok(None);
+    };
+    if dry_run {
+        return Ok(Some(endpoint));
+    }
+    let response = agent.post(&endpoint).send_form([("source", source), ("target", target)])?;
+    if !response.status().is_success() {
+        bail!("{} answered {}", endpoint, response.status());
+    }
+    Ok(Some(endpoint))
+}
+
+/// Finds a page's webmention endpoint in its `Link` headers or its HTML.
+fn discover(agent: &ureq::Agent, target: &str) -> Result<Option<String>> {
+    let mut response = agent.get(target).call()?;
+    let base = Url::parse(&response.get_uri().to_string())?;
+    let from_header = response
+        .headers()
+        .get_all("link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| endpoint_from_link_header(value).map(str::to_string));
+    let href = match from_header {
+        Some(href) => Some(href),
+        None if response.status().is_success() => {
+            let html = response.body_mut().with_config().limit(2 * 1024 * 1024).read_to_string()?;
+            endpoint_from_html(&html)?
+        }
+        None => None,
+    };
+    Ok(href.and_then(|href| base.join(&href).ok()).map(String::from))
+}
+
+/// The URL of the first `rel="webmention"` entry in a `Link` header.
+fn endpoint_from_link_header(value: &str) -> Option<&str> {
+    value.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let rel = params.split(';').find_map(|param| param.trim().strip_prefix("rel="))?;
+        let is_webmention = rel.trim_matches('"').split_whitespace().any(|r| r == "webmention");
+        is_webmention.then(|| url.trim().trim_start_matches('<').trim_end_matches('>'))
+    })
+}
+
+/// The `href` of the first `<link>` or `<a>` with `rel="webmention"`.
+fn endpoint_from_html(html: &str) -> Result<Option<String>> {
+    let endpoint = RefCell::new(None);
+    rewrite_str(
+        html,
+        RewriteStrSettings::new().append_element_content_handler(element!("link[rel~=webmention][href], a[rel~=webmention][href]", |el| {
+            endpoint.borrow_mut().get_or_insert(el.get_attribute("href").unwrap_or_default());
+            Ok(())
+        })),
+    )?;
+    Ok(endpoint.into_inner())
+}
+
+/// Absolute http(s) links inside elements matching `selector`.
+fn outbound_links(html: &str, selector: &str) -> Result<BTreeSet<String>> {
+    let links = RefCell::new(BTreeSet::new());
+    let selector = format!("{} a[href]", selector);
+    rewrite_str(
+        html,
+        RewriteStrSettings::new().append_element_content_handler(element!(&selector, |el| {
+            let href = el.get_attribute("href").unwrap_or_default();
+            if href.starts_with("http://") || href.starts_with("https://") {
+                links.borrow_mut().insert(href);
+            }
+            Ok(())
+        })),
+    )?;
+    Ok(links.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_and_outbound_links() {
+        // Arrange
+        let html = r#"<html><head><link rel="pingback" href="/xmlrpc"><link rel="webmention" href="/mentions"></head>
+            <body><nav><a href="https://github.com/me">GitHub</a></nav>
+            <article><a href="https://blog.example/post">A post</a> <a href="other.html">Local</a></article></body></html>"#;
+
+        // Act
+        let header = endpoint_from_link_header(r#"<https://a.example/>; rel="preload", <https://a.example/wm>; rel="webmention""#);
+        let endpoint = endpoint_from_html(html).unwrap();
+        let links = outbound_links(html, "article").unwrap();
+
+        // Assert
+        assert_eq!(header, Some("https://a.example/wm"));
+        assert_eq!(endpoint.as_deref(), Some("/mentions"));
+        assert_eq!(links.into_iter().collect::<Vec<_>>(), ["https://blog.example/post"]);
+    }
+}
@@ -0,0 +1,335 @@
+// Rustic - `[[Page Name]]` wiki links resolved against the site's pages
+
+use anyhow::Result;
+use gray_matter::{engine::YAML, Matter};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+use walkdir::WalkDir;
+
+use crate::shortcodes::escape_attr;
+use crate::slug::{collisions, slugify};
+
+/// Matches `[[Target]]`, `[[Target|Label]]` or an Obsidian `![[embed.png]]`.
+static WIKI_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(!?)\[\[([^\[\]|\n]+?)(?:\|([^\[\]\n]+?))?\]\]").unwrap());
+
+/// Extensions rendered as images when embedded with `![[...]]`.
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "webp", "svg", "avif"];
+
+/// Maps page titles and file names, both slugified, to output paths relative to the site root.
+#[derive(Debug, Default)]
+pub struct Index {
+    titles: HashMap<String, String>,
+    file_names: HashMap<String, String>,
+    /// Pages linking to each output path, as (output path, title) pairs
+    backlinks: HashMap<String, Vec<(String, String)>>,
+    /// Non-markdown files in the content directory, by lowercase file name
+    attachments: HashMap<String, String>,
+    /// Slugified titles and file names of drafts and scheduled or expired pages
+    unpublished: HashSet<String>,
+}
+
+/// A page that links to the one being rendered, as exposed to templates.
+#[derive(Debug, Serialize)]
+pub struct Backlink {
+    pub title: String,
+    pub url: String,
+}
+
+impl Index {
+    /// Reads the title and outgoing wiki links of every markdown page under `input_dir`.
+    ///
+    /// Pages `published` rejects can't be linked to and add no backlinks, so drafts and
+    /// scheduled pages don't give away their titles. Unlisted pages are left out too:
+    /// they are published only at their token URL, which links must not give away.
+    pub fn build(input_dir: &Path, published: impl Fn(&Path) -> bool) -> Result<Self> {
+        let matter = Matter::<YAML>::new();
+        let mut index = Self::default();
+        let mut sources = Vec::new();
+
+        for entry in WalkDir::new(input_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            let relative = path.strip_prefix(input_dir)?;
+            if path.extension().and_then(|s| s.to_str()) != Some("md") {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                index.attachments.insert(name, relative.to_string_lossy().replace('\\', "/"));
+                continue;
+            }
+            let output = relative.with_extension("html");
+            let output = output.to_string_lossy().replace('\\', "/");
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+
+            // Pages without front matter fail later anyway
+            let Ok(raw) = fs::read_to_string(path) else {
+                continue;
+            };
+            let parsed = matter.parse(&raw);
+            let fields = parsed.data.and_then(|data| data.as_hashmap().ok()).unwrap_or_default();
+            let title = fields.get("title").and_then(|title| title.as_string().ok());
+            // Links to them are reported as broken rather than pointing at a page never written
+            if fields.get("unlisted").and_then(|unlisted| unlisted.as_bool().ok()).unwrap_or(false) {
+                continue;
+            }
+            if !published(path) {
+                index.unpublished.insert(slugify(stem));
+                index.unpublished.extend(title.as_deref().map(slugify));
+                continue;
+            }
+            index.file_names.entry(slugify(stem)).or_insert_with(|| output.clone());
+            if let Some(title) = &title {
+                index.titles.entry(slugify(title)).or_insert_with(|| output.clone());
+            }
+            let title = title.unwrap_or_else(|| stem.to_string());
+            sources.push((output, title, targets(&parsed.content)));
+        }
+
+        // Pages whose titles slugify alike can't all be reached by a title link
+        let titles = collisions(sources.iter().map(|(output, title, _)| (title.as_str(), output.as_str())));
+        for (slug, pages) in titles {
+            let listed: Vec<String> = pages.iter().map(|(title, output)| format!("{:?} ({})", title, output)).collect();
+            eprintln!(
+                "⚠️  Titles collide as `{}`: {}. Wiki links resolve to {}; rename a page or link by file name instead",
+                slug,
+                listed.join(", "),
+                pages[0].1
+            );
+        }
+
+        for (output, title, targets) in sources {
+            let mut linked: Vec<String> = targets
+                .iter()
+                .filter_map(|target| index.resolve(target))
+                .filter(|target| *target != output)
+                .map(str::to_string)
+                .collect();
+            linked.sort();
+            linked.dedup();
+            for target in linked {
+                index.backlinks.entry(target).or_default().push((output.clone(), title.clone()));
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Looks up a page by title, then by file name, ignoring any `#heading` suffix.
+    pub fn resolve(&self, target: &str) -> Option<&str> {
+        let slug = slugify(target.split('#').next().unwrap_or(target));
+        self.titles.get(&slug).or_else(|| self.file_names.get(&slug)).map(String::as_str)
+    }
+
+    /// Whether `target` names a page that exists but isn't published in this build.
+    fn is_unpublished(&self, target: &str) -> bool {
+        self.resolve(target).is_none() && self.unpublished.contains(&slugify(target.split('#').next().unwrap_or(target)))
+    }
+
+    /// Looks up a file in the content directory by name, wherever it is stored.
+    fn attachment(&self, name: &str) -> Option<&str> {
+        let name = name.rsplit('/').next().unwrap_or(name).to_lowercase();
+        self.attachments.get(&name).map(String::as_str)
+    }
+
+    /// Lists the pages linking to `output`, sorted by title, with URLs relative to `depth`.
+    pub fn backlinks(&self, output: &str, depth: usize) -> Vec<Backlink> {
+        let prefix = "../".repeat(depth);
+        let mut backlinks: Vec<Backlink> = self
+            .backlinks
+            .get(output)
+            .into_iter()
+            .flatten()
+            .map(|(url, title)| Backlink { title: title.clone(), url: format!("{}{}", prefix, url) })
+            .collect();
+        backlinks.sort_by(|a, b| a.title.cmp(&b.title));
+        backlinks
+    }
+}
+
+/// Collects the targets of wiki links outside code blocks.
+fn targets(markdown: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    for_each_prose_line(markdown, |line| {
+        targets.extend(WIKI_LINK.captures_iter(line).map(|caps| caps[2].trim().to_string()));
+        line.to_string()
+    });
+    targets
+}
+
+/// Splits `markdown` into runs of prose and fenced code blocks, fences included, each
+/// marked with whether it is prose. The runs join back into `markdown` unchanged.
+pub fn prose_runs(markdown: &str) -> Vec<(bool, &str)> {
+    let mut runs = Vec::new();
+    let mut fence: Option<&str> = None;
+    let (mut start, mut pos) = (0, 0);
+
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => {
+                fence = None;
+                runs.push((false, &markdown[start..pos + line.len()]));
+                start = pos + line.len();
+            }
+            Some(_) => {}
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                if pos > start {
+                    runs.push((true, &markdown[start..pos]));
+                }
+                fence = Some(&trimmed[..3]);
+                start = pos;
+            }
+            None => {}
+        }
+        pos += line.len();
+    }
+
+    if start < markdown.len() {
+        runs.push((fence.is_none(), &markdown[start..]));
+    }
+    runs
+}
+
+/// Applies `f` to every line outside fenced code blocks, returning the rewritten text.
+fn for_each_prose_line(markdown: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut fence: Option<&str> = None;
+    let mut out = Vec::new();
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            out.push(line.to_string());
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            out.push(line.to_string());
+        } else {
+            out.push(f(line));
+        }
+    }
+
+    out.join("\n")
+}
+
+/// Replaces wiki links outside code blocks with markdown links relative to `depth`.
+///
+/// With `embeds`, Obsidian's `![[file.png]]` becomes an image (or a link, for other
+/// files) pointing at the attachment wherever it sits in the content directory.
+/// Unresolved links are reported and left as their label text.
+pub fn expand(markdown: &str, page: &Path, depth: usize, index: &Index, embeds: bool) -> String {
+    let prefix = "../".repeat(depth);
+    for_each_prose_line(markdown, |line| {
+        WIKI_LINK
+            .replace_all(line, |caps: &Captures| {
+                let bang = &caps[1];
+                let target = caps[2].trim();
+                let page_name = target.split('#').next().unwrap_or(target);
+                let label = caps.get(3).map_or(page_name, |l| l.as_str().trim());
+
+                if let Some(url) = index.attachment(target).filter(|_| embeds && !bang.is_empty()) {
+                    return embed(target, caps.get(3).map(|l| l.as_str().trim()), &prefix, url);
+                }
+                match index.resolve(target) {
+                    Some(url) if embeds => format!("[{}](<{}{}>)", label, prefix, url),
+                    Some(url) => format!("{}[{}](<{}{}>)", bang, label, prefix, url),
+                    None if index.is_unpublished(target) => {
+                        eprintln!("⚠️  Wiki link [[{}]] in {:?} points at a page that isn't published", target, page);
+                        label.to_string()
+                    }
+                    None => {
+                        eprintln!("⚠️  Broken wiki link [[{}]] in {:?}", target, page);
+                        label.to_string()
+                    }
+                }
+            })
+            .into_owned()
+    })
+}
+
+/// Renders an embedded attachment; for images a numeric label is the display width.
+fn embed(target: &str, label: Option<&str>, prefix: &str, url: &str) -> String {
+    let extension = target.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return format!("[{}](<{}{}>)", label.unwrap_or(target), prefix, url);
+    }
+    match label.and_then(|l| l.split('x').next()?.parse::<u32>().ok()) {
+        Some(width) => format!(
+            "<img src=\"{}{}\" alt=\"{}\" width=\"{}\" />",
+            prefix,
+            escape_attr(url),
+            escape_attr(target),
+            width
+        ),
+        None => format!("![{}](<{}{}>)", label.unwrap_or(target), prefix, url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_wiki_links() {
+        // Arrange
+        let mut index = Index::default();
+        index.titles.insert("rust-notes".to_string(), "notes/rust-notes.html".to_string());
+
+        // Act
+        let markdown = "See [[Rust Notes]] and [[rust-notes|these]], not [[Missing]].\n```\n[[Rust Notes]]\n```";
+        let expanded = expand(markdown, Path::new("index.md"), 1, &index, false);
+
+        // Assert
+        assert_eq!(
+            expanded,
+            "See [Rust Notes](<../notes/rust-notes.html>) and [these](<../notes/rust-notes.html>), not Missing.\n```\n[[Rust Notes]]\n```"
+        );
+        assert_eq!(targets(markdown), ["Rust Notes", "rust-notes", "Missing"]);
+    }
+
+    #[test]
+    fn test_build_leaves_out_unpublished_pages() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("rustic-wikilinks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.md"), "---\ntitle: A\n---\nSee [[B]] and [[Secret Plans]].\n").unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: B\n---\nB\n").unwrap();
+        fs::write(dir.join("plans.md"), "---\ntitle: Secret Plans\ndraft: true\n---\nSee [[B]].\n").unwrap();
+        fs::write(dir.join("draft.md"), "---\ntitle: Review Copy\nunlisted: true\n---\nSee [[B]].\n").unwrap();
+
+        // Act
+        let index = Index::build(&dir, |path| !path.ends_with("plans.md")).unwrap();
+
+        // Assert
+        let backlinks: Vec<String> = index.backlinks("b.html", 0).into_iter().map(|b| b.title).collect();
+        assert_eq!(backlinks, ["A"]);
+        assert!(index.resolve("Secret Plans").is_none());
+        assert!(index.resolve("Review Copy").is_none() && index.resolve("draft").is_none());
+        assert!(index.is_unpublished("secret plans") && index.is_unpublished("plans"));
+        assert_eq!(expand("[[Secret Plans]]", Path::new("a.md"), 0, &index, false), "Secret Plans");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_obsidian_embeds() {
+        // Arrange
+        let mut index = Index::default();
+        index.attachments.insert("diagram.png".to_string(), "attachments/diagram.png".to_string());
+        index.attachments.insert("paper.pdf".to_string(), "attachments/paper.pdf".to_string());
+
+        // Act
+        let expanded = expand("![[Diagram.png]] ![[diagram.png|300]] ![[paper.pdf]]", Path::new("a.md"), 0, &index, true);
+
+        // Assert
+        assert_eq!(
+            expanded,
+            "![Diagram.png](<attachments/diagram.png>) <img src=\"attachments/diagram.png\" alt=\"diagram.png\" width=\"300\" /> [paper.pdf](<attachments/paper.pdf>)"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+// Rustic - workspaces of several sites built together with `rustic build --all`
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Override;
+use crate::{build, Cli, Command};
+
+/// The sites of a workspace, listed under `[[sites]]` in `rustic-workspace.toml`.
+#[derive(Debug, Deserialize)]
+pub struct Workspace {
+    /// Templates every site falls back to, relative to the workspace file
+    pub theme_dir: Option<PathBuf>,
+    /// Data files every site falls back to, relative to the workspace file
+    pub shared_data_dir: Option<PathBuf>,
+    pub sites: Vec<SiteEntry>,
+}
+
+/// One site of a workspace; its paths are relative to its `root`, as in a single-site project.
+#[derive(Debug, Deserialize)]
+pub struct SiteEntry {
+    /// Directory holding the site's config, templates, static files and data
+    pub root: PathBuf,
+    #[serde(default = "default_config")]
+    pub config: PathBuf,
+    #[serde(default = "default_input_dir")]
+    pub input_dir: PathBuf,
+    #[serde(default = "default_out_dir")]
+    pub out_dir: PathBuf,
+}
+
+fn default_config() -> PathBuf {
+    PathBuf::from("rustic.toml")
+}
+
+fn default_input_dir() -> PathBuf {
+    PathBuf::from("content")
+}
+
+fn default_out_dir() -> PathBuf {
+    PathBuf::from("public")
+}
+
+impl Workspace {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path).with_context(|| format!("Failed to read workspace file: {:?}", path))?;
+        let workspace: Self = toml::from_str(&raw).with_context(|| format!("Failed to parse workspace file: {:?}", path))?;
+        if workspace.sites.is_empty() {
+            bail!("{:?} lists no [[sites]]", path);
+        }
+        Ok(workspace)
+    }
+}
+
+/// Builds each site of the workspace in turn, from its own root directory.
+///
+/// The shared theme and data are passed to every site as `theme_dir` and `shared_data_dir`;
+/// `--profile`, `--set` and `--only` apply to all of them.
+pub fn build_all(cli: &Cli, workspace_path: &Path, only: &[String]) -> Result<()> {
+    let workspace = Workspace::load(workspace_path)?;
+    let base = workspace_path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let base = if base.as_os_str().is_empty() { env::current_dir()? } else { base.canonicalize()? };
+
+    let mut shared = Vec::new();
+    for (key, dir) in [("theme_dir", &workspace.theme_dir), ("shared_data_dir", &workspace.shared_data_dir)] {
+        if let Some(dir) = dir {
+            let dir = base.join(dir);
+            let value = toml::Value::String(dir.to_string_lossy().into_owned());
+            shared.push(format!("{}={}", key, value).parse::<Override>().map_err(anyhow::Error::msg)?);
+        }
+    }
+
+    let started_in = env::current_dir()?;
+    for site in &workspace.sites {
+        let root = base.join(&site.root);
+        println!("Building site in {:?}", root);
+        // Sites find templates/, static/ and data/ relative to the working directory
+        env::set_current_dir(&root).with_context(|| format!("Failed to enter site directory: {:?}", root))?;
+        let site_cli = Cli {
+            command: Some(Command::Build { all: false, workspace: workspace_path.to_path_buf(), only: only.to_vec() }),
+            input_dir: site.input_dir.clone(),
+            out_dir: site.out_dir.clone(),
+            config: site.config.clone(),
+            profile: cli.profile.clone(),
+            overrides: shared.iter().chain(&cli.overrides).cloned().collect(),
+            timings: cli.timings,
+            unused_templates: cli.unused_templates,
+            archive: None,
+            dump_context: cli.dump_context.as_ref().map(|dir| started_in.join(dir).join(&site.root)),
+            dry_run: cli.dry_run,
+        };
+        let result = build(&site_cli).with_context(|| format!("Failed to build the site in {:?}", root));
+        env::set_current_dir(&started_in)?;
+        result?;
+    }
+    println!("✅ Built {} sites", workspace.sites.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_defaults() {
+        // Act
+        let workspace: Workspace = toml::from_str(
+            "theme_dir = \"themes/corporate\"\n[[sites]]\nroot = \"sites/blog\"\n[[sites]]\nroot = \"sites/docs\"\nout_dir = \"dist\"\n",
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(workspace.theme_dir, Some(PathBuf::from("themes/corporate")));
+        assert_eq!(workspace.sites[0].config, PathBuf::from("rustic.toml"));
+        assert_eq!(workspace.sites[0].out_dir, PathBuf::from("public"));
+        assert_eq!(workspace.sites[1].out_dir, PathBuf::from("dist"));
+    }
+}
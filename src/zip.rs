@@ -0,0 +1,109 @@
+// Rustic - minimal ZIP writer for exports, with fixed timestamps so output is reproducible
+
+use anyhow::{bail, Result};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// MS-DOS date of 1980-01-01, the earliest a ZIP entry can carry.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// A file already written, remembered for the central directory.
+struct Entry {
+    name: String,
+    crc: u32,
+    method: u16,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Writes ZIP archives entry by entry, without ZIP64, so archives stay under 4 GiB.
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    entries: Vec<Entry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, offset: 0, entries: Vec::new() }
+    }
+
+    /// Adds a file, deflated unless `compress` is off, as EPUB needs for its `mimetype`.
+    pub fn add(&mut self, name: &str, data: &[u8], compress: bool) -> Result<()> {
+        let (method, body) = if compress {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            (8u16, encoder.finish()?)
+        } else {
+            (0u16, data.to_vec())
+        };
+        let (Ok(offset), Ok(size), Ok(compressed_size)) =
+            (u32::try_from(self.offset), u32::try_from(data.len()), u32::try_from(body.len()))
+        else {
+            bail!("{} doesn't fit in a ZIP archive without ZIP64", name);
+        };
+        let entry = Entry { name: name.to_string(), crc: crc32fast::hash(data), method, compressed_size, size, offset };
+
+        let mut header = Vec::new();
+        header.extend(0x04034b50u32.to_le_bytes());
+        header.extend(20u16.to_le_bytes()); // version needed
+        header.extend(0x0800u16.to_le_bytes()); // UTF-8 names
+        header.extend(entry.method.to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // time
+        header.extend(DOS_DATE.to_le_bytes());
+        header.extend(entry.crc.to_le_bytes());
+        header.extend(entry.compressed_size.to_le_bytes());
+        header.extend(entry.size.to_le_bytes());
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // extra field length
+        header.extend(name.as_bytes());
+        self.write(&header)?;
+        self.write(&body)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let start = self.offset;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend(0x02014b50u32.to_le_bytes());
+            directory.extend((0x0300u16 | 20).to_le_bytes()); // made by Unix, ZIP 2.0
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(0x0800u16.to_le_bytes());
+            directory.extend(entry.method.to_le_bytes());
+            directory.extend(0u16.to_le_bytes());
+            directory.extend(DOS_DATE.to_le_bytes());
+            directory.extend(entry.crc.to_le_bytes());
+            directory.extend(entry.compressed_size.to_le_bytes());
+            directory.extend(entry.size.to_le_bytes());
+            directory.extend((entry.name.len() as u16).to_le_bytes());
+            directory.extend([0u8; 8]); // extra and comment lengths, disk number, internal attributes
+            directory.extend((0o100644u32 << 16).to_le_bytes()); // external attributes: rw-r--r--
+            directory.extend(entry.offset.to_le_bytes());
+            directory.extend(entry.name.as_bytes());
+        }
+        self.write(&directory)?;
+
+        let count = u16::try_from(self.entries.len())?;
+        let mut end = Vec::new();
+        end.extend(0x06054b50u32.to_le_bytes());
+        end.extend([0u8; 4]); // disk numbers
+        end.extend(count.to_le_bytes());
+        end.extend(count.to_le_bytes());
+        end.extend(u32::try_from(directory.len())?.to_le_bytes());
+        end.extend(u32::try_from(start)?.to_le_bytes());
+        end.extend(0u16.to_le_bytes()); // comment length
+        self.write(&end)?;
+        Ok(self.out)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+}